@@ -0,0 +1,170 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Benchmarks for the parsing done at greeter startup
+//!
+//! Run with `cargo bench`. Before benchmarking, every parser is checked against a loose time budget, so that accidental
+//! algorithmic regressions fail loudly instead of only showing up as a slower boot.
+
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use criterion::Criterion;
+use regreet::cache::Cache;
+use regreet::config::{Config, SessionFilter};
+use regreet::sysutil::{NormalUser, SysUtil};
+
+/// Number of session desktop files created per session directory
+const NUM_SESSIONS: usize = 50;
+/// Number of users in the generated cache
+const NUM_CACHED_USERS: usize = 100;
+/// Number of runs used when checking a time budget
+const BUDGET_RUNS: u32 = 20;
+
+/// The sample config shipped with the greeter
+const SAMPLE_CONFIG: &str = include_str!("../regreet.sample.toml");
+
+/// Generate a `login.defs` file that resembles a distro-provided one, with the UID limits at the very end.
+fn login_defs() -> String {
+    let mut text = String::new();
+    for i in 0..100 {
+        text.push_str("#\n# Some documentation for the next option.\n#\n");
+        text.push_str(&format!("OPTION_{i}\t\tvalue\n"));
+    }
+    text.push_str("UID_MIN\t\t\t 1000\nUID_MAX\t\t\t60000\n");
+    text
+}
+
+/// Generate a cache file with the last sessions of many users.
+fn cache_toml() -> String {
+    let mut text = String::from("last_user = \"user0\"\n\n[user_to_last_sess]\n");
+    for i in 0..NUM_CACHED_USERS {
+        text.push_str(&format!("user{i} = \"Session {i}\"\n"));
+    }
+    text
+}
+
+/// X11 and Wayland session directories filled with desktop files, which are removed on drop
+struct SessionDirs {
+    root: PathBuf,
+    /// The `:`-separated list of session directories
+    dirs: String,
+}
+
+impl SessionDirs {
+    fn new() -> Self {
+        let root = std::env::temp_dir().join(format!("regreet-bench-{}", std::process::id()));
+        let mut dirs = Vec::new();
+
+        for kind in ["xsessions", "wayland-sessions"] {
+            let dir = root.join(kind);
+            create_dir_all(&dir).expect("Couldn't create session directory");
+            for i in 0..NUM_SESSIONS {
+                write(
+                    dir.join(format!("session-{i}.desktop")),
+                    format!(
+                        "[Desktop Entry]\nName=Session {i}\nComment=A session\nExec=session-{i} --flag\n\
+                         Type=Application\nDesktopNames=Session{i}\n"
+                    ),
+                )
+                .expect("Couldn't write session desktop file");
+            }
+            dirs.push(dir.display().to_string());
+        }
+
+        Self {
+            root,
+            dirs: dirs.join(":"),
+        }
+    }
+}
+
+impl Drop for SessionDirs {
+    fn drop(&mut self) {
+        if let Err(err) = remove_dir_all(&self.root) {
+            eprintln!("Couldn't remove '{}': {err}", self.root.display());
+        }
+    }
+}
+
+fn x11_prefix() -> Vec<String> {
+    vec!["startx".into(), "/usr/bin/env".into()]
+}
+
+/// Panic if a single run of `f` takes longer than `budget` on average.
+fn check_budget(name: &str, budget: Duration, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..BUDGET_RUNS {
+        f();
+    }
+    let per_run = start.elapsed() / BUDGET_RUNS;
+
+    assert!(
+        per_run <= budget,
+        "{name} took {per_run:?} per run, exceeding its budget of {budget:?}"
+    );
+    println!("{name}: {per_run:?} per run (budget: {budget:?})");
+}
+
+fn check_budgets(sessions: &str) {
+    let login_defs = login_defs();
+    let cache = cache_toml();
+    let prefix = x11_prefix();
+    let filter = SessionFilter::default();
+
+    check_budget("parse_login_defs", Duration::from_millis(1), || {
+        black_box(NormalUser::parse_login_defs(black_box(&login_defs)));
+    });
+    check_budget("scan_sessions", Duration::from_millis(100), || {
+        black_box(SysUtil::scan_sessions(black_box(sessions), &prefix, &filter).unwrap());
+    });
+    check_budget("deserialize_cache", Duration::from_millis(5), || {
+        black_box(toml::from_str::<Cache>(black_box(&cache)).unwrap());
+    });
+    check_budget("deserialize_config", Duration::from_millis(5), || {
+        black_box(toml::from_str::<Config>(black_box(SAMPLE_CONFIG)).unwrap());
+    });
+}
+
+fn bench_login_defs(c: &mut Criterion) {
+    let text = login_defs();
+    c.bench_function("parse_login_defs", |b| {
+        b.iter(|| NormalUser::parse_login_defs(black_box(&text)))
+    });
+}
+
+fn bench_sessions(c: &mut Criterion, sessions: &str) {
+    let prefix = x11_prefix();
+    let filter = SessionFilter::default();
+    c.bench_function("scan_sessions", |b| {
+        b.iter(|| SysUtil::scan_sessions(black_box(sessions), &prefix, &filter).unwrap())
+    });
+}
+
+fn bench_cache(c: &mut Criterion) {
+    let text = cache_toml();
+    c.bench_function("deserialize_cache", |b| {
+        b.iter(|| toml::from_str::<Cache>(black_box(&text)).unwrap())
+    });
+}
+
+fn bench_config(c: &mut Criterion) {
+    c.bench_function("deserialize_config", |b| {
+        b.iter(|| toml::from_str::<Config>(black_box(SAMPLE_CONFIG)).unwrap())
+    });
+}
+
+fn main() {
+    let sessions = SessionDirs::new();
+    check_budgets(&sessions.dirs);
+
+    let mut criterion = Criterion::default().configure_from_args();
+    bench_login_defs(&mut criterion);
+    bench_sessions(&mut criterion, &sessions.dirs);
+    bench_cache(&mut criterion);
+    bench_config(&mut criterion);
+    criterion.final_summary();
+}
@@ -0,0 +1,26 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Build script that records when the greeter was built
+//!
+//! The system clock can't be set before this, so the greeter warns if it is. For reproducible builds, the time is taken
+//! from `SOURCE_DATE_EPOCH` if it's set.
+
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let build_time = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs())
+        });
+    println!("cargo:rustc-env=REGREET_BUILD_TIME={build_time}");
+}
@@ -0,0 +1,15 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Fuzz the deserialization of the greeter config, which is read at boot
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regreet::config::Config;
+
+fuzz_target!(|text: &str| {
+    // Errors are fine, since the greeter falls back to the default config. Panics are not.
+    let _ = toml::from_str::<Config>(text);
+});
@@ -0,0 +1,22 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Fuzz the session desktop file parser, which reads system files at boot
+
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+use regreet::sysutil::{SessionInfo, SessionType};
+
+fuzz_target!(|text: &str| {
+    let prefix = ["startx".to_string(), "/usr/bin/env".to_string()];
+    SessionInfo::parse(
+        text,
+        Path::new("/usr/share/xsessions/fuzz.desktop"),
+        SessionType::X11,
+        Some(&prefix),
+    );
+});
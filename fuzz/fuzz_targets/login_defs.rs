@@ -0,0 +1,14 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Fuzz the `login.defs` parser, which reads a system file at boot
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regreet::sysutil::NormalUser;
+
+fuzz_target!(|text: &str| {
+    NormalUser::parse_login_defs(text);
+});
@@ -0,0 +1,213 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Authentication of the local admin for the settings panel
+//!
+//! The settings panel changes what everyone sees at the greeter, so it's only shown after an admin user (or a member
+//! of an admin group) from the config has authenticated. This goes through greetd like a login, except that the
+//! session is cancelled instead of started.
+
+use greetd_ipc::{codec::Error as GreetdError, AuthMessageType, Response};
+
+use crate::client::GreetdClient;
+use crate::config::{Config, PanelValues};
+use crate::sysutil::user_in_groups;
+use crate::tomlutils::TomlFileError;
+
+/// Why the settings panel couldn't be unlocked, or its settings couldn't be saved
+#[derive(thiserror::Error, Debug)]
+pub enum AdminError {
+    #[error("'{0}' isn't allowed to change the settings")]
+    NotAdmin(String),
+    #[error("Authentication failed: {0}")]
+    AuthFailed(String),
+    #[error("Only authentication with a single password is supported")]
+    UnsupportedPrompt,
+    #[error("Couldn't communicate with greetd: {0}")]
+    Greetd(#[from] GreetdError),
+    #[error("No admin has authenticated")]
+    NotAuthenticated,
+    #[error("The greeter is read-only")]
+    ReadOnly,
+    #[error("Couldn't save the settings: {0}")]
+    Save(#[from] TomlFileError),
+}
+
+/// Check whether the user is allowed to change the settings from the settings panel.
+pub fn is_admin(config: &Config, username: &str) -> bool {
+    let settings = config.get_settings_panel();
+    settings.admin_users.iter().any(|user| user == username)
+        || user_in_groups(username, &settings.admin_groups)
+}
+
+/// Admin who has authenticated for the settings panel, if any
+#[derive(Default)]
+pub struct AdminAuth {
+    admin: Option<String>,
+}
+
+impl AdminAuth {
+    /// Authenticate an admin through greetd.
+    ///
+    /// The password is sent for the first secret prompt, and info and error messages are acknowledged. Other prompts,
+    /// eg. for a second factor, aren't supported. The greetd session is cancelled afterwards, so nobody is logged in.
+    pub async fn authenticate(
+        &mut self,
+        client: &mut GreetdClient,
+        config: &Config,
+        username: &str,
+        password: String,
+    ) -> Result<(), AdminError> {
+        self.lock();
+        if !is_admin(config, username) {
+            return Err(AdminError::NotAdmin(username.to_string()));
+        }
+
+        let result = check_password(client, username, password).await;
+        if let Err(err) = client.cancel_session().await {
+            warn!(error = %err, "Couldn't cancel the greetd session of the admin");
+        }
+        result?;
+
+        info!(
+            user = username,
+            "Admin authenticated for the settings panel"
+        );
+        self.admin = Some(username.to_string());
+        Ok(())
+    }
+
+    /// Forget the authenticated admin, eg. once the settings panel is closed.
+    pub fn lock(&mut self) {
+        self.admin = None;
+    }
+
+    /// Save the settings from the settings panel, if an admin has authenticated.
+    ///
+    /// Nothing is written in read-only mode, which includes demo mode.
+    pub fn save(
+        &self,
+        config: &Config,
+        values: PanelValues,
+        read_only: bool,
+    ) -> Result<(), AdminError> {
+        let admin = self.admin.as_deref().ok_or(AdminError::NotAuthenticated)?;
+        if read_only {
+            return Err(AdminError::ReadOnly);
+        }
+        info!(user = admin, "Saving the settings from the settings panel");
+        config.save_panel_values(values)?;
+        Ok(())
+    }
+}
+
+/// Go through the authentication of a greetd session with the password.
+async fn check_password(
+    client: &mut GreetdClient,
+    username: &str,
+    password: String,
+) -> Result<(), AdminError> {
+    let mut password = Some(password);
+    let mut response = client.create_session(username).await?;
+    loop {
+        response = match response {
+            Response::Success => return Ok(()),
+            Response::Error { description, .. } => return Err(AdminError::AuthFailed(description)),
+            Response::AuthMessage {
+                auth_message_type: AuthMessageType::Secret,
+                ..
+            } => {
+                let password = password.take().ok_or(AdminError::UnsupportedPrompt)?;
+                client.send_auth_response(Some(password)).await?
+            }
+            Response::AuthMessage {
+                auth_message_type: AuthMessageType::Info | AuthMessageType::Error,
+                ..
+            } => client.send_auth_response(None).await?,
+            Response::AuthMessage {
+                auth_message_type: AuthMessageType::Visible,
+                ..
+            } => return Err(AdminError::UnsupportedPrompt),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod AdminAuth {
+        use super::super::*;
+        use crate::testutil::TempDir;
+
+        /// Config whose settings panel saves to a drop-in in the directory
+        fn config(dir: &TempDir) -> Config {
+            toml::from_str(&format!(
+                "[settings_panel]\nenabled = true\nadmin_users = [\"admin\"]\npath = {:?}",
+                dir.join("settings.toml").display().to_string()
+            ))
+            .expect("Invalid config")
+        }
+
+        fn values() -> PanelValues {
+            PanelValues {
+                background: None,
+                greeting_msg: "Welcome to the lab".to_string(),
+                gtk_theme: None,
+            }
+        }
+
+        #[test]
+        fn save_without_auth() {
+            let dir = TempDir::new("admin-locked");
+            let result = AdminAuth::default().save(&config(&dir), values(), false);
+
+            assert!(matches!(result, Err(AdminError::NotAuthenticated)));
+            assert!(!dir.join("settings.toml").exists());
+        }
+
+        #[test]
+        fn save_after_lock() {
+            let dir = TempDir::new("admin-relocked");
+            let mut auth = AdminAuth {
+                admin: Some("admin".to_string()),
+            };
+            auth.lock();
+            let result = auth.save(&config(&dir), values(), false);
+
+            assert!(matches!(result, Err(AdminError::NotAuthenticated)));
+            assert!(!dir.join("settings.toml").exists());
+        }
+
+        #[test]
+        fn save_read_only() {
+            let dir = TempDir::new("admin-read-only");
+            let auth = AdminAuth {
+                admin: Some("admin".to_string()),
+            };
+            let result = auth.save(&config(&dir), values(), true);
+
+            assert!(matches!(result, Err(AdminError::ReadOnly)));
+            assert!(!dir.join("settings.toml").exists());
+        }
+
+        #[test]
+        fn save_authenticated() {
+            let dir = TempDir::new("admin-unlocked");
+            let auth = AdminAuth {
+                admin: Some("admin".to_string()),
+            };
+            let result = auth.save(&config(&dir), values(), false);
+
+            assert!(result.is_ok());
+            assert!(dir.join("settings.toml").exists());
+        }
+
+        #[test_case("admin" => true; "admin user")]
+        #[test_case("guest" => false; "other user")]
+        fn is_admin(username: &str) -> bool {
+            let dir = TempDir::new("admin-users");
+            super::super::is_admin(&config(&dir), username)
+        }
+    }
+}
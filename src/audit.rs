@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Audit log of login attempts
+//!
+//! Unlike the debug logs, this only has one line for each finished login attempt, with the time, the username, the
+//! session and the outcome. The file is only ever appended to, and it's rotated once it grows too large.
+
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::{Result as IOResult, Write};
+use std::path::Path;
+
+use file_rotate::{compression::Compression, suffix::AppendCount, ContentLimit, FileRotate};
+use jiff::Timestamp;
+
+/// Number of rotated audit logs kept besides the current one
+const MAX_AUDIT_FILES: usize = 5;
+/// Size in bytes after which the audit log is rotated
+const MAX_AUDIT_SIZE: usize = 1024 * 1024;
+/// Permissions of the audit logs, which are only readable by the greeter's user, since they contain usernames
+const AUDIT_FILE_MODE: u32 = 0o600;
+
+/// How a login attempt ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// The user was authenticated, so the session is started.
+    Success,
+    /// The authentication failed, eg. due to a wrong password.
+    AuthFailure,
+    /// greetd reported an error other than a failed authentication.
+    Error,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::AuthFailure => "auth-failure",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Format a line of the audit log.
+///
+/// The username and the session are quoted and escaped, since they can be typed in manually and mustn't be able to
+/// forge other lines.
+fn format_entry(
+    time: Timestamp,
+    username: &str,
+    session: Option<&str>,
+    outcome: Outcome,
+) -> String {
+    let session = if let Some(session) = session {
+        format!("{session:?}")
+    } else {
+        "-".to_string()
+    };
+    format!(
+        "{time} user={username:?} session={session} outcome={}",
+        outcome.as_str()
+    )
+}
+
+/// Rotated file that login attempts are appended to
+pub struct AuditLog {
+    file: FileRotate<AppendCount>,
+}
+
+impl AuditLog {
+    /// Open the audit log, creating it and its directory if needed.
+    pub fn open(path: &Path) -> IOResult<Self> {
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        // `FileRotate` silently fails if the file can't be written to, so check that first.
+        OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: FileRotate::new(
+                path,
+                AppendCount::new(MAX_AUDIT_FILES),
+                ContentLimit::Bytes(MAX_AUDIT_SIZE),
+                Compression::None,
+                Some(AUDIT_FILE_MODE),
+            ),
+        })
+    }
+
+    /// Append a login attempt to the log.
+    pub fn record(
+        &mut self,
+        username: &str,
+        session: Option<&str>,
+        outcome: Outcome,
+    ) -> IOResult<()> {
+        let entry = format_entry(Timestamp::now(), username, session, outcome);
+        writeln!(self.file, "{entry}")?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test_case("alice", Some("Sway"), Outcome::Success
+        => "1970-01-01T00:00:00Z user=\"alice\" session=\"Sway\" outcome=success"; "selected session")]
+    #[test_case("bob", None, Outcome::AuthFailure
+        => "1970-01-01T00:00:00Z user=\"bob\" session=- outcome=auth-failure"; "no session")]
+    #[test_case("eve\n1970-01-01T00:00:00Z user=\"root\"", None, Outcome::Error
+        => "1970-01-01T00:00:00Z user=\"eve\\n1970-01-01T00:00:00Z user=\\\"root\\\"\" session=- outcome=error";
+        "forged line")]
+    fn format_entry(username: &str, session: Option<&str>, outcome: Outcome) -> String {
+        super::format_entry(Timestamp::UNIX_EPOCH, username, session, outcome)
+    }
+
+    #[test]
+    fn record() {
+        let dir = TempDir::new("audit");
+        let path = dir.join("audit.log");
+        let mut audit_log = AuditLog::open(&path).expect("Couldn't open audit log");
+        audit_log
+            .record("alice", Some("Sway"), Outcome::AuthFailure)
+            .expect("Couldn't write audit log");
+        audit_log
+            .record("alice", Some("Sway"), Outcome::Success)
+            .expect("Couldn't write audit log");
+        let text = read_to_string(&path).expect("Couldn't read audit log");
+        let outcomes: Vec<_> = text
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(outcomes, ["outcome=auth-failure", "outcome=success"]);
+    }
+}
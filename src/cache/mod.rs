@@ -10,15 +10,21 @@ use std::fs::{create_dir_all, write};
 use std::num::NonZeroUsize;
 use std::path::Path;
 
+use jiff::Timestamp;
 use serde::{Deserialize, Serialize};
 
 use self::lru::LruCache;
 use crate::constants::CACHE_PATH;
 use crate::tomlutils::{load_toml, TomlFileResult};
 
-/// Limit to the size of the user to last-used session mapping.
+/// Limit to the size of the mappings from users to their last-used session and last login.
 const CACHE_LIMIT: usize = 100;
 
+/// Seconds in a minute, an hour and a day
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
 /// Holds info needed to persist between logins
 #[derive(Deserialize, Serialize)]
 pub struct Cache {
@@ -26,30 +32,69 @@ pub struct Cache {
     last_user: Option<String>,
     /// The last-used session for each user
     user_to_last_sess: LruCache<String, String>,
+    /// The time of the last login of each user, in seconds since the UNIX epoch
+    #[serde(default = "new_user_map")]
+    user_to_last_login: LruCache<String, i64>,
+    /// Whether the username was last entered manually instead of chosen from the drop-down
+    #[serde(default)]
+    manual_user_mode: bool,
+    /// Whether the session command was last entered manually instead of chosen from the drop-down
+    #[serde(default)]
+    manual_sess_mode: bool,
+    /// Whether the high-contrast theme was turned on at the greeter
+    #[serde(default)]
+    high_contrast: bool,
+    /// Whether saving to disk is disabled, eg. in demo mode
+    #[serde(skip)]
+    read_only: bool,
 }
 
 impl Default for Cache {
     fn default() -> Self {
         Self {
             last_user: None,
-            user_to_last_sess: LruCache::new(CACHE_LIMIT),
+            user_to_last_sess: new_user_map(),
+            user_to_last_login: new_user_map(),
+            manual_user_mode: false,
+            manual_sess_mode: false,
+            high_contrast: false,
+            read_only: false,
         }
     }
 }
 
+/// Create a mapping from users to something, which is limited to `CACHE_LIMIT` users.
+fn new_user_map<V>() -> LruCache<String, V> {
+    LruCache::new(CACHE_LIMIT)
+}
+
 impl Cache {
     /// Load the cache file from disk.
     pub fn new() -> Self {
         let mut cache: Self = load_toml(CACHE_PATH);
-        // Make sure that the LRU can contain the needed amount of mappings.
+        // Make sure that the LRUs can contain the needed amount of mappings.
+        let limit = NonZeroUsize::new(CACHE_LIMIT).expect("Cache limit cannot be zero");
+        cache.user_to_last_sess.resize(limit);
+        cache.user_to_last_login.resize(limit);
         cache
-            .user_to_last_sess
-            .resize(NonZeroUsize::new(CACHE_LIMIT).expect("Cache limit cannot be zero"));
+    }
+
+    /// Load the cache file from disk, without ever saving it back.
+    pub fn read_only() -> Self {
+        let mut cache = Self::new();
+        cache.read_only = true;
         cache
     }
 
     /// Save the cache file to disk.
+    ///
+    /// This does nothing if the cache is read-only.
     pub fn save(&self) -> TomlFileResult<()> {
+        if self.read_only {
+            info!("Not saving read-only cache to disk");
+            return Ok(());
+        }
+
         let cache_path = Path::new(CACHE_PATH);
         if !cache_path.exists() {
             // Create the cache directory.
@@ -74,6 +119,43 @@ impl Cache {
         self.user_to_last_sess.get(user).map(String::as_str)
     }
 
+    /// Get the time of the last login of the given user.
+    pub fn get_last_login(&self, user: &str) -> Option<Timestamp> {
+        self.user_to_last_login
+            .peek(user)
+            .and_then(|&seconds| Timestamp::from_second(seconds).ok())
+    }
+
+    /// Check whether the given user has logged in before, as far as the cache knows.
+    ///
+    /// Only a limited number of users are kept, and older caches don't have the last logins.
+    pub fn has_logged_in(&self, user: &str) -> bool {
+        self.last_user.as_deref() == Some(user)
+            || self.user_to_last_sess.peek(user).is_some()
+            || self.user_to_last_login.peek(user).is_some()
+    }
+
+    /// Get whether the username and the session command were last entered manually.
+    pub fn get_manual_modes(&self) -> (bool, bool) {
+        (self.manual_user_mode, self.manual_sess_mode)
+    }
+
+    /// Set whether the username and the session command were last entered manually.
+    pub fn set_manual_modes(&mut self, manual_user_mode: bool, manual_sess_mode: bool) {
+        self.manual_user_mode = manual_user_mode;
+        self.manual_sess_mode = manual_sess_mode;
+    }
+
+    /// Get whether the high-contrast theme was turned on at the greeter.
+    pub fn get_high_contrast(&self) -> bool {
+        self.high_contrast
+    }
+
+    /// Set whether the high-contrast theme is turned on at the greeter.
+    pub fn set_high_contrast(&mut self, high_contrast: bool) {
+        self.high_contrast = high_contrast;
+    }
+
     /// Set the last user to login.
     pub fn set_last_user(&mut self, user: &str) {
         self.last_user = Some(String::from(user));
@@ -84,4 +166,44 @@ impl Cache {
         self.user_to_last_sess
             .push(String::from(user), String::from(session));
     }
+
+    /// Set the time of the last login of the given user.
+    pub fn set_last_login(&mut self, user: &str, time: Timestamp) {
+        self.user_to_last_login
+            .push(String::from(user), time.as_second());
+    }
+}
+
+/// Describe how long ago something happened, given the elapsed seconds, eg. "2 days ago".
+pub fn time_ago(seconds: i64) -> String {
+    let (count, unit) = if seconds >= DAY {
+        (seconds / DAY, "day")
+    } else if seconds >= HOUR {
+        (seconds / HOUR, "hour")
+    } else if seconds >= MINUTE {
+        (seconds / MINUTE, "minute")
+    } else {
+        // The clock might also have been changed since then.
+        return "just now".to_string();
+    };
+
+    if count == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{count} {unit}s ago")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case(-5 => "just now"; "in the future")]
+    #[test_case(59 => "just now"; "seconds")]
+    #[test_case(60 => "1 minute ago"; "one minute")]
+    #[test_case(2 * HOUR + 59 * MINUTE => "2 hours ago"; "hours")]
+    #[test_case(3 * DAY => "3 days ago"; "days")]
+    fn time_ago(seconds: i64) -> String {
+        super::time_ago(seconds)
+    }
 }
@@ -3,28 +3,45 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 //! Client that communicates with greetd
+//!
+//! By default, the client connects to greetd over the UNIX socket given by the `GREETD_SOCK` environment variable.
+//! Other transports, such as TCP, vsock or a proxy, can be used by passing any type that implements
+//! [`GreetdTransport`] to [`GreetdClient::with_transport`], or to the `transport` field of
+//! [`GreeterInit`](crate::gui::GreeterInit).
 
 use std::env;
 use std::io::Result as IOResult;
+use std::time::Duration;
 
 use greetd_ipc::{
     codec::{Error as GreetdError, TokioCodec},
-    AuthMessageType, ErrorType, Request, Response,
+    Request, Response,
 };
-use tokio::net::UnixStream;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::UnixStream,
+    time::sleep,
+};
+
+use crate::demo::{DemoFlow, DemoGreetd};
 
 /// Environment variable containing the path to the greetd socket
-const GREETD_SOCK_ENV_VAR: &str = "GREETD_SOCK";
+pub(crate) const GREETD_SOCK_ENV_VAR: &str = "GREETD_SOCK";
 
-/// Demo mode credentials
-const DEMO_AUTH_MSG_OPT: &str = "One-Time Password:";
-const DEMO_AUTH_MSG_PASSWD: &str = "Password:";
-const DEMO_AUTH_MSG_ERROR: &str = "pam_authenticate: AUTH_ERR";
-const DEMO_OTP: &str = "0248";
-const DEMO_PASSWD: &str = "pass";
+/// Time taken by the demo greetd to respond to messages that need no input, eg. to "scan" a fingerprint
+const DEMO_MESSAGE_DELAY: Duration = Duration::from_secs(2);
 
 pub type GreetdResult = Result<Response, GreetdError>;
 
+/// A bidirectional byte stream to greetd
+///
+/// Requests and responses are sent over it using the greetd IPC protocol. This is implemented for every async stream,
+/// eg. `tokio::net::UnixStream` and `tokio::net::TcpStream`, so custom transports only need to implement `AsyncRead`
+/// and `AsyncWrite`.
+pub trait GreetdTransport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> GreetdTransport for T {}
+
 /// The authentication status of the current greetd session
 #[derive(Clone)]
 pub enum AuthStatus {
@@ -33,36 +50,45 @@ pub enum AuthStatus {
     Done,
 }
 
-/// Client that uses UNIX sockets to communicate with greetd
+/// Client that communicates with greetd
 pub struct GreetdClient {
-    /// Socket to communicate with greetd
-    socket: Option<UnixStream>,
+    /// Transport to communicate with greetd
+    socket: Option<Box<dyn GreetdTransport>>,
+    /// Fake greetd used instead of the socket in demo mode
+    demo: Option<DemoGreetd>,
     /// Current authentication status
     auth_status: AuthStatus,
 }
 
 impl GreetdClient {
     /// Initialize the socket to communicate with greetd.
-    pub async fn new(demo: bool) -> IOResult<Self> {
-        let socket: Option<UnixStream> = if demo {
-            warn!(
-                "Run as demo: [otp: {}, password: {}]",
-                DEMO_OTP, DEMO_PASSWD
-            );
+    pub async fn new(demo: Option<DemoFlow>) -> IOResult<Self> {
+        let demo = demo.map(DemoGreetd::new);
+        let socket: Option<Box<dyn GreetdTransport>> = if demo.is_some() {
             None
         } else {
             let sock_path = env::var(GREETD_SOCK_ENV_VAR).unwrap_or_else(|_| {
                 panic!("Missing environment variable '{GREETD_SOCK_ENV_VAR}'. Is greetd running?",)
             });
-            Some(UnixStream::connect(sock_path).await?)
+            Some(Box::new(UnixStream::connect(sock_path).await?))
         };
 
         Ok(Self {
             socket,
+            demo,
             auth_status: AuthStatus::NotStarted,
         })
     }
 
+    /// Communicate with greetd over the given transport, instead of its UNIX socket.
+    pub fn with_transport(transport: Box<dyn GreetdTransport>) -> Self {
+        Self {
+            socket: Some(transport),
+            demo: None,
+            auth_status: AuthStatus::NotStarted,
+        }
+    }
+
     /// Initialize a greetd session.
     pub async fn create_session(&mut self, username: &str) -> GreetdResult {
         info!("Creating session for username: {username}");
@@ -74,10 +100,7 @@ impl GreetdClient {
             msg.write_to(socket).await?;
             Response::read_from(socket).await?
         } else {
-            Response::AuthMessage {
-                auth_message_type: AuthMessageType::Secret,
-                auth_message: DEMO_AUTH_MSG_OPT.to_string(),
-            }
+            self.demo_greetd().create_session()
         };
 
         match resp {
@@ -103,17 +126,10 @@ impl GreetdClient {
             msg.write_to(socket).await?;
             Response::read_from(socket).await?
         } else {
-            match input.as_deref() {
-                Some(DEMO_OTP) => Response::AuthMessage {
-                    auth_message_type: AuthMessageType::Secret,
-                    auth_message: DEMO_AUTH_MSG_PASSWD.to_string(),
-                },
-                Some(DEMO_PASSWD) => Response::Success,
-                _ => Response::Error {
-                    error_type: ErrorType::AuthError,
-                    description: DEMO_AUTH_MSG_ERROR.to_string(),
-                },
+            if self.demo_greetd().shows_message() {
+                sleep(DEMO_MESSAGE_DELAY).await;
             }
+            self.demo_greetd().send_auth_response(input.as_deref())
         };
 
         match resp {
@@ -141,6 +157,7 @@ impl GreetdClient {
         info!("Starting greetd session with command: {command:?}");
 
         if self.socket.is_none() {
+            warn!("demo: skip starting session with env: {environment:?}");
             return Ok(Response::Success);
         }
 
@@ -183,4 +200,11 @@ impl GreetdClient {
     pub fn get_auth_status(&self) -> &AuthStatus {
         &self.auth_status
     }
+
+    /// Get the fake greetd, which is used when there's no socket.
+    fn demo_greetd(&mut self) -> &mut DemoGreetd {
+        self.demo
+            .as_mut()
+            .expect("The demo greetd must be set when there's no socket")
+    }
 }
@@ -4,155 +4,2630 @@
 
 //! Configuration for the greeter
 
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::path::Path;
+use std::error::Error;
+use std::fs::{create_dir_all, read_dir, read_to_string, write};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
+use regex::Regex;
+use relm4::gtk;
 use serde::{Deserialize, Serialize};
 
-use crate::constants::{GREETING_MSG, POWEROFF_CMD, REBOOT_CMD, X11_CMD_PREFIX};
+use crate::constants::{
+    FIRST_LOGIN_MSG, GREETING_MSG, HIBERNATE_CMD, POWEROFF_CMD, REBOOT_CMD, SETTINGS_DROP_IN_PATH,
+    SUSPEND_CMD, UNCLEAN_SHUTDOWN_MARKER, UNCLEAN_SHUTDOWN_MSG, X11_CMD_PREFIX,
+};
 use crate::gui::widget::clock::ClockConfig;
-use crate::tomlutils::load_toml;
+use crate::notify::{Level, SinkConfig};
+use crate::time_format::{HourCycle, TimeFormatter};
+use crate::tomlutils::{load_raw_toml, load_toml, TomlFileResult};
+
+/// Environment variable that xkbcommon reads the default XKB layouts from
+const XKB_LAYOUT_ENV_VAR: &str = "XKB_DEFAULT_LAYOUT";
+/// Environment variable that xkbcommon reads the default XKB options from
+const XKB_OPTIONS_ENV_VAR: &str = "XKB_DEFAULT_OPTIONS";
+
+/// The toolkit used to style the greeter
+#[derive(Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Toolkit {
+    /// Plain GTK, styled by the GTK theme
+    #[default]
+    Gtk,
+    /// libadwaita, which shows notifications as toasts
+    Adwaita,
+}
+
+/// How much space there is between and around the widgets
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+    /// Less space, eg. for small screens
+    Compact,
+    #[default]
+    Comfortable,
+}
+
+impl Density {
+    /// Get the spacing in pixels between the widgets, which is also used for their margins.
+    pub const fn spacing(self) -> i32 {
+        match self {
+            Self::Compact => 6,
+            Self::Comfortable => 15,
+        }
+    }
+}
+
+/// Where the login box is shown on the screen
+///
+/// Left and right are swapped for right-to-left layouts, like the rest of the layout.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PanelPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl PanelPosition {
+    /// Get the horizontal alignment of the login box.
+    pub fn halign(self) -> gtk::Align {
+        match self {
+            Self::TopLeft | Self::Left | Self::BottomLeft => gtk::Align::Start,
+            Self::Top | Self::Center | Self::Bottom => gtk::Align::Center,
+            Self::TopRight | Self::Right | Self::BottomRight => gtk::Align::End,
+        }
+    }
+
+    /// Get the vertical alignment of the login box.
+    pub fn valign(self) -> gtk::Align {
+        match self {
+            Self::TopLeft | Self::Top | Self::TopRight => gtk::Align::Start,
+            Self::Left | Self::Center | Self::Right => gtk::Align::Center,
+            Self::BottomLeft | Self::Bottom | Self::BottomRight => gtk::Align::End,
+        }
+    }
+}
 
 #[derive(Deserialize, Serialize)]
 pub struct AppearanceSettings {
     #[serde(default = "default_greeting_msg")]
     pub greeting_msg: String,
+    /// Whether the greeting message is Pango markup, eg. with links
+    #[serde(default)]
+    pub greeting_markup: bool,
+    #[serde(default)]
+    pub toolkit: Toolkit,
+    /// Mirror the layout horizontally, eg. putting the labels to the right of the entries
+    #[serde(default)]
+    pub mirror_layout: bool,
+    /// GTK theme name, which overrides `theme_name` in the `[GTK]` section
+    #[serde(default)]
+    pub gtk_theme: Option<String>,
+    /// Whether to prefer the dark theme, which overrides `application_prefer_dark_theme` in the `[GTK]` section
+    #[serde(default)]
+    pub prefer_dark: Option<bool>,
+    /// Icon theme name, which overrides `icon_theme_name` in the `[GTK]` section
+    #[serde(default)]
+    pub icon_theme: Option<String>,
+    /// Cursor theme name, which overrides `cursor_theme_name` in the `[GTK]` section
+    #[serde(default)]
+    pub cursor_theme: Option<String>,
+    /// Cursor size in pixels, eg. to match the cursor of the compositor
+    #[serde(default)]
+    pub cursor_size: Option<i32>,
+    /// Font name and size, which overrides `font_name` in the `[GTK]` section
+    #[serde(default)]
+    pub font: Option<String>,
+    /// Scale of the greeting message's font, relative to the font size
+    #[serde(default = "default_greeting_scale")]
+    pub greeting_scale: f64,
+    /// Radius in pixels for blurring the background behind the login box
+    #[serde(default)]
+    pub panel_blur: f64,
+    /// Opacity of the black drawn over the background behind the login box
+    #[serde(default)]
+    pub panel_darken: f32,
+    /// Opacity of the background of the login box and the clock, eg. 0.5 for translucent panels
+    #[serde(default)]
+    pub panel_opacity: Option<f64>,
+    /// Radius in pixels of the corners of the login box and the clock
+    #[serde(default)]
+    pub panel_radius: Option<u32>,
+    /// Where the login box is shown on the screen
+    #[serde(default)]
+    pub panel_position: PanelPosition,
+    /// Distance in pixels between the login box and the edges of the screen
+    #[serde(default)]
+    pub panel_margin: i32,
+    /// How much space there is between and around the widgets
+    #[serde(default)]
+    pub density: Density,
+    /// Different themes and backgrounds by day and by night
+    #[serde(default)]
+    pub schedule: Option<ThemeSchedule>,
+    /// Whether GTK animates widgets, eg. the transitions when revealing prompts
+    #[serde(default = "default_animations")]
+    pub animations: bool,
+    /// Locale of the greeter, set as `LANG` and `LC_TIME`, eg. "de_DE.UTF-8"
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Format of the times of day shown at the greeter, which also overrides `format` in the `[widget.clock]` section
+    #[serde(default)]
+    pub time_format: Option<String>,
+    /// Whether times of day are shown with a 12-hour or a 24-hour clock, unless a time format is set
+    #[serde(default)]
+    pub hour_cycle: HourCycle,
 }
 
 impl Default for AppearanceSettings {
     fn default() -> Self {
         AppearanceSettings {
             greeting_msg: default_greeting_msg(),
+            greeting_markup: false,
+            toolkit: Toolkit::default(),
+            mirror_layout: false,
+            gtk_theme: None,
+            prefer_dark: None,
+            icon_theme: None,
+            cursor_theme: None,
+            cursor_size: None,
+            font: None,
+            greeting_scale: default_greeting_scale(),
+            panel_blur: 0.0,
+            panel_darken: 0.0,
+            panel_opacity: None,
+            panel_radius: None,
+            panel_position: PanelPosition::default(),
+            panel_margin: 0,
+            density: Density::default(),
+            schedule: None,
+            animations: default_animations(),
+            locale: None,
+            time_format: None,
+            hour_cycle: HourCycle::default(),
         }
     }
 }
 
-/// Struct holding all supported GTK settings
+/// Part of the day that a theme is scheduled for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThemePeriod {
+    Day,
+    Night,
+}
+
+/// Theme and background that replace the general ones for a part of the day
 #[derive(Default, Deserialize, Serialize)]
-pub struct GtkSettings {
+pub struct ThemeVariant {
+    /// GTK theme name
     #[serde(default)]
-    pub application_prefer_dark_theme: bool,
+    pub gtk_theme: Option<String>,
+    /// Whether to prefer the dark theme
     #[serde(default)]
-    pub cursor_theme_name: Option<String>,
+    pub prefer_dark: Option<bool>,
+    /// Path to the background image or video
     #[serde(default)]
-    pub font_name: Option<String>,
+    pub background: Option<String>,
+    /// Path to a CSS file, which is loaded after the custom CSS
     #[serde(default)]
-    pub icon_theme_name: Option<String>,
+    pub css: Option<String>,
+}
+
+/// Settings for switching between a day and a night theme
+#[derive(Deserialize, Serialize)]
+pub struct ThemeSchedule {
+    /// Time of day (as "HH:MM") from which the day theme is used
+    #[serde(default = "default_day_start")]
+    pub day_start: String,
+    /// Time of day (as "HH:MM") from which the night theme is used
+    #[serde(default = "default_night_start")]
+    pub night_start: String,
     #[serde(default)]
-    pub theme_name: Option<String>,
+    pub day: ThemeVariant,
+    #[serde(default)]
+    pub night: ThemeVariant,
 }
 
-/// Analogue to `gtk4::ContentFit`
-#[derive(Default, Deserialize, Serialize)]
-pub enum BgFit {
-    Fill,
-    #[default]
-    Contain,
-    Cover,
-    ScaleDown,
+impl ThemeSchedule {
+    /// Get the part of the day that the given time (in minutes since midnight) falls in.
+    ///
+    /// The day may also wrap around midnight, eg. for night shifts. If either time is invalid, this is `None`.
+    pub fn period_at(&self, minutes: u16) -> Option<ThemePeriod> {
+        let day_start = parse_day_time(&self.day_start)?;
+        let night_start = parse_day_time(&self.night_start)?;
+        let is_day = if day_start <= night_start {
+            day_start <= minutes && minutes < night_start
+        } else {
+            minutes >= day_start || minutes < night_start
+        };
+        Some(if is_day {
+            ThemePeriod::Day
+        } else {
+            ThemePeriod::Night
+        })
+    }
+
+    pub fn get_variant(&self, period: ThemePeriod) -> &ThemeVariant {
+        match period {
+            ThemePeriod::Day => &self.day,
+            ThemePeriod::Night => &self.night,
+        }
+    }
 }
 
-/// Struct for info about the background image
-#[derive(Default, Deserialize, Serialize)]
-struct Background {
+/// Parse a time of day as "HH:MM" into minutes since midnight.
+fn parse_day_time(time: &str) -> Option<u16> {
+    let (hours, minutes) = time.split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes) = (hours.parse::<u16>().ok()?, minutes.parse::<u16>().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Settings for the authentication flow
+#[derive(Deserialize, Serialize)]
+pub struct AuthSettings {
+    /// Show a secret entry along with each visible prompt, whose text is sent for the next secret prompt
     #[serde(default)]
-    path: Option<String>,
+    pub combine_prompts: bool,
+    /// Don't use the compositor's input method for secret entries
     #[serde(default)]
-    fit: BgFit,
+    pub disable_password_ime: bool,
+    /// Ask before starting a session for an authenticated user who already has an active one
+    #[serde(default = "default_check_duplicate_login")]
+    pub check_duplicate_login: bool,
+    /// Refuse logging in as root or system users before contacting greetd
+    #[serde(default)]
+    pub deny_system_users: bool,
+    /// Show how long the login took before starting the session
+    #[serde(default)]
+    pub show_login_duration: bool,
+    /// Say that no password is required before starting the session of a user who needs none, eg. a kiosk user
+    #[serde(default)]
+    pub announce_passwordless: bool,
+    /// Keyboard shortcut that has to be pressed before logging in, in the format of
+    /// `gtk::ShortcutTrigger::parse_string`
+    #[serde(default)]
+    pub secure_attention_key: Option<String>,
 }
 
-/// Struct for various system commands
+impl Default for AuthSettings {
+    fn default() -> Self {
+        AuthSettings {
+            combine_prompts: false,
+            disable_password_ime: false,
+            check_duplicate_login: default_check_duplicate_login(),
+            deny_system_users: false,
+            show_login_duration: false,
+            announce_passwordless: false,
+            secure_attention_key: None,
+        }
+    }
+}
+
+/// Settings for how the greeter behaves
 #[derive(Deserialize, Serialize)]
-pub struct SystemCommands {
-    #[serde(default = "default_reboot_command")]
-    pub reboot: Vec<String>,
-    #[serde(default = "default_poweroff_command")]
-    pub poweroff: Vec<String>,
-    #[serde(default = "default_x11_command_prefix")]
-    pub x11_prefix: Vec<String>,
+pub struct BehaviorSettings {
+    /// Comma-separated XKB layouts (eg. "us,de") for the greeter and the started session
+    #[serde(default)]
+    pub xkb_layouts: Option<String>,
+    /// XKB options (eg. "compose:ralt") for the greeter and the started session
+    #[serde(default)]
+    pub xkb_options: Option<String>,
+    /// Submit the input when Enter is pressed in an entry, instead of only with the login button
+    #[serde(default = "default_submit_on_enter")]
+    pub submit_on_enter: bool,
+    /// Seconds without pointer motion after which the pointer is hidden, eg. for kiosks
+    #[serde(default)]
+    pub hide_cursor_after_secs: Option<u64>,
+    /// Regular expressions, one of which the program of a manually entered session command has to fully match
+    #[serde(default)]
+    pub manual_session_allowlist: Option<Vec<String>>,
 }
 
-impl Default for SystemCommands {
+impl Default for BehaviorSettings {
     fn default() -> Self {
-        SystemCommands {
-            reboot: default_reboot_command(),
-            poweroff: default_poweroff_command(),
-            x11_prefix: default_x11_command_prefix(),
+        BehaviorSettings {
+            xkb_layouts: None,
+            xkb_options: None,
+            submit_on_enter: default_submit_on_enter(),
+            hide_cursor_after_secs: None,
+            manual_session_allowlist: None,
         }
     }
 }
 
-fn default_reboot_command() -> Vec<String> {
-    shlex::split(REBOOT_CMD).expect("Unable to lex reboot command")
+/// Settings for the keyboard-driven power menu
+#[derive(Deserialize, Serialize)]
+pub struct PowerMenuSettings {
+    /// Keyboard shortcut that opens the power menu, in the format of `gtk::ShortcutTrigger::parse_string`
+    #[serde(default = "default_power_menu_shortcut")]
+    pub shortcut: String,
+    /// Text to type in the power menu to reboot
+    #[serde(default = "default_reboot_word")]
+    pub reboot_word: String,
+    /// Text to type in the power menu to power off
+    #[serde(default = "default_poweroff_word")]
+    pub poweroff_word: String,
 }
 
-fn default_poweroff_command() -> Vec<String> {
-    shlex::split(POWEROFF_CMD).expect("Unable to lex poweroff command")
+impl Default for PowerMenuSettings {
+    fn default() -> Self {
+        PowerMenuSettings {
+            shortcut: default_power_menu_shortcut(),
+            reboot_word: default_reboot_word(),
+            poweroff_word: default_poweroff_word(),
+        }
+    }
 }
 
-fn default_x11_command_prefix() -> Vec<String> {
-    shlex::split(X11_CMD_PREFIX).expect("Unable to lex X11 command prefix")
+/// Settings for which parts of the UI are shown, eg. to strip it down to a password prompt for kiosks
+#[derive(Deserialize, Serialize)]
+pub struct VisibilitySettings {
+    /// Whether the greeting message is shown, while no other message is shown instead
+    #[serde(default = "default_visible")]
+    pub greeting: bool,
+    /// Whether the session can be chosen, instead of always using the preselected one
+    #[serde(default = "default_visible")]
+    pub session_selector: bool,
+    #[serde(default = "default_visible")]
+    pub reboot_button: bool,
+    #[serde(default = "default_visible")]
+    pub poweroff_button: bool,
+    #[serde(default = "default_visible")]
+    pub suspend_button: bool,
+    #[serde(default = "default_visible")]
+    pub hibernate_button: bool,
+    /// Whether the buttons that end the greeter (eg. Reboot) are shown at all
+    #[serde(default = "default_visible")]
+    pub end_buttons: bool,
+    /// Whether the button that turns the high-contrast theme on and off is shown
+    #[serde(default = "default_visible")]
+    pub high_contrast_button: bool,
 }
 
-fn default_greeting_msg() -> String {
-    GREETING_MSG.to_string()
+impl Default for VisibilitySettings {
+    fn default() -> Self {
+        VisibilitySettings {
+            greeting: default_visible(),
+            session_selector: default_visible(),
+            reboot_button: default_visible(),
+            poweroff_button: default_visible(),
+            suspend_button: default_visible(),
+            hibernate_button: default_visible(),
+            end_buttons: default_visible(),
+            high_contrast_button: default_visible(),
+        }
+    }
 }
 
-/// The configuration struct
+/// Messages shown in particular states of the login, eg. for rephrasing or translating them
+///
+/// Words in braces (eg. `{reason}`) are placeholders, which are replaced when the message is shown.
+#[derive(Deserialize, Serialize)]
+pub struct MessageSettings {
+    /// Message asking to press the secure attention key, with the `{key}` placeholder
+    #[serde(default = "default_attention_msg")]
+    pub attention: String,
+    /// Message shown when logging in fails, with the `{reason}` placeholder for the error from greetd
+    #[serde(default = "default_login_failed_msg")]
+    pub login_failed: String,
+    /// Message shown when logging in as root or a system user fails, with the `{user}` placeholder
+    #[serde(default = "default_system_user_login_failed_msg")]
+    pub system_user_login_failed: String,
+    #[serde(default = "default_session_start_failed_msg")]
+    pub session_start_failed: String,
+    /// Message showing how long the login took, with the `{seconds}` placeholder
+    #[serde(default = "default_logged_in_msg")]
+    pub logged_in: String,
+    /// Message shown before starting the session of a user who needs no password, with the `{user}` placeholder
+    #[serde(default = "default_passwordless_msg")]
+    pub passwordless: String,
+}
+
+impl Default for MessageSettings {
+    fn default() -> Self {
+        MessageSettings {
+            attention: default_attention_msg(),
+            login_failed: default_login_failed_msg(),
+            system_user_login_failed: default_system_user_login_failed_msg(),
+            session_start_failed: default_session_start_failed_msg(),
+            logged_in: default_logged_in_msg(),
+            passwordless: default_passwordless_msg(),
+        }
+    }
+}
+
+/// Replace the placeholders (eg. `{reason}`) in a message from the config with their values.
+pub fn fill_placeholders(message: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(message.to_string(), |message, (name, value)| {
+            message.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+/// Settings for messages about the previous boot
+#[derive(Deserialize, Serialize)]
+pub struct BootSettings {
+    /// File whose existence means that the previous shutdown was unclean
+    #[serde(default = "default_unclean_shutdown_marker")]
+    pub unclean_shutdown_marker: String,
+    #[serde(default = "default_unclean_shutdown_msg")]
+    pub unclean_shutdown_msg: String,
+}
+
+impl Default for BootSettings {
+    fn default() -> Self {
+        BootSettings {
+            unclean_shutdown_marker: default_unclean_shutdown_marker(),
+            unclean_shutdown_msg: default_unclean_shutdown_msg(),
+        }
+    }
+}
+
+/// Settings for the hint shown when the selected user logs in for the first time
+#[derive(Deserialize, Serialize)]
+pub struct FirstLoginSettings {
+    /// Whether the hint is shown
+    #[serde(default)]
+    pub hint: bool,
+    #[serde(default = "default_first_login_msg")]
+    pub message: String,
+}
+
+impl Default for FirstLoginSettings {
+    fn default() -> Self {
+        FirstLoginSettings {
+            hint: false,
+            message: default_first_login_msg(),
+        }
+    }
+}
+
+/// Settings for a legal notice that has to be accepted before logging in
 #[derive(Default, Deserialize)]
-pub struct Config {
+pub struct LegalNoticeSettings {
+    /// Text of the notice
     #[serde(default)]
-    appearance: AppearanceSettings,
+    pub text: Option<String>,
+    /// File containing the notice, which is preferred over the text
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl LegalNoticeSettings {
+    /// Read the notice from the file, or else use the text.
+    ///
+    /// The text is a fallback for when the file can't be read, so that the notice is still shown. If neither is
+    /// available, this fails instead of skipping the notice, since it's required.
+    pub fn read(&self) -> Result<Option<String>, String> {
+        let text = self
+            .text
+            .as_deref()
+            .map(str::trim_end)
+            .filter(|text| !text.is_empty())
+            .map(String::from);
+        let path = if let Some(path) = &self.path {
+            path
+        } else {
+            return Ok(text);
+        };
+
+        let problem = match read_to_string(path) {
+            Ok(notice) if !notice.trim_end().is_empty() => {
+                return Ok(Some(notice.trim_end().to_string()))
+            }
+            Ok(_) => format!("Legal notice '{path}' is empty"),
+            Err(err) => format!("Couldn't read legal notice '{path}': {err}"),
+        };
+        if text.is_some() {
+            warn!(%problem, "Showing the text of the legal notice instead");
+            Ok(text)
+        } else {
+            Err(problem)
+        }
+    }
+}
 
+/// Settings for showing and forwarding notifications
+#[derive(Deserialize)]
+pub struct NotificationSettings {
+    /// Whether notifications are shown on the screen, instead of only being logged
+    #[serde(default = "default_notifications_enabled")]
+    pub enabled: bool,
+    /// Sinks that notifications are forwarded to, in addition to the screen
     #[serde(default)]
-    env: HashMap<String, String>,
+    pub sinks: Vec<SinkConfig>,
+    /// Time after which notifications on the screen are dismissed, by their level
+    #[serde(default)]
+    pub dismiss_after: DismissSettings,
+    /// Whether to warn if the system clock looks wrong, since some logins (eg. with Kerberos) fail then
+    #[serde(default = "default_clock_warning")]
+    pub clock_warning: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            enabled: default_notifications_enabled(),
+            sinks: Vec::new(),
+            dismiss_after: DismissSettings::default(),
+            clock_warning: default_clock_warning(),
+        }
+    }
+}
+
+/// Time after which notifications on the screen are dismissed, which keeps them until clicked away if unset
+#[derive(Default, Deserialize)]
+pub struct DismissSettings {
+    #[serde(with = "humantime_serde", default)]
+    pub info: Option<Duration>,
+    #[serde(with = "humantime_serde", default)]
+    pub warning: Option<Duration>,
+}
 
+/// Settings for the status file used for monitoring
+#[derive(Deserialize)]
+pub struct StatusSettings {
+    /// Path to the status file, which isn't written if this is unset
     #[serde(default)]
-    background: Background,
+    pub path: Option<String>,
+    /// Amount of time between writes to the status file
+    #[serde(with = "humantime_serde", default = "default_status_interval")]
+    pub interval: Duration,
+}
 
-    #[serde(default, rename = "GTK")]
-    gtk: Option<GtkSettings>,
+impl Default for StatusSettings {
+    fn default() -> Self {
+        StatusSettings {
+            path: None,
+            interval: default_status_interval(),
+        }
+    }
+}
 
+/// Settings for the login metrics
+#[derive(Default, Deserialize)]
+pub struct MetricsSettings {
+    /// Path to the metrics file, which isn't written if this is unset
     #[serde(default)]
-    commands: SystemCommands,
+    pub path: Option<String>,
+}
 
+/// Settings for the audit log of login attempts
+#[derive(Default, Deserialize)]
+pub struct AuditSettings {
+    /// Path to the audit log, which isn't written if this is unset
     #[serde(default)]
-    pub(crate) widget: WidgetConfig,
+    pub path: Option<String>,
 }
 
-#[derive(Deserialize, Default)]
-pub struct WidgetConfig {
+/// Settings for the panel that lets the local admin change a few settings at the greeter
+#[derive(Deserialize)]
+pub struct SettingsPanel {
+    /// Show a button that opens the panel, once an admin has authenticated
     #[serde(default)]
-    pub(crate) clock: ClockConfig,
+    pub enabled: bool,
+    /// Users who can open the panel
+    #[serde(default)]
+    pub admin_users: Vec<String>,
+    /// Groups whose members can open the panel
+    #[serde(default)]
+    pub admin_groups: Vec<String>,
+    /// Drop-in file that the panel saves to, whose settings take precedence over the config file
+    #[serde(default = "default_settings_drop_in")]
+    pub path: String,
 }
 
-impl Config {
-    pub fn new(path: &Path) -> Self {
-        load_toml(path)
+impl Default for SettingsPanel {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            admin_users: Vec::new(),
+            admin_groups: Vec::new(),
+            path: default_settings_drop_in(),
+        }
     }
+}
 
-    pub fn get_env(&self) -> &HashMap<String, String> {
-        &self.env
-    }
+fn default_settings_drop_in() -> String {
+    SETTINGS_DROP_IN_PATH.to_string()
+}
 
-    pub fn get_background(&self) -> Option<&str> {
-        self.background.path.as_deref()
+/// Settings that can be changed from the settings panel
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanelValues {
+    pub background: Option<String>,
+    pub greeting_msg: String,
+    pub gtk_theme: Option<String>,
+}
+
+/// Drop-in file that the settings panel saves to
+///
+/// The greeter can write to this file, so only the settings of the panel are read from it, and any other keys are
+/// ignored. Otherwise, it could eg. set the environment of sessions or the commands that the greeter runs.
+#[derive(Default, Deserialize, Serialize)]
+struct SettingsDropIn {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    greeting_msg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gtk_theme: Option<String>,
+}
+
+/// Settings for the log file
+#[derive(Default, Deserialize)]
+pub struct LoggingSettings {
+    /// Time zone of the timestamps in the logs, which is the system time zone if this is unset
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+/// Settings for the greeter's own process, eg. to work around GPU driver issues
+#[derive(Default, Deserialize)]
+pub struct RuntimeSettings {
+    /// Number of threads running background tasks, which is Relm4's default if this is unset
+    #[serde(default)]
+    pub threads: Option<usize>,
+    /// Renderer used by GTK, set as `GSK_RENDERER`, eg. "cairo" to avoid the GPU
+    #[serde(default)]
+    pub gsk_renderer: Option<String>,
+    /// Display backend used by GTK, set as `GDK_BACKEND`, eg. "wayland"
+    #[serde(default)]
+    pub gdk_backend: Option<String>,
+}
+
+/// Settings for logging in by scanning a badge, eg. with a barcode scanner attached as a keyboard
+#[derive(Default, Deserialize)]
+pub struct BadgeLoginSettings {
+    /// Whether scanned badges are captured to log in
+    #[serde(default)]
+    pub enabled: bool,
+    /// Text separating the username from a credential in scanned badges
+    #[serde(default)]
+    pub credential_separator: Option<String>,
+}
+
+impl BadgeLoginSettings {
+    /// Split a scanned badge into the username and the credential, if it contains one.
+    pub fn split_scan<'a>(&self, scan: &'a str) -> (&'a str, Option<&'a str>) {
+        let scan = scan.trim();
+        let split = self
+            .credential_separator
+            .as_deref()
+            .filter(|separator| !separator.is_empty())
+            .and_then(|separator| scan.split_once(separator));
+        if let Some((username, credential)) = split {
+            (username, Some(credential))
+        } else {
+            (scan, None)
+        }
     }
+}
 
-    #[cfg(feature = "gtk4_8")]
-    pub fn get_background_fit(&self) -> &BgFit {
-        &self.background.fit
+/// Settings for telling users when the machine is shut down, eg. for updates
+#[derive(Deserialize)]
+pub struct MaintenanceSettings {
+    /// Maintenance window shown at the greeter, eg. "This machine reboots nightly at 03:00"
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Whether to warn about shutdowns scheduled with logind, eg. by `shutdown -r +30`
+    #[serde(default = "default_check_scheduled_shutdown")]
+    pub check_scheduled_shutdown: bool,
+}
+
+impl Default for MaintenanceSettings {
+    fn default() -> Self {
+        Self {
+            message: None,
+            check_scheduled_shutdown: default_check_scheduled_shutdown(),
+        }
     }
+}
 
-    pub fn get_gtk_settings(&self) -> &Option<GtkSettings> {
-        &self.gtk
+/// Settings for an on-screen keyboard, eg. on tablets without a physical keyboard
+#[derive(Deserialize)]
+pub struct OskSettings {
+    /// Command that runs the on-screen keyboard, which is killed again to hide it
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Whether the keyboard is shown when an entry is tapped on a touchscreen
+    #[serde(default = "default_osk_auto_show")]
+    pub auto_show: bool,
+}
+
+impl Default for OskSettings {
+    fn default() -> Self {
+        Self {
+            command: Vec::new(),
+            auto_show: default_osk_auto_show(),
+        }
     }
+}
 
-    pub fn get_sys_commands(&self) -> &SystemCommands {
-        &self.commands
+/// Settings that override the general ones for a particular user, eg. on shared workstations
+#[derive(Default, Deserialize)]
+pub struct UserSettings {
+    /// Environment variables for the user's sessions, which override those in the `[env]` section
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Session to select for the user, if they haven't started one yet
+    #[serde(default)]
+    pub session: Option<String>,
+    /// Greeting message shown while the user is selected
+    #[serde(default)]
+    pub greeting_msg: Option<String>,
+}
+
+/// Settings for the users shown in the user list, and for particular users
+#[derive(Default, Deserialize)]
+pub struct UsersSettings {
+    /// Lowest UID of normal users, which overrides `UID_MIN` in `login.defs`
+    #[serde(default)]
+    pub min_uid: Option<u64>,
+    /// Highest UID of normal users, which overrides `UID_MAX` in `login.defs`
+    #[serde(default)]
+    pub max_uid: Option<u64>,
+    /// Settings for particular users, by their username
+    #[serde(flatten)]
+    pub per_user: HashMap<String, UserSettings>,
+}
+
+/// Settings for the sessions that can be chosen
+#[derive(Default, Deserialize)]
+pub struct SessionSettings {
+    /// ID of the desktop file of the session to select for users who haven't started one yet, eg. "sway.desktop"
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(flatten)]
+    pub filter: SessionFilter,
+}
+
+/// Filter for the sessions shown, by the IDs of their desktop files, eg. "gnome-classic.desktop"
+#[derive(Default, Deserialize)]
+pub struct SessionFilter {
+    /// If set, only these sessions are shown
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+    /// Sessions that are never shown
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl SessionFilter {
+    /// Check whether the session with the given desktop file ID is shown.
+    pub fn is_shown(&self, id: &str) -> bool {
+        let allowed = if let Some(allow) = &self.allow {
+            allow.iter().any(|allowed| allowed == id)
+        } else {
+            true
+        };
+        allowed && !self.deny.iter().any(|denied| denied == id)
+    }
+}
+
+/// Rule that preselects a user and session on machines whose hostname matches a pattern
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct HostPreselect {
+    /// Glob pattern for the hostname, eg. "lab-*"
+    pub hostname: String,
+    /// Username to preselect
+    pub user: String,
+    /// Session to preselect for the user, instead of their last used one
+    #[serde(default)]
+    pub session: Option<String>,
+}
+
+/// Hint for the input entry shown for PAM prompts matching a pattern, eg. for explaining a one-time password
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct PromptHint {
+    /// Regular expression matched against the prompt, eg. "(?i)one-time password"
+    pub prompt: String,
+    /// Placeholder text shown in the empty entry
+    #[serde(default)]
+    pub placeholder: Option<String>,
+    /// Tooltip of the entry
+    #[serde(default)]
+    pub tooltip: Option<String>,
+}
+
+/// Patterns from the config, which are compiled once when it's loaded
+#[derive(Default)]
+struct Patterns {
+    /// Hostname patterns of the preselect rules, in the same order, which are `None` if they're invalid
+    hostnames: Vec<Option<glob::Pattern>>,
+    /// Prompt patterns of the prompt hints, in the same order, which are `None` if they're invalid
+    prompts: Vec<Option<Regex>>,
+    /// Patterns that the program of a manually entered session command has to match, without the invalid ones
+    manual_session_allowlist: Option<Vec<Regex>>,
+}
+
+/// Struct holding all supported GTK settings
+#[derive(Default, Deserialize, Serialize)]
+pub struct GtkSettings {
+    #[serde(default)]
+    pub application_prefer_dark_theme: bool,
+    #[serde(default)]
+    pub cursor_theme_name: Option<String>,
+    #[serde(default)]
+    pub font_name: Option<String>,
+    #[serde(default)]
+    pub icon_theme_name: Option<String>,
+    #[serde(default)]
+    pub theme_name: Option<String>,
+}
+
+/// Analogue to `gtk4::ContentFit`
+#[derive(Default, Deserialize, Serialize)]
+pub enum BgFit {
+    Fill,
+    #[default]
+    Contain,
+    Cover,
+    ScaleDown,
+}
+
+/// Struct for info about the background image
+#[derive(Default, Deserialize, Serialize)]
+struct Background {
+    /// Path to an image, an animated GIF or a video
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    fit: BgFit,
+    /// Path to an image shown if the animated background can't be played, instead of the first frame of a GIF or no
+    /// background for a video
+    #[serde(default)]
+    fallback: Option<String>,
+    /// Color shown behind the background image, or instead of it if there's none
+    #[serde(default)]
+    color: Option<String>,
+    /// Top and bottom colors of a gradient, which is shown like the color but takes precedence over it
+    #[serde(default)]
+    gradient: Option<[String; 2]>,
+    /// File extensions of the images picked if the path is a directory, which allows all files if unset
+    #[serde(default)]
+    extensions: Option<Vec<String>>,
+    /// Directory that the path was picked from, if any
+    #[serde(skip)]
+    picked_from: Option<String>,
+}
+
+/// Pick a file from the directory, using the seed as a random number.
+///
+/// Files are sorted by name before picking, so that the same seed always picks the same file. Extensions are
+/// compared case-insensitively, and only files with one of them are picked if given.
+fn pick_file(dir: &Path, extensions: Option<&[String]>, seed: u64) -> io::Result<Option<PathBuf>> {
+    let mut files: Vec<_> = read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            extensions.map_or(true, |extensions| {
+                path.extension().is_some_and(|extension| {
+                    extensions.iter().any(|allowed| {
+                        extension.eq_ignore_ascii_case(allowed.trim_start_matches('.'))
+                    })
+                })
+            })
+        })
+        .collect();
+    if files.is_empty() {
+        return Ok(None);
+    }
+    files.sort_unstable();
+    let index = (seed % files.len() as u64) as usize;
+    Ok(Some(files.swap_remove(index)))
+}
+
+/// Extra button that runs a command, eg. for rebooting into another OS
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CustomButton {
+    pub label: String,
+    /// Name of an icon from the icon theme, which is shown before the label
+    #[serde(default)]
+    pub icon: Option<String>,
+    pub command: Vec<String>,
+    /// Whether to ask before running the command
+    #[serde(default)]
+    pub require_confirm: bool,
+}
+
+/// Struct for various system commands
+#[derive(Deserialize, Serialize)]
+pub struct SystemCommands {
+    #[serde(default = "default_reboot_command")]
+    pub reboot: Vec<String>,
+    #[serde(default = "default_poweroff_command")]
+    pub poweroff: Vec<String>,
+    #[serde(default = "default_suspend_command")]
+    pub suspend: Vec<String>,
+    #[serde(default = "default_hibernate_command")]
+    pub hibernate: Vec<String>,
+    #[serde(default = "default_x11_command_prefix")]
+    pub x11_prefix: Vec<String>,
+    /// Extra buttons next to the reboot and power-off buttons
+    #[serde(default)]
+    pub buttons: Vec<CustomButton>,
+    /// Greeter that is started instead if ReGreet can't show its UI, which is unset if empty
+    #[serde(default)]
+    pub fallback_greeter: Vec<String>,
+}
+
+impl Default for SystemCommands {
+    fn default() -> Self {
+        SystemCommands {
+            reboot: default_reboot_command(),
+            poweroff: default_poweroff_command(),
+            suspend: default_suspend_command(),
+            hibernate: default_hibernate_command(),
+            x11_prefix: default_x11_command_prefix(),
+            buttons: Vec::new(),
+            fallback_greeter: Vec::new(),
+        }
+    }
+}
+
+fn default_reboot_command() -> Vec<String> {
+    shlex::split(REBOOT_CMD).expect("Unable to lex reboot command")
+}
+
+fn default_poweroff_command() -> Vec<String> {
+    shlex::split(POWEROFF_CMD).expect("Unable to lex poweroff command")
+}
+
+fn default_suspend_command() -> Vec<String> {
+    shlex::split(SUSPEND_CMD).expect("Unable to lex suspend command")
+}
+
+fn default_hibernate_command() -> Vec<String> {
+    shlex::split(HIBERNATE_CMD).expect("Unable to lex hibernate command")
+}
+
+fn default_x11_command_prefix() -> Vec<String> {
+    shlex::split(X11_CMD_PREFIX).expect("Unable to lex X11 command prefix")
+}
+
+fn default_greeting_msg() -> String {
+    GREETING_MSG.to_string()
+}
+
+const fn default_greeting_scale() -> f64 {
+    1.0
+}
+
+const fn default_animations() -> bool {
+    true
+}
+
+fn default_day_start() -> String {
+    "07:00".to_string()
+}
+
+fn default_night_start() -> String {
+    "19:00".to_string()
+}
+
+const fn default_check_duplicate_login() -> bool {
+    true
+}
+
+const fn default_submit_on_enter() -> bool {
+    true
+}
+
+const fn default_notifications_enabled() -> bool {
+    true
+}
+
+const fn default_clock_warning() -> bool {
+    true
+}
+
+const fn default_visible() -> bool {
+    true
+}
+
+const fn default_osk_auto_show() -> bool {
+    true
+}
+
+const fn default_check_scheduled_shutdown() -> bool {
+    true
+}
+
+fn default_unclean_shutdown_marker() -> String {
+    UNCLEAN_SHUTDOWN_MARKER.to_string()
+}
+
+fn default_unclean_shutdown_msg() -> String {
+    UNCLEAN_SHUTDOWN_MSG.to_string()
+}
+
+fn default_first_login_msg() -> String {
+    FIRST_LOGIN_MSG.to_string()
+}
+
+fn default_attention_msg() -> String {
+    "Press {key} to log in".to_string()
+}
+
+fn default_login_failed_msg() -> String {
+    "Login failed: {reason}".to_string()
+}
+
+fn default_system_user_login_failed_msg() -> String {
+    "Login failed: Logging in as {user} might not be allowed here".to_string()
+}
+
+fn default_session_start_failed_msg() -> String {
+    "Failed to start session".to_string()
+}
+
+fn default_logged_in_msg() -> String {
+    "Logged in after {seconds} s".to_string()
+}
+
+fn default_passwordless_msg() -> String {
+    "No password required for {user}".to_string()
+}
+
+fn default_power_menu_shortcut() -> String {
+    "<Control><Alt>p".to_string()
+}
+
+fn default_reboot_word() -> String {
+    "reboot".to_string()
+}
+
+fn default_poweroff_word() -> String {
+    "poweroff".to_string()
+}
+
+const fn default_status_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// Override of a config value, eg. from the command line as `section.key=value`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigOverride {
+    /// Names of the nested sections, followed by the name of the key
+    path: Vec<String>,
+    value: toml::Value,
+}
+
+impl ConfigOverride {
+    /// Set the value in the given TOML table, creating any missing sections.
+    fn apply(&self, mut table: &mut toml::Table) {
+        let (key, sections) = self.path.split_last().expect("Override must have a key");
+        for section in sections {
+            let entry = table
+                .entry(section.as_str())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            table = if let toml::Value::Table(table) = entry {
+                table
+            } else {
+                warn!(
+                    key = self.path.join("."),
+                    "Can't override a key in a non-table"
+                );
+                return;
+            };
+        }
+        table.insert(key.clone(), self.value.clone());
+    }
+}
+
+impl FromStr for ConfigOverride {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (key, value) = text
+            .split_once('=')
+            .ok_or_else(|| format!("Expected SECTION.KEY=VALUE, found '{text}'"))?;
+        let path: Vec<_> = key.split('.').map(|name| name.trim().to_string()).collect();
+        if path.iter().any(String::is_empty) {
+            return Err(format!("Invalid key '{key}'"));
+        }
+
+        // Values are TOML (eg. `true` or `["a", "b"]`), but strings don't need quotes.
+        let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+        Ok(Self { path, value })
+    }
+}
+
+/// The configuration struct
+#[derive(Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    appearance: AppearanceSettings,
+
+    #[serde(default)]
+    auth: AuthSettings,
+
+    #[serde(default)]
+    behavior: BehaviorSettings,
+
+    #[serde(default)]
+    boot: BootSettings,
+
+    #[serde(default)]
+    messages: MessageSettings,
+
+    #[serde(default)]
+    first_login: FirstLoginSettings,
+
+    #[serde(default)]
+    notifications: NotificationSettings,
+
+    #[serde(default)]
+    status: StatusSettings,
+
+    #[serde(default)]
+    metrics: MetricsSettings,
+
+    #[serde(default)]
+    audit: AuditSettings,
+
+    #[serde(default)]
+    settings_panel: SettingsPanel,
+
+    #[serde(default)]
+    logging: LoggingSettings,
+
+    #[serde(default)]
+    runtime: RuntimeSettings,
+
+    #[serde(default)]
+    badge_login: BadgeLoginSettings,
+
+    #[serde(default)]
+    osk: OskSettings,
+
+    #[serde(default)]
+    maintenance: MaintenanceSettings,
+
+    #[serde(default)]
+    legal_notice: LegalNoticeSettings,
+
+    #[serde(default)]
+    preselect: Vec<HostPreselect>,
+
+    #[serde(default)]
+    prompt_hints: Vec<PromptHint>,
+
+    #[serde(default)]
+    env: HashMap<String, String>,
+
+    #[serde(default)]
+    users: UsersSettings,
+
+    #[serde(default)]
+    sessions: SessionSettings,
+
+    #[serde(default)]
+    background: Background,
+
+    #[serde(default, rename = "GTK")]
+    gtk: Option<GtkSettings>,
+
+    #[serde(default)]
+    commands: SystemCommands,
+
+    #[serde(default)]
+    power_menu: PowerMenuSettings,
+
+    #[serde(default)]
+    visibility: VisibilitySettings,
+
+    #[serde(default)]
+    pub(crate) widget: WidgetConfig,
+
+    #[serde(skip)]
+    patterns: Patterns,
+}
+
+#[derive(Deserialize, Default)]
+pub struct WidgetConfig {
+    #[serde(default)]
+    pub(crate) clock: ClockConfig,
+}
+
+/// Log the problems that were fixed up when loading the config.
+fn log_problems(problems: Vec<String>) {
+    for problem in problems {
+        warn!(%problem, "Problem in the config");
+    }
+}
+
+impl Config {
+    /// Load the config file from disk, and override the given values in it.
+    pub fn new(path: &Path, overrides: &[ConfigOverride]) -> Self {
+        let mut config: Self = if overrides.is_empty() {
+            load_toml(path)
+        } else {
+            Self::with_overrides(load_toml(path), overrides)
+        };
+        if config.settings_panel.enabled {
+            config.merge_drop_in(overrides);
+        }
+        config.export_xkb_settings();
+        log_problems(config.validate());
+        config.pick_random_background();
+        config
+    }
+
+    /// Load a config file to preview it, eg. when it's dropped onto the window in demo mode.
+    ///
+    /// Unlike [`Config::new`], this fails instead of falling back to the defaults, so that mistakes are noticed.
+    pub fn load_preview(path: &Path) -> TomlFileResult<Self> {
+        let mut config: Self = load_raw_toml(path)?;
+        log_problems(config.validate());
+        config.pick_random_background();
+        Ok(config)
+    }
+
+    /// Apply the settings saved by the settings panel over the ones from the config file.
+    ///
+    /// The overrides still take precedence over them.
+    fn merge_drop_in(&mut self, overrides: &[ConfigOverride]) {
+        let drop_in_path = Path::new(&self.settings_panel.path);
+        if !drop_in_path.exists() {
+            return;
+        }
+        let drop_in: SettingsDropIn = match load_raw_toml(drop_in_path) {
+            Ok(drop_in) => drop_in,
+            Err(err) => {
+                warn!(path = %drop_in_path.display(), error = %err, "Error loading the settings drop-in");
+                return;
+            }
+        };
+
+        let overridden = |section: &str, key: &str| {
+            overrides
+                .iter()
+                .any(|config_override| config_override.path == [section, key])
+        };
+        if drop_in.background.is_some() && !overridden("background", "path") {
+            self.background.path = drop_in.background;
+        }
+        if let Some(greeting_msg) = drop_in.greeting_msg {
+            if !overridden("appearance", "greeting_msg") {
+                self.appearance.greeting_msg = greeting_msg;
+            }
+        }
+        if drop_in.gtk_theme.is_some() && !overridden("appearance", "gtk_theme") {
+            self.appearance.gtk_theme = drop_in.gtk_theme;
+        }
+        info!(path = %drop_in_path.display(), "Merged the settings drop-in");
+    }
+
+    /// Deserialize the config from a TOML table, after overriding values in it.
+    ///
+    /// Overrides that make the config invalid (eg. a number for a string) are ignored with a warning, so that one typo
+    /// doesn't reset the whole config. If the config is invalid without them, then this returns the default config,
+    /// like for an invalid config file.
+    fn with_overrides(mut table: toml::Table, overrides: &[ConfigOverride]) -> Self {
+        for config_override in overrides {
+            let mut overridden = table.clone();
+            config_override.apply(&mut overridden);
+            match toml::Value::Table(overridden.clone()).try_into::<Self>() {
+                Ok(_) => table = overridden,
+                Err(err) => warn!(
+                    key = config_override.path.join("."),
+                    error = %err,
+                    "Ignoring invalid config override"
+                ),
+            }
+        }
+        toml::Value::Table(table).try_into().unwrap_or_else(|err| {
+            warn!(error = %err, "Error applying config overrides");
+            Self::default()
+        })
+    }
+
+    /// Turn off everything that needs a lot of CPU or memory, for old hardware like thin clients.
+    ///
+    /// This hides the background (including the scheduled ones), doesn't blur it, disables the animations and hides
+    /// the clock, so that it isn't updated.
+    pub fn limit_resources(&mut self) {
+        self.background.path = None;
+        self.background.fallback = None;
+        if let Some(schedule) = &mut self.appearance.schedule {
+            schedule.day.background = None;
+            schedule.night.background = None;
+        }
+        self.appearance.panel_blur = 0.0;
+        self.appearance.animations = false;
+        self.widget.clock.enabled = false;
+    }
+
+    /// Check the config file for problems, eg. before rebooting into the greeter with it.
+    ///
+    /// This returns a human-readable description of each problem, which is empty if there are none. Unlike
+    /// [`Config::new`], this doesn't fall back to the defaults if the file can't be loaded.
+    pub fn check_file(path: &Path) -> Vec<String> {
+        match load_raw_toml::<Self>(path) {
+            Ok(mut config) => config.check(),
+            Err(err) => {
+                let reason = if let Some(source) = err.source() {
+                    format!("{err}: {source}")
+                } else {
+                    err.to_string()
+                };
+                vec![format!("Couldn't load '{}': {reason}", path.display())]
+            }
+        }
+    }
+
+    /// Check the config for problems that would only show up in the greeter.
+    ///
+    /// This includes the problems that are fixed up when the config is loaded, eg. invalid patterns.
+    fn check(&mut self) -> Vec<String> {
+        let mut problems = self.validate();
+
+        let paths = [
+            ("Background", &self.background.path),
+            ("Background fallback", &self.background.fallback),
+            ("Legal notice", &self.legal_notice.path),
+        ];
+        let scheduled_paths = self.appearance.schedule.iter().flat_map(|schedule| {
+            [
+                ("Day background", &schedule.day.background),
+                ("Day CSS", &schedule.day.css),
+                ("Night background", &schedule.night.background),
+                ("Night CSS", &schedule.night.css),
+            ]
+        });
+        let colors = self.background.color.iter();
+        for color in colors.chain(self.background.gradient.iter().flatten()) {
+            if let Err(err) = gtk::gdk::RGBA::parse(color) {
+                problems.push(format!("Invalid background color '{color}': {err}"));
+            }
+        }
+        for (name, path) in paths.into_iter().chain(scheduled_paths) {
+            if let Some(path) = path {
+                if !Path::new(path).exists() {
+                    problems.push(format!("{name} '{path}' doesn't exist"));
+                }
+            }
+        }
+
+        let commands = [
+            ("reboot", &self.commands.reboot),
+            ("poweroff", &self.commands.poweroff),
+            ("suspend", &self.commands.suspend),
+            ("hibernate", &self.commands.hibernate),
+        ];
+        for (name, command) in commands {
+            if command.is_empty() {
+                problems.push(format!("The {name} command is empty"));
+            }
+        }
+        for button in &self.commands.buttons {
+            if button.command.is_empty() {
+                problems.push(format!(
+                    "The command of the button '{}' is empty",
+                    button.label
+                ));
+            }
+        }
+        let panel = &self.settings_panel;
+        if panel.enabled && panel.admin_users.is_empty() && panel.admin_groups.is_empty() {
+            problems.push(
+                "The settings panel is enabled, but no admin users or groups can open it"
+                    .to_string(),
+            );
+        }
+        for sink in &self.notifications.sinks {
+            if let SinkConfig::Command { command } = sink {
+                if command.is_empty() {
+                    problems.push("The command of a notification sink is empty".to_string());
+                }
+            }
+        }
+
+        // These can't be passed to the session.
+        let user_envs = self.users.per_user.values().map(|settings| &settings.env);
+        for (key, value) in [&self.env].into_iter().chain(user_envs).flatten() {
+            if key.is_empty() || key.contains(['=', '\0']) || value.contains('\0') {
+                problems.push(format!("Invalid environment variable '{key}'"));
+            }
+        }
+
+        if let Some(timezone) = &self.logging.timezone {
+            if let Err(err) = jiff::tz::TimeZone::get(timezone) {
+                problems.push(format!("Invalid log timezone '{timezone}': {err}"));
+            }
+        }
+        if let (Some(min_uid), Some(max_uid)) = (self.users.min_uid, self.users.max_uid) {
+            if min_uid > max_uid {
+                problems.push(format!(
+                    "The lowest UID of normal users ({min_uid}) is above the highest one ({max_uid})"
+                ));
+            }
+        }
+        if let Some(opacity) = self.appearance.panel_opacity {
+            if !(0.0..=1.0).contains(&opacity) {
+                problems.push(format!(
+                    "The panel opacity ({opacity}) isn't between 0 and 1"
+                ));
+            }
+        }
+        if let Some(size) = self.appearance.cursor_size.filter(|size| *size <= 0) {
+            problems.push(format!("The cursor size ({size}) isn't positive"));
+        }
+        if let Some(format) = &self.appearance.time_format {
+            if let Err(err) = jiff::fmt::strtime::format(format, &jiff::Zoned::now()) {
+                problems.push(format!("Invalid time format '{format}': {err}"));
+            }
+        }
+        if self.runtime.threads == Some(0) {
+            problems.push("The number of runtime threads is zero".to_string());
+        }
+        if let Some(schedule) = &self.appearance.schedule {
+            for time in [&schedule.day_start, &schedule.night_start] {
+                if parse_day_time(time).is_none() {
+                    problems.push(format!(
+                        "Invalid time of day '{time}' in the theme schedule"
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Replace a background directory with a random image from it, so that a different one is shown on each start.
+    fn pick_random_background(&mut self) {
+        let dir = if let Some(path) = &self.background.path {
+            PathBuf::from(path)
+        } else {
+            return;
+        };
+        if !dir.is_dir() {
+            return;
+        }
+
+        // The hasher's keys are random for each process, which is enough for picking a wallpaper.
+        let seed = RandomState::new().build_hasher().finish();
+        match pick_file(&dir, self.background.extensions.as_deref(), seed) {
+            Ok(Some(path)) => {
+                info!(path = %path.display(), dir = %dir.display(), "Picked the background");
+                self.background.picked_from = self.background.path.take();
+                self.background.path = Some(path.to_string_lossy().into_owned());
+            }
+            Ok(None) => {
+                warn!(path = %dir.display(), "No background images found");
+                self.background.path = None;
+            }
+            Err(err) => {
+                warn!(path = %dir.display(), error = %err, "Couldn't read the background directory");
+                self.background.path = None;
+            }
+        }
+    }
+
+    /// Fix up the parts of the config that would break the greeter, returning a description of each problem.
+    ///
+    /// This is shared by loading the config and checking it, so that both find the same problems.
+    fn validate(&mut self) -> Vec<String> {
+        let mut problems = self.validate_greeting_markup();
+        problems.extend(self.compile_patterns());
+        problems
+    }
+
+    /// Show the greeting message as plain text if its markup is invalid, since GTK would show nothing otherwise.
+    fn validate_greeting_markup(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self.appearance.greeting_markup {
+            return problems;
+        }
+        if let Err(err) = gtk::pango::parse_markup(&self.appearance.greeting_msg, '\0') {
+            problems.push(format!(
+                "Invalid markup in the greeting message, so it's shown as plain text: {err}"
+            ));
+            self.appearance.greeting_markup = false;
+            return problems;
+        }
+
+        // Users' greetings are shown with the same markup setting, so fall back to the default one for them.
+        for (user, settings) in &mut self.users.per_user {
+            let greeting = if let Some(greeting) = &settings.greeting_msg {
+                greeting
+            } else {
+                continue;
+            };
+            if let Err(err) = gtk::pango::parse_markup(greeting, '\0') {
+                problems.push(format!(
+                    "Invalid markup in the greeting message for '{user}', so the default one is shown: {err}"
+                ));
+                settings.greeting_msg = None;
+            }
+        }
+        problems
+    }
+
+    /// Compile the patterns in the config, so that they aren't compiled each time they're matched.
+    ///
+    /// Invalid patterns never match, so they don't allow anything. A description of each of them is returned.
+    pub(crate) fn compile_patterns(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let hostnames = self.preselect.iter().map(|rule| &rule.hostname);
+        self.patterns.hostnames = hostnames
+            .map(|pattern| match glob::Pattern::new(pattern) {
+                Ok(pattern) => Some(pattern),
+                Err(err) => {
+                    problems.push(format!("Invalid hostname pattern '{pattern}': {err}"));
+                    None
+                }
+            })
+            .collect();
+
+        let prompts = self.prompt_hints.iter().map(|hint| &hint.prompt);
+        self.patterns.prompts = prompts
+            .map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    problems.push(format!("Invalid prompt pattern '{pattern}': {err}"));
+                    None
+                }
+            })
+            .collect();
+
+        // The patterns have to match the whole program.
+        let allowlist = self.behavior.manual_session_allowlist.as_ref();
+        self.patterns.manual_session_allowlist = allowlist.map(|patterns| {
+            patterns
+                .iter()
+                .filter_map(|pattern| match Regex::new(&format!("^(?:{pattern})$")) {
+                    Ok(regex) => Some(regex),
+                    Err(err) => {
+                        problems.push(format!(
+                            "Invalid session command pattern '{pattern}': {err}"
+                        ));
+                        None
+                    }
+                })
+                .collect()
+        });
+
+        problems
+    }
+
+    /// Pass the XKB layouts and options to the session, unless its environment already sets them.
+    fn export_xkb_settings(&mut self) {
+        let settings = [
+            (XKB_LAYOUT_ENV_VAR, &self.behavior.xkb_layouts),
+            (XKB_OPTIONS_ENV_VAR, &self.behavior.xkb_options),
+        ];
+        for (env_var, value) in settings {
+            let value = if let Some(value) = value {
+                value
+            } else {
+                continue;
+            };
+
+            // The greeter's keymap is set by the compositor, which has to be started with these settings.
+            if std::env::var(env_var).ok().as_ref() != Some(value) {
+                warn!(
+                    "The XKB setting '{value}' is only applied to the greeter if the compositor is started with \
+                    {env_var}='{value}'"
+                );
+            }
+
+            self.env
+                .entry(env_var.to_string())
+                .or_insert_with(|| value.clone());
+        }
+    }
+
+    pub fn get_env(&self) -> &HashMap<String, String> {
+        &self.env
+    }
+
+    /// Get the environment variables for the sessions of the given user, including those set only for them.
+    pub fn get_user_env(&self, username: Option<&str>) -> Cow<HashMap<String, String>> {
+        let user_env =
+            if let Some(settings) = username.and_then(|user| self.users.per_user.get(user)) {
+                &settings.env
+            } else {
+                return Cow::Borrowed(&self.env);
+            };
+        if user_env.is_empty() {
+            return Cow::Borrowed(&self.env);
+        }
+        let mut env = self.env.clone();
+        env.extend(
+            user_env
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+        Cow::Owned(env)
+    }
+
+    /// Get the lowest and the highest UIDs of normal users, if they override the ones in `login.defs`.
+    pub fn get_uid_range(&self) -> (Option<u64>, Option<u64>) {
+        (self.users.min_uid, self.users.max_uid)
+    }
+
+    pub fn get_session_filter(&self) -> &SessionFilter {
+        &self.sessions.filter
+    }
+
+    /// Get the session to select for the given user, if they haven't started one yet.
+    pub fn get_user_session(&self, username: &str) -> Option<&str> {
+        self.users.per_user.get(username)?.session.as_deref()
+    }
+
+    /// Get the ID of the desktop file of the session to select for users who haven't started one yet, if any.
+    ///
+    /// Unlike the sessions set for particular users, this is the same kind of ID as in the session filter, so that it
+    /// doesn't depend on the translated name of the session.
+    pub fn get_default_session(&self) -> Option<&str> {
+        self.sessions.default.as_deref()
+    }
+
+    /// Get the short names of the keyboard layouts, in the order of the XKB layout groups.
+    ///
+    /// If they aren't configured, this uses the ones that the compositor was started with, if any.
+    pub fn get_xkb_layouts(&self) -> Vec<String> {
+        let layouts = if let Some(layouts) = &self.behavior.xkb_layouts {
+            layouts.clone()
+        } else if let Ok(layouts) = std::env::var(XKB_LAYOUT_ENV_VAR) {
+            layouts
+        } else {
+            return Vec::new();
+        };
+        layouts
+            .split(',')
+            .map(str::trim)
+            .filter(|layout| !layout.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    pub fn get_submit_on_enter(&self) -> bool {
+        self.behavior.submit_on_enter
+    }
+
+    /// Get the patterns that the program of a manually entered session command has to match, if it's restricted.
+    ///
+    /// The patterns have to match the whole program. Invalid patterns are skipped, so they don't allow anything.
+    pub fn get_manual_session_allowlist(&self) -> Option<&[Regex]> {
+        self.patterns.manual_session_allowlist.as_deref()
+    }
+
+    /// Get the time without pointer motion after which the pointer is hidden, if it is.
+    pub fn get_hide_cursor_after(&self) -> Option<Duration> {
+        self.behavior
+            .hide_cursor_after_secs
+            .map(Duration::from_secs)
+    }
+
+    pub fn get_background(&self) -> Option<&str> {
+        self.background.path.as_deref()
+    }
+
+    pub fn get_background_fallback(&self) -> Option<&str> {
+        self.background.fallback.as_deref()
+    }
+
+    #[cfg(feature = "gtk4_8")]
+    pub fn get_background_fit(&self) -> &BgFit {
+        &self.background.fit
+    }
+
+    /// Get the color shown behind the background image, or the two stops of its gradient, which is empty if unset.
+    ///
+    /// Invalid colors are skipped with a warning, so a gradient with an invalid stop is shown as its valid color.
+    pub fn get_background_colors(&self) -> Vec<gtk::gdk::RGBA> {
+        let colors = if let Some(gradient) = &self.background.gradient {
+            gradient.as_slice()
+        } else {
+            self.background.color.as_slice()
+        };
+        colors
+            .iter()
+            .filter_map(|color| {
+                gtk::gdk::RGBA::parse(color)
+                    .map_err(|err| warn!(color, error = %err, "Ignoring invalid background color"))
+                    .ok()
+            })
+            .collect()
+    }
+
+    pub fn get_gtk_settings(&self) -> &Option<GtkSettings> {
+        &self.gtk
+    }
+
+    pub fn get_gtk_theme(&self) -> Option<&str> {
+        self.appearance
+            .gtk_theme
+            .as_deref()
+            .or_else(|| self.gtk.as_ref()?.theme_name.as_deref())
+    }
+
+    pub fn get_prefer_dark(&self) -> Option<bool> {
+        self.appearance.prefer_dark.or_else(|| {
+            self.gtk
+                .as_ref()
+                .map(|gtk| gtk.application_prefer_dark_theme)
+        })
+    }
+
+    pub fn get_icon_theme(&self) -> Option<&str> {
+        self.appearance
+            .icon_theme
+            .as_deref()
+            .or_else(|| self.gtk.as_ref()?.icon_theme_name.as_deref())
+    }
+
+    pub fn get_cursor_theme(&self) -> Option<&str> {
+        self.appearance
+            .cursor_theme
+            .as_deref()
+            .or_else(|| self.gtk.as_ref()?.cursor_theme_name.as_deref())
+    }
+
+    /// Get the cursor size, ignoring sizes that aren't positive.
+    pub fn get_cursor_size(&self) -> Option<i32> {
+        self.appearance.cursor_size.filter(|size| *size > 0)
+    }
+
+    pub fn get_font(&self) -> Option<&str> {
+        self.appearance
+            .font
+            .as_deref()
+            .or_else(|| self.gtk.as_ref()?.font_name.as_deref())
+    }
+
+    pub fn get_greeting_scale(&self) -> f64 {
+        self.appearance.greeting_scale
+    }
+
+    pub fn get_animations(&self) -> bool {
+        self.appearance.animations
+    }
+
+    /// Get the blur radius and the darkening opacity for the background behind the login box.
+    pub fn get_panel_backdrop(&self) -> (f64, f32) {
+        (self.appearance.panel_blur, self.appearance.panel_darken)
+    }
+
+    /// Get the opacity of the background of the login box and the clock, and the radius of their corners, if set.
+    pub fn get_panel_style(&self) -> (Option<f64>, Option<u32>) {
+        (
+            self.appearance
+                .panel_opacity
+                .map(|opacity| opacity.clamp(0.0, 1.0)),
+            self.appearance.panel_radius,
+        )
+    }
+
+    /// Get the position of the login box, and its distance to the edges of the screen.
+    pub fn get_panel_position(&self) -> (PanelPosition, i32) {
+        (
+            self.appearance.panel_position,
+            self.appearance.panel_margin.max(0),
+        )
+    }
+
+    pub fn get_density(&self) -> Density {
+        self.appearance.density
+    }
+
+    pub fn get_theme_schedule(&self) -> Option<&ThemeSchedule> {
+        self.appearance.schedule.as_ref()
+    }
+
+    /// Get the theme and background that are scheduled for the given part of the day.
+    pub fn get_theme_variant(&self, period: Option<ThemePeriod>) -> Option<&ThemeVariant> {
+        Some(self.appearance.schedule.as_ref()?.get_variant(period?))
+    }
+
+    pub fn get_sys_commands(&self) -> &SystemCommands {
+        &self.commands
+    }
+
+    pub fn get_power_menu(&self) -> &PowerMenuSettings {
+        &self.power_menu
+    }
+
+    pub fn get_visibility(&self) -> &VisibilitySettings {
+        &self.visibility
+    }
+
+    pub fn get_greeting_markup(&self) -> bool {
+        self.appearance.greeting_markup
     }
 
     pub fn get_default_message(&self) -> String {
         self.appearance.greeting_msg.clone()
     }
+
+    /// Get the greeting message for the given user, which is the default one unless it's set for them.
+    pub fn get_user_greeting(&self, username: &str) -> String {
+        self.users
+            .per_user
+            .get(username)
+            .and_then(|settings| settings.greeting_msg.clone())
+            .unwrap_or_else(|| self.get_default_message())
+    }
+
+    pub fn get_toolkit(&self) -> &Toolkit {
+        &self.appearance.toolkit
+    }
+
+    pub fn get_mirror_layout(&self) -> bool {
+        self.appearance.mirror_layout
+    }
+
+    pub fn get_combine_prompts(&self) -> bool {
+        self.auth.combine_prompts
+    }
+
+    pub fn get_disable_password_ime(&self) -> bool {
+        self.auth.disable_password_ime
+    }
+
+    pub fn get_show_login_duration(&self) -> bool {
+        self.auth.show_login_duration
+    }
+
+    pub fn get_announce_passwordless(&self) -> bool {
+        self.auth.announce_passwordless
+    }
+
+    pub fn get_secure_attention_key(&self) -> Option<&str> {
+        self.auth.secure_attention_key.as_deref()
+    }
+
+    pub fn get_check_duplicate_login(&self) -> bool {
+        self.auth.check_duplicate_login
+    }
+
+    pub fn get_deny_system_users(&self) -> bool {
+        self.auth.deny_system_users
+    }
+
+    pub fn get_messages(&self) -> &MessageSettings {
+        &self.messages
+    }
+
+    pub fn get_unclean_shutdown_marker(&self) -> &Path {
+        Path::new(&self.boot.unclean_shutdown_marker)
+    }
+
+    pub fn get_unclean_shutdown_msg(&self) -> &str {
+        &self.boot.unclean_shutdown_msg
+    }
+
+    /// Get the hint shown when the selected user logs in for the first time, if it's enabled.
+    pub fn get_first_login_hint(&self) -> Option<&str> {
+        self.first_login
+            .hint
+            .then_some(self.first_login.message.as_str())
+    }
+
+    pub fn get_notifications_enabled(&self) -> bool {
+        self.notifications.enabled
+    }
+
+    pub fn get_clock_warning_enabled(&self) -> bool {
+        self.notifications.clock_warning
+    }
+
+    pub fn get_notification_sinks(&self) -> &[SinkConfig] {
+        &self.notifications.sinks
+    }
+
+    /// Get the time after which notifications with the given level are dismissed, if they are.
+    ///
+    /// Errors are kept until they're clicked away.
+    pub fn get_dismiss_timeout(&self, level: Level) -> Option<Duration> {
+        let dismiss_after = &self.notifications.dismiss_after;
+        match level {
+            Level::Info => dismiss_after.info,
+            Level::Warning => dismiss_after.warning,
+            Level::Error => None,
+        }
+    }
+
+    pub fn get_status_path(&self) -> Option<&Path> {
+        self.status.path.as_deref().map(Path::new)
+    }
+
+    pub fn get_status_interval(&self) -> Duration {
+        self.status.interval
+    }
+
+    pub fn get_metrics_path(&self) -> Option<&Path> {
+        self.metrics.path.as_deref().map(Path::new)
+    }
+
+    pub fn get_audit_path(&self) -> Option<&Path> {
+        self.audit.path.as_deref().map(Path::new)
+    }
+
+    /// Get whether the settings panel is enabled.
+    pub fn get_settings_panel_enabled(&self) -> bool {
+        self.settings_panel.enabled
+    }
+
+    pub fn get_settings_panel(&self) -> &SettingsPanel {
+        &self.settings_panel
+    }
+
+    /// Get the current values of the settings that can be changed from the settings panel.
+    pub fn get_panel_values(&self) -> PanelValues {
+        PanelValues {
+            background: (self.background.picked_from.as_ref())
+                .or(self.background.path.as_ref())
+                .cloned(),
+            greeting_msg: self.appearance.greeting_msg.clone(),
+            gtk_theme: self.appearance.gtk_theme.clone(),
+        }
+    }
+
+    /// Change the settings from the settings panel for the rest of this run.
+    pub fn set_panel_values(&mut self, values: PanelValues) {
+        self.background.path = values.background;
+        self.background.picked_from = None;
+        self.appearance.greeting_msg = values.greeting_msg;
+        self.appearance.gtk_theme = values.gtk_theme;
+        log_problems(self.validate_greeting_markup());
+        self.pick_random_background();
+    }
+
+    /// Save the settings from the settings panel to its drop-in file, so that they're kept after a restart.
+    ///
+    /// Unset values are left out, so that they fall back to the config file.
+    pub fn save_panel_values(&self, values: PanelValues) -> TomlFileResult<()> {
+        let drop_in = SettingsDropIn {
+            background: values.background,
+            greeting_msg: Some(values.greeting_msg),
+            gtk_theme: values.gtk_theme,
+        };
+
+        let path = Path::new(&self.settings_panel.path);
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        info!(path = %path.display(), "Saving the settings panel");
+        write(path, toml::to_string_pretty(&drop_in)?)?;
+        Ok(())
+    }
+
+    pub fn get_log_timezone(&self) -> Option<&str> {
+        self.logging.timezone.as_deref()
+    }
+
+    pub fn get_runtime_threads(&self) -> Option<usize> {
+        self.runtime.threads
+    }
+
+    /// Get the locale for the greeter's own process, if it overrides the one from the environment.
+    pub fn get_locale(&self) -> Option<&str> {
+        self.appearance.locale.as_deref()
+    }
+
+    /// Get the formatter for the times shown at the greeter, which uses the time zone of the clock.
+    pub fn get_time_formatter(&self) -> TimeFormatter {
+        TimeFormatter::new(
+            self.appearance.time_format.as_deref(),
+            self.appearance.hour_cycle,
+            self.widget.clock.timezone.clone(),
+        )
+    }
+
+    /// Get the config of the clock, with the time format from the `[appearance]` section.
+    ///
+    /// Unless a format is set for it, the clock shows the weekday along with the time of day.
+    pub fn get_clock_config(&self) -> ClockConfig {
+        let mut clock = self.widget.clock.clone();
+        if let Some(format) = &self.appearance.time_format {
+            clock.format = Some(format.clone());
+        } else if clock.format.is_none() {
+            clock.format = Some(format!("%a {}", self.get_time_formatter().format()));
+        }
+        clock
+    }
+
+    /// Get the environment variables for GTK in the greeter's own process.
+    pub fn get_gtk_env(&self) -> [(&'static str, Option<&str>); 2] {
+        [
+            ("GSK_RENDERER", self.runtime.gsk_renderer.as_deref()),
+            ("GDK_BACKEND", self.runtime.gdk_backend.as_deref()),
+        ]
+    }
+
+    pub fn get_badge_login(&self) -> &BadgeLoginSettings {
+        &self.badge_login
+    }
+
+    pub fn get_maintenance(&self) -> &MaintenanceSettings {
+        &self.maintenance
+    }
+
+    /// Get the settings for the on-screen keyboard, if a command is set for it.
+    pub fn get_osk(&self) -> Option<&OskSettings> {
+        if self.osk.command.is_empty() {
+            None
+        } else {
+            Some(&self.osk)
+        }
+    }
+
+    /// Get the legal notice that has to be accepted before logging in, if any.
+    ///
+    /// This reads the notice file every time, so it should only be called once.
+    /// Get the legal notice to accept before logging in, or why it couldn't be loaded.
+    pub fn get_legal_notice(&self) -> Result<Option<String>, String> {
+        self.legal_notice.read()
+    }
+
+    /// Get the first rule that preselects a user and session for the given hostname.
+    pub fn get_host_preselect(&self, hostname: &str) -> Option<&HostPreselect> {
+        let patterns = self.patterns.hostnames.iter();
+        self.preselect
+            .iter()
+            .zip(patterns)
+            .find(|(_, pattern)| {
+                pattern
+                    .as_ref()
+                    .is_some_and(|pattern| pattern.matches(hostname))
+            })
+            .map(|(rule, _)| rule)
+    }
+
+    /// Get the first hint for the entry of the given PAM prompt.
+    pub fn get_prompt_hint(&self, prompt: &str) -> Option<&PromptHint> {
+        let patterns = self.patterns.prompts.iter();
+        self.prompt_hints
+            .iter()
+            .zip(patterns)
+            .find(|(_, regex)| regex.as_ref().is_some_and(|regex| regex.is_match(prompt)))
+            .map(|(hint, _)| hint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod Config {
+        use super::super::*;
+
+        const PRESELECT_RULES: &str = r#"
+            [[preselect]]
+            hostname = "["
+            user = "invalid"
+
+            [[preselect]]
+            hostname = "lab-*"
+            user = "student"
+            session = "Sway"
+
+            [[preselect]]
+            hostname = "*kiosk"
+            user = "kiosk"
+        "#;
+
+        #[test_case("lab-01" => Some("student".to_string()); "pattern")]
+        #[test_case("kiosk" => Some("kiosk".to_string()); "exact")]
+        #[test_case("lab-kiosk" => Some("student".to_string()); "first match")]
+        #[test_case("office" => None; "no match")]
+        fn get_host_preselect(hostname: &str) -> Option<String> {
+            let mut config: Config = toml::from_str(PRESELECT_RULES).expect("Invalid config");
+            config.compile_patterns();
+            config
+                .get_host_preselect(hostname)
+                .map(|rule| rule.user.clone())
+        }
+
+        const PROMPT_HINTS: &str = r#"
+            [[prompt_hints]]
+            prompt = "("
+            placeholder = "invalid"
+
+            [[prompt_hints]]
+            prompt = "(?i)one-time password"
+            placeholder = "6-digit code from your authenticator"
+
+            [[prompt_hints]]
+            prompt = "^Password"
+            tooltip = "Your domain password"
+        "#;
+
+        #[test_case("One-time password:" => Some("6-digit code from your authenticator".to_string()); "placeholder")]
+        #[test_case("Password:" => Some("Your domain password".to_string()); "tooltip")]
+        #[test_case("Username:" => None; "no match")]
+        fn get_prompt_hint(prompt: &str) -> Option<String> {
+            let mut config: Config = toml::from_str(PROMPT_HINTS).expect("Invalid config");
+            config.compile_patterns();
+            let hint = config.get_prompt_hint(prompt)?;
+            hint.placeholder.clone().or_else(|| hint.tooltip.clone())
+        }
+
+        #[test_case(r#"Need help? Visit <a href="https://help.example.com">the help page</a>"# => true; "link")]
+        #[test_case("Welcome <b>back!" => false; "unclosed tag")]
+        #[test_case("Tom & Jerry" => false; "unescaped ampersand")]
+        fn validate_greeting_markup(greeting: &str) -> bool {
+            let mut config = Config::default();
+            config.appearance.greeting_msg = greeting.to_string();
+            config.appearance.greeting_markup = true;
+            config.validate_greeting_markup();
+            config.get_greeting_markup()
+        }
+
+        #[test_case("" => (PanelPosition::Center, 0); "default")]
+        #[test_case("panel_position = \"top-right\"\npanel_margin = 40" => (PanelPosition::TopRight, 40); "corner")]
+        #[test_case("panel_position = \"left\"\npanel_margin = -5" => (PanelPosition::Left, 0); "negative margin")]
+        fn get_panel_position(appearance: &str) -> (PanelPosition, i32) {
+            let config: Config =
+                toml::from_str(&format!("[appearance]\n{appearance}")).expect("Invalid config");
+            config.get_panel_position()
+        }
+
+        const USERS: &str = r#"
+            [env]
+            EDITOR = "vim"
+            LANG = "en_US.UTF-8"
+
+            [users.alice]
+            greeting_msg = "Hi Alice"
+            session = "GNOME"
+
+            [users.bob.env]
+            LANG = "de_DE.UTF-8"
+            GDK_SCALE = "2"
+        "#;
+
+        #[test_case(Some("bob") => vec!["EDITOR=vim", "GDK_SCALE=2", "LANG=de_DE.UTF-8"]; "overridden")]
+        #[test_case(Some("alice") => vec!["EDITOR=vim", "LANG=en_US.UTF-8"]; "no user env")]
+        #[test_case(Some("carol") => vec!["EDITOR=vim", "LANG=en_US.UTF-8"]; "unknown user")]
+        #[test_case(None => vec!["EDITOR=vim", "LANG=en_US.UTF-8"]; "no user")]
+        fn get_user_env(username: Option<&str>) -> Vec<String> {
+            let config: Config = toml::from_str(USERS).expect("Invalid config");
+            let mut env: Vec<_> = config
+                .get_user_env(username)
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect();
+            env.sort();
+            env
+        }
+
+        #[test]
+        fn get_uid_range() {
+            let config: Config = toml::from_str(&format!("[users]\nmin_uid = 500\n{USERS}"))
+                .expect("Invalid config");
+            assert_eq!(config.get_uid_range(), (Some(500), None));
+            assert_eq!(config.get_user_greeting("alice"), "Hi Alice");
+        }
+
+        #[test_case("alice" => Some("GNOME".to_string()); "set")]
+        #[test_case("bob" => None; "not set")]
+        fn get_user_session(username: &str) -> Option<String> {
+            let config: Config =
+                toml::from_str(&format!("[sessions]\ndefault = \"sway.desktop\"\n{USERS}"))
+                    .expect("Invalid config");
+            config.get_user_session(username).map(str::to_string)
+        }
+
+        #[test_case("alice" => "Hi Alice"; "set")]
+        #[test_case("bob" => GREETING_MSG; "default")]
+        fn get_user_greeting(username: &str) -> String {
+            let config: Config = toml::from_str(USERS).expect("Invalid config");
+            config.get_user_greeting(username)
+        }
+
+        #[test_case(Level::Info => Some(Duration::from_secs(10)); "info")]
+        #[test_case(Level::Warning => None; "unset")]
+        #[test_case(Level::Error => None; "error")]
+        fn get_dismiss_timeout(level: Level) -> Option<Duration> {
+            let config: Config = toml::from_str("[notifications.dismiss_after]\ninfo = \"10s\"")
+                .expect("Invalid config");
+            config.get_dismiss_timeout(level)
+        }
+
+        #[test]
+        fn get_clock_config() {
+            let config: Config = toml::from_str(
+                "[appearance]\ntime_format = \"%d.%m. %H:%M\"\n[widget.clock]\nformat = \"%a %H:%M\"",
+            )
+            .expect("Invalid config");
+            assert_eq!(
+                config.get_clock_config().format.as_deref(),
+                Some("%d.%m. %H:%M")
+            );
+        }
+
+        #[test]
+        fn limit_resources() {
+            let mut config: Config = toml::from_str(
+                r#"
+                    [background]
+                    path = "/usr/share/backgrounds/greeter.jpg"
+
+                    [appearance]
+                    panel_blur = 20.0
+
+                    [appearance.schedule.night]
+                    background = "/usr/share/backgrounds/greeter-night.jpg"
+                "#,
+            )
+            .expect("Invalid config");
+            config.limit_resources();
+
+            assert_eq!(config.get_background(), None);
+            assert_eq!(
+                config
+                    .get_theme_variant(Some(ThemePeriod::Night))
+                    .unwrap()
+                    .background,
+                None
+            );
+            assert_eq!(config.get_panel_backdrop().0, 0.0);
+            assert!(!config.get_animations());
+            assert!(!config.widget.clock.enabled);
+        }
+
+        #[test]
+        fn check() {
+            let mut config: Config = toml::from_str(
+                r#"
+                    [background]
+                    path = "/nonexistent/background.jpg"
+
+                    [commands]
+                    reboot = []
+
+                    [env]
+                    "A=B" = "c"
+
+                    [[preselect]]
+                    hostname = "["
+                    user = "invalid"
+                "#,
+            )
+            .expect("Invalid config");
+            let problems = config.check();
+
+            assert_eq!(problems.len(), 4);
+            assert!(problems[0].starts_with("Invalid hostname pattern '['"));
+            assert_eq!(
+                problems[1..],
+                [
+                    "Background '/nonexistent/background.jpg' doesn't exist",
+                    "The reboot command is empty",
+                    "Invalid environment variable 'A=B'",
+                ]
+            );
+        }
+
+        #[test]
+        fn check_default() {
+            assert!(Config::default().check().is_empty());
+        }
+
+        #[test_case("color = \"#102030\"" => vec!["rgb(16,32,48)"]; "color")]
+        #[test_case("color = \"red\"\ngradient = [\"#102030\", \"blue\"]" => vec!["rgb(16,32,48)", "rgb(0,0,255)"];
+            "gradient")]
+        #[test_case("gradient = [\"#102030\", \"nope\"]" => vec!["rgb(16,32,48)"]; "invalid stop")]
+        #[test_case("" => Vec::<String>::new(); "unset")]
+        fn get_background_colors(background: &str) -> Vec<String> {
+            let config: Config =
+                toml::from_str(&format!("[background]\n{background}")).expect("Invalid config");
+            config
+                .get_background_colors()
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        }
+
+        #[test]
+        fn with_overrides() {
+            let table =
+                toml::from_str("[appearance]\ngreeting_msg = \"Hello\"\ndensity = \"compact\"")
+                    .expect("Invalid config");
+            let overrides = [
+                "appearance.greeting_msg=Hi there",
+                "auth.combine_prompts=true",
+            ]
+            .map(|text| text.parse::<ConfigOverride>().expect("Invalid override"));
+            let config = Config::with_overrides(table, &overrides);
+
+            assert_eq!(config.get_default_message(), "Hi there");
+            assert_eq!(config.get_density(), Density::Compact);
+            assert!(config.get_combine_prompts());
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod ConfigOverride {
+        use super::super::*;
+
+        fn value(text: &str) -> toml::Value {
+            toml::from_str::<toml::Table>(&format!("value = {text}")).unwrap()["value"].clone()
+        }
+
+        #[test_case("background.fit=Cover" => Ok(("background/fit".into(), value("'Cover'"))); "string")]
+        #[test_case(
+            "auth.combine_prompts = true"
+            => Ok(("auth/combine_prompts".into(), value("true")));
+            "bool"
+        )]
+        #[test_case(
+            "widget.clock.label_width=200"
+            => Ok(("widget/clock/label_width".into(), value("200")));
+            "nested"
+        )]
+        #[test_case(
+            r#"commands.reboot=["loginctl", "reboot"]"#
+            => Ok(("commands/reboot".into(), value("['loginctl', 'reboot']")));
+            "array"
+        )]
+        #[test_case(
+            "background.fit"
+            => Err("Expected SECTION.KEY=VALUE, found 'background.fit'".into());
+            "no value"
+        )]
+        #[test_case("background..fit=Cover" => Err("Invalid key 'background..fit'".into()); "empty section")]
+        fn from_str(text: &str) -> Result<(String, toml::Value), String> {
+            text.parse::<ConfigOverride>()
+                .map(|config_override| (config_override.path.join("/"), config_override.value))
+        }
+
+        #[test]
+        fn invalid_override() {
+            let overrides = [
+                "appearance.greeting_msg=2024",
+                "appearance.gtk_theme=Adwaita",
+            ]
+            .map(|text| text.parse::<ConfigOverride>().unwrap());
+            let config = Config::with_overrides(toml::Table::new(), &overrides);
+            assert_eq!(config.get_default_message(), GREETING_MSG);
+            assert_eq!(config.appearance.gtk_theme.as_deref(), Some("Adwaita"));
+        }
+
+        #[test]
+        fn apply_to_non_table() {
+            let mut table: toml::Table = toml::from_str("background = 1").unwrap();
+            let config_override: ConfigOverride = "background.fit=Cover".parse().unwrap();
+            config_override.apply(&mut table);
+            assert_eq!(table, toml::from_str("background = 1").unwrap());
+        }
+    }
+
+    mod fill_placeholders {
+        use super::super::*;
+
+        #[test_case("Login failed: {reason}", &[("reason", "Wrong password")] => "Login failed: Wrong password"; "one")]
+        #[test_case("{user}: {reason} ({reason})", &[("user", "alice"), ("reason", "Locked")] => "alice: Locked (Locked)"; "repeated")]
+        #[test_case("Press {key}", &[] => "Press {key}"; "missing value")]
+        #[test_case("Failed", &[("reason", "Locked")] => "Failed"; "unused value")]
+        fn fill_placeholders(message: &str, values: &[(&str, &str)]) -> String {
+            super::super::fill_placeholders(message, values)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod SettingsPanel {
+        use std::fs::write;
+
+        use super::super::*;
+        use crate::testutil::TempDir;
+
+        #[test]
+        fn drop_in() {
+            let dir = TempDir::new("settings");
+            let config_path = dir.join("regreet.toml");
+            let drop_in_path = dir.join("settings.toml");
+            write(
+                &config_path,
+                format!(
+                    "[appearance]\ngreeting_msg = \"Hello\"\ngtk_theme = \"Adwaita\"\n\
+                    [settings_panel]\nenabled = true\npath = {:?}",
+                    drop_in_path.display().to_string()
+                ),
+            )
+            .expect("Couldn't create test config");
+
+            let values = PanelValues {
+                background: None,
+                greeting_msg: "Welcome to the lab".to_string(),
+                gtk_theme: Some("HighContrast".to_string()),
+            };
+            let saved = Config::new(&config_path, &[]).save_panel_values(values.clone());
+            let loaded = Config::new(&config_path, &[]).get_panel_values();
+            let overridden = Config::new(
+                &config_path,
+                &["appearance.greeting_msg=Hi"
+                    .parse()
+                    .expect("Invalid override")],
+            )
+            .get_default_message();
+
+            assert!(saved.is_ok());
+            assert_eq!(loaded, values);
+            assert_eq!(overridden, "Hi");
+        }
+
+        #[test]
+        fn ignores_other_keys() {
+            let dir = TempDir::new("settings-keys");
+            let config_path = dir.join("regreet.toml");
+            let drop_in_path = dir.join("settings.toml");
+            write(
+                &config_path,
+                format!(
+                    "[settings_panel]\nenabled = true\npath = {:?}",
+                    drop_in_path.display().to_string()
+                ),
+            )
+            .expect("Couldn't create test config");
+            write(
+                &drop_in_path,
+                "greeting_msg = \"Hello\"\n[env]\nLD_PRELOAD = \"/tmp/evil.so\"\n\
+                [commands]\nreboot = [\"true\"]",
+            )
+            .expect("Couldn't create test drop-in");
+
+            let config = Config::new(&config_path, &[]);
+
+            assert_eq!(config.get_default_message(), "Hello");
+            assert!(config.get_env().is_empty());
+            assert_ne!(config.get_sys_commands().reboot, ["true"]);
+        }
+    }
+
+    mod pick_file {
+        use std::fs::{create_dir_all, write};
+
+        use super::super::*;
+        use crate::testutil::TempDir;
+
+        #[test]
+        fn pick_file() {
+            let dir = TempDir::new("backgrounds");
+            create_dir_all(dir.join("nested.jpg")).expect("Couldn't create test directory");
+            for file in ["b.PNG", "a.jpg", "notes.txt"] {
+                write(dir.join(file), "").expect("Couldn't create test file");
+            }
+            let extensions = ["jpg".to_string(), ".png".to_string()];
+            let picked: Vec<_> = [
+                (Some(&extensions[..]), 0),
+                (Some(&extensions[..]), 3),
+                (None, 2),
+            ]
+            .into_iter()
+            .map(|(extensions, seed)| super::super::pick_file(&dir, extensions, seed))
+            .collect();
+            let empty = super::super::pick_file(&dir, Some(&["gif".to_string()]), 0);
+
+            let picked: Vec<_> = picked
+                .into_iter()
+                .map(|path| path.unwrap().unwrap().file_name().unwrap().to_owned())
+                .collect();
+            assert_eq!(picked, ["a.jpg", "b.PNG", "notes.txt"]);
+            assert!(empty.unwrap().is_none());
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod ThemeSchedule {
+        use super::super::*;
+
+        #[test_case("07:00", "19:00", 12 * 60 => Some(ThemePeriod::Day); "day")]
+        #[test_case("07:00", "19:00", 7 * 60 => Some(ThemePeriod::Day); "day start")]
+        #[test_case("07:00", "19:00", 19 * 60 => Some(ThemePeriod::Night); "night start")]
+        #[test_case("07:00", "19:00", 3 * 60 => Some(ThemePeriod::Night); "early morning")]
+        #[test_case("22:00", "06:00", 23 * 60 => Some(ThemePeriod::Day); "day over midnight")]
+        #[test_case("22:00", "06:00", 12 * 60 => Some(ThemePeriod::Night); "night within a day")]
+        #[test_case("07:00", "07:00", 7 * 60 => Some(ThemePeriod::Night); "empty day")]
+        #[test_case("7:00", "19:00", 12 * 60 => None; "short hour")]
+        #[test_case("07:00", "24:00", 12 * 60 => None; "out of range")]
+        fn period_at(day_start: &str, night_start: &str, minutes: u16) -> Option<ThemePeriod> {
+            let schedule = ThemeSchedule {
+                day_start: day_start.to_string(),
+                night_start: night_start.to_string(),
+                day: ThemeVariant::default(),
+                night: ThemeVariant::default(),
+            };
+            schedule.period_at(minutes)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod SessionFilter {
+        use super::super::*;
+
+        #[test_case("" => true; "default")]
+        #[test_case("deny = [\"gnome-classic.desktop\"]" => false; "denied")]
+        #[test_case("deny = [\"gnome.desktop\"]" => true; "other denied")]
+        #[test_case("allow = [\"gnome-classic.desktop\"]" => true; "allowed")]
+        #[test_case("allow = [\"sway.desktop\"]" => false; "not allowed")]
+        #[test_case("allow = []" => false; "none allowed")]
+        #[test_case(
+            "allow = [\"gnome-classic.desktop\"]\ndeny = [\"gnome-classic.desktop\"]" => false;
+            "allowed and denied"
+        )]
+        fn is_shown(filter: &str) -> bool {
+            let config: Config =
+                toml::from_str(&format!("[sessions]\n{filter}")).expect("Invalid config");
+            config
+                .get_session_filter()
+                .is_shown("gnome-classic.desktop")
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod BadgeLoginSettings {
+        use super::super::*;
+
+        #[test_case("alice", None => ("alice", None); "username only")]
+        #[test_case("alice\n", None => ("alice", None); "trailing newline")]
+        #[test_case("alice:1234", None => ("alice:1234", None); "no separator")]
+        #[test_case("alice:1234", Some(":") => ("alice", Some("1234")); "with credential")]
+        #[test_case("alice:12:34", Some(":") => ("alice", Some("12:34")); "separator in credential")]
+        #[test_case("alice", Some(":") => ("alice", None); "missing credential")]
+        #[test_case("alice:1234", Some("") => ("alice:1234", None); "empty separator")]
+        fn split_scan<'a>(scan: &'a str, separator: Option<&str>) -> (&'a str, Option<&'a str>) {
+            let settings = BadgeLoginSettings {
+                enabled: true,
+                credential_separator: separator.map(String::from),
+            };
+            settings.split_scan(scan)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod LegalNoticeSettings {
+        use super::super::*;
+
+        #[test_case(None, Some("Authorized use only.\n") => Ok(Some("Authorized use only.".to_string())); "text")]
+        #[test_case(Some("/nonexistent/notice"), Some("Authorized use only.") => Ok(Some("Authorized use only.".to_string())); "fallback to text")]
+        #[test_case(None, Some(" \n") => Ok(None); "empty text")]
+        #[test_case(None, None => Ok(None); "none")]
+        fn read(path: Option<&str>, text: Option<&str>) -> Result<Option<String>, String> {
+            let settings = LegalNoticeSettings {
+                text: text.map(String::from),
+                path: path.map(String::from),
+            };
+            settings.read()
+        }
+
+        #[test]
+        fn missing_file() {
+            let settings = LegalNoticeSettings {
+                text: None,
+                path: Some("/nonexistent/notice".to_string()),
+            };
+            let problem = settings
+                .read()
+                .expect_err("A missing notice must not be skipped");
+            assert!(problem.contains("/nonexistent/notice"));
+        }
+    }
 }
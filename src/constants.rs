@@ -33,6 +33,10 @@ pub const CSS_PATH: &str = concatcp!(GREETD_CONFIG_DIR, "/", GREETER_NAME, ".css
 const CACHE_DIR: &str = env_or!("CACHE_DIR", concatcp!("/var/cache/", GREETER_NAME));
 /// Path to the cache file
 pub const CACHE_PATH: &str = concatcp!(CACHE_DIR, "/cache.toml");
+/// Path to the file counting the greeter's starts that didn't run long enough to be considered stable
+pub const CRASH_BREADCRUMB_PATH: &str = concatcp!(CACHE_DIR, "/unstable-starts");
+/// Default path to the drop-in config file that the settings panel saves to
+pub const SETTINGS_DROP_IN_PATH: &str = concatcp!(CACHE_DIR, "/settings.toml");
 
 /// The directory for system log files
 const LOG_DIR: &str = env_or!("LOG_DIR", concatcp!("/var/log/", GREETER_NAME));
@@ -43,10 +47,24 @@ pub const LOG_PATH: &str = concatcp!(LOG_DIR, "/log");
 pub const REBOOT_CMD: &str = env_or!("REBOOT_CMD", "reboot");
 /// Default command for shutting down
 pub const POWEROFF_CMD: &str = env_or!("POWEROFF_CMD", "poweroff");
+/// Default command for suspending
+pub const SUSPEND_CMD: &str = env_or!("SUSPEND_CMD", "systemctl suspend");
+/// Default command for hibernating
+pub const HIBERNATE_CMD: &str = env_or!("HIBERNATE_CMD", "systemctl hibernate");
 
 /// Default greeting message
 pub const GREETING_MSG: &str = "Welcome back!";
 
+/// Default path to a file whose existence means that the previous shutdown was unclean
+pub const UNCLEAN_SHUTDOWN_MARKER: &str = env_or!(
+    "UNCLEAN_SHUTDOWN_MARKER",
+    concatcp!("/run/", GREETER_NAME, "/unclean-shutdown")
+);
+/// Default message shown after an unclean shutdown
+pub const UNCLEAN_SHUTDOWN_MSG: &str = "System was not shut down cleanly";
+/// Default hint shown when the selected user logs in for the first time
+pub const FIRST_LOGIN_MSG: &str = "First login may take longer while the desktop is prepared";
+
 /// `:`-separated search path for `login.defs` file.
 ///
 /// By default this file is at `/etc/login.defs`, however some distros (e.g. Tumbleweed) move it to other locations.
@@ -67,7 +85,7 @@ lazy_static! {
         const ENV: &str = env_or!("LOGIN_DEFS_UID_MIN", formatcp!("{DEFAULT}"));
 
         ENV.parse()
-            .map_err(|e| error!("Failed to parse LOGIN_DEFS_UID_MIN='{ENV}': {e}. This is a compile time mistake!"))
+            .map_err(|e| error!(error = %e, "Failed to parse LOGIN_DEFS_UID_MIN='{ENV}'. This is a compile time mistake!"))
             .unwrap_or(DEFAULT)
     };
 
@@ -81,7 +99,7 @@ lazy_static! {
         const ENV: &str = env_or!("LOGIN_DEFS_UID_MAX", formatcp!("{DEFAULT}"));
 
         ENV.parse()
-            .map_err(|e| error!("Failed to parse LOGIN_DEFS_UID_MAX='{ENV}': {e}. This is a compile time mistake!"))
+            .map_err(|e| error!(error = %e, "Failed to parse LOGIN_DEFS_UID_MAX='{ENV}'. This is a compile time mistake!"))
             .unwrap_or(DEFAULT)
     };
 }
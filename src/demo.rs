@@ -0,0 +1,190 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Fake greetd used in demo mode
+//!
+//! Each flow is a fixed sequence of auth messages, so that every path through the login UI can be tried out without
+//! greetd.
+
+use clap::ValueEnum;
+use greetd_ipc::{AuthMessageType, ErrorType, Response};
+
+const DEMO_AUTH_MSG_OTP: &str = "One-Time Password:";
+const DEMO_AUTH_MSG_PASSWD: &str = "Password:";
+const DEMO_AUTH_MSG_FINGERPRINT: &str = "Place your finger on the fingerprint reader";
+const DEMO_AUTH_MSG_EXPIRY: &str = "Your password will expire in 3 days";
+const DEMO_AUTH_MSG_ERROR: &str = "pam_authenticate: AUTH_ERR";
+const DEMO_OTP: &str = "0248";
+const DEMO_PASSWD: &str = "pass";
+
+/// A built-in authentication flow for demo mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum DemoFlow {
+    /// Ask for a password
+    Password,
+    /// Ask for a one-time password, then a password
+    #[default]
+    PasswordOtp,
+    /// Show a fingerprint prompt that needs no input
+    Fingerprint,
+    /// Show an informative error, then ask for a password
+    InformativeError,
+    /// Log in without any authentication
+    Passwordless,
+}
+
+/// A single auth message sent by the fake greetd
+struct DemoStep {
+    message_type: AuthMessageType,
+    message: &'static str,
+    /// The input that greetd expects, or `None` for info and error messages
+    expected: Option<&'static str>,
+}
+
+impl DemoStep {
+    fn secret(message: &'static str, expected: &'static str) -> Self {
+        Self {
+            message_type: AuthMessageType::Secret,
+            message,
+            expected: Some(expected),
+        }
+    }
+}
+
+impl DemoFlow {
+    fn steps(self) -> Vec<DemoStep> {
+        match self {
+            Self::Password => vec![DemoStep::secret(DEMO_AUTH_MSG_PASSWD, DEMO_PASSWD)],
+            Self::PasswordOtp => vec![
+                DemoStep::secret(DEMO_AUTH_MSG_OTP, DEMO_OTP),
+                DemoStep::secret(DEMO_AUTH_MSG_PASSWD, DEMO_PASSWD),
+            ],
+            Self::Fingerprint => vec![DemoStep {
+                message_type: AuthMessageType::Info,
+                message: DEMO_AUTH_MSG_FINGERPRINT,
+                expected: None,
+            }],
+            Self::InformativeError => vec![
+                DemoStep {
+                    message_type: AuthMessageType::Error,
+                    message: DEMO_AUTH_MSG_EXPIRY,
+                    expected: None,
+                },
+                DemoStep::secret(DEMO_AUTH_MSG_PASSWD, DEMO_PASSWD),
+            ],
+            Self::Passwordless => Vec::new(),
+        }
+    }
+}
+
+/// A fake greetd that authenticates using a demo flow
+pub struct DemoGreetd {
+    flow: DemoFlow,
+    /// Index of the step whose auth message was sent last
+    current: usize,
+}
+
+impl DemoGreetd {
+    pub fn new(flow: DemoFlow) -> Self {
+        let inputs: Vec<_> = flow
+            .steps()
+            .into_iter()
+            .filter_map(|step| step.expected)
+            .collect();
+        warn!("Run as demo: [flow: {flow:?}, inputs: {inputs:?}]");
+
+        Self { flow, current: 0 }
+    }
+
+    /// Start a login attempt from the first step.
+    pub fn create_session(&mut self) -> Response {
+        self.current = 0;
+        self.response()
+    }
+
+    /// Check the input for the current step, and move on to the next one if it's correct.
+    pub fn send_auth_response(&mut self, input: Option<&str>) -> Response {
+        let expected = self.step().and_then(|step| step.expected);
+        if input != expected {
+            // Start over, like PAM would on the next attempt.
+            self.current = 0;
+            return Response::Error {
+                error_type: ErrorType::AuthError,
+                description: DEMO_AUTH_MSG_ERROR.to_string(),
+            };
+        }
+
+        self.current += 1;
+        self.response()
+    }
+
+    /// Whether the current step only shows a message, and needs no input from the user.
+    ///
+    /// This is used to simulate delays, such as scanning a fingerprint.
+    pub fn shows_message(&self) -> bool {
+        self.step().is_some_and(|step| step.expected.is_none())
+    }
+
+    fn step(&self) -> Option<DemoStep> {
+        self.flow.steps().into_iter().nth(self.current)
+    }
+
+    /// The auth message for the current step, or success if all steps are done.
+    fn response(&self) -> Response {
+        if let Some(step) = self.step() {
+            Response::AuthMessage {
+                auth_message_type: step.message_type,
+                auth_message: step.message.to_string(),
+            }
+        } else {
+            Response::Success
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod DemoGreetd {
+        use super::super::*;
+
+        /// Go through a flow with the given inputs, and return whether it succeeded.
+        fn run(flow: DemoFlow, inputs: &[Option<&str>]) -> bool {
+            let mut greetd = DemoGreetd::new(flow);
+            let mut response = greetd.create_session();
+            for input in inputs {
+                if !matches!(response, Response::AuthMessage { .. }) {
+                    return false;
+                }
+                response = greetd.send_auth_response(*input);
+            }
+            matches!(response, Response::Success)
+        }
+
+        #[test_case(DemoFlow::Password, &[Some(DEMO_PASSWD)] => true; "password")]
+        #[test_case(DemoFlow::Password, &[Some(DEMO_OTP)] => false; "wrong password")]
+        #[test_case(DemoFlow::PasswordOtp, &[Some(DEMO_OTP), Some(DEMO_PASSWD)] => true; "password otp")]
+        #[test_case(DemoFlow::PasswordOtp, &[Some(DEMO_PASSWD)] => false; "otp skipped")]
+        #[test_case(DemoFlow::Fingerprint, &[None] => true; "fingerprint")]
+        #[test_case(DemoFlow::InformativeError, &[None, Some(DEMO_PASSWD)] => true; "informative error")]
+        #[test_case(DemoFlow::Passwordless, &[] => true; "passwordless")]
+        fn flow(flow: DemoFlow, inputs: &[Option<&str>]) -> bool {
+            run(flow, inputs)
+        }
+
+        #[test]
+        fn retry_after_error() {
+            let mut greetd = DemoGreetd::new(DemoFlow::PasswordOtp);
+            greetd.create_session();
+            assert!(matches!(
+                greetd.send_auth_response(Some("wrong")),
+                Response::Error { .. }
+            ));
+            assert!(matches!(
+                greetd.create_session(),
+                Response::AuthMessage { auth_message, .. } if auth_message == DEMO_AUTH_MSG_OTP
+            ));
+        }
+    }
+}
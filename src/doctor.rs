@@ -0,0 +1,150 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Self-diagnostic checks, eg. for a blank screen after installing the greeter
+//!
+//! These are run by `regreet doctor`. They're most accurate when run as the user that greetd runs the greeter as,
+//! since the greeter's own permissions decide whether it can reach greetd and write its cache.
+
+use std::env;
+use std::fs::{create_dir_all, remove_file, write, File};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use relm4::gtk;
+
+use crate::client::GREETD_SOCK_ENV_VAR;
+use crate::config::{Config, ConfigOverride};
+use crate::constants::CACHE_PATH;
+use crate::sysutil::SysUtil;
+
+/// Outcome of a single check, with details for the user
+pub type CheckOutcome = Result<String, String>;
+
+/// Run all checks, returning the name and the outcome of each.
+pub fn run_checks(
+    config_path: &Path,
+    overrides: &[ConfigOverride],
+) -> Vec<(&'static str, CheckOutcome)> {
+    let config = Config::new(config_path, overrides);
+    let cache_dir = Path::new(CACHE_PATH).parent().unwrap_or(Path::new("/"));
+    vec![
+        ("Config file", check_config(config_path)),
+        ("greetd socket", check_greetd_socket()),
+        ("Sessions", check_sessions(&config)),
+        ("Cache directory", check_writable_dir(cache_dir)),
+        ("Background", check_background(&config)),
+        ("GTK", check_gtk()),
+    ]
+}
+
+/// Check that the config file can be loaded and has no problems.
+fn check_config(path: &Path) -> CheckOutcome {
+    if !path.exists() {
+        // The greeter runs with the defaults then.
+        return Ok(format!(
+            "'{}' doesn't exist, so using the defaults",
+            path.display()
+        ));
+    }
+    let problems = Config::check_file(path);
+    if problems.is_empty() {
+        Ok(format!("'{}' has no problems", path.display()))
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+/// Check that greetd's socket can be connected to.
+fn check_greetd_socket() -> CheckOutcome {
+    let sock_path = env::var(GREETD_SOCK_ENV_VAR).map_err(|_| {
+        format!("{GREETD_SOCK_ENV_VAR} isn't set, so this isn't run by greetd (or greetd isn't running)")
+    })?;
+    match UnixStream::connect(&sock_path) {
+        Ok(_) => Ok(format!("Connected to '{sock_path}'")),
+        Err(err) => Err(format!("Couldn't connect to '{sock_path}': {err}")),
+    }
+}
+
+/// Check that there are sessions to start.
+fn check_sessions(config: &Config) -> CheckOutcome {
+    let sys_util =
+        SysUtil::new(config).map_err(|err| format!("Couldn't read users and sessions: {err}"))?;
+    let count = sys_util.get_sessions().len();
+    let malformed = sys_util.get_malformed_sessions().len();
+    if count == 0 {
+        Err("No sessions found in the X11 and Wayland session directories".to_string())
+    } else if malformed > 0 {
+        Ok(format!(
+            "Found {count} session(s), {malformed} of them malformed"
+        ))
+    } else {
+        Ok(format!("Found {count} session(s)"))
+    }
+}
+
+/// Check that files can be created in the directory, creating it like the greeter would.
+fn check_writable_dir(dir: &Path) -> CheckOutcome {
+    let probe = dir.join(format!(".regreet-doctor-{}", std::process::id()));
+    let result = create_dir_all(dir).and_then(|()| write(&probe, ""));
+    // The probe may have been created even if writing it failed.
+    let _ = remove_file(&probe);
+    match result {
+        Ok(()) => Ok(format!("'{}' is writable", dir.display())),
+        Err(err) => Err(format!("Couldn't write to '{}': {err}", dir.display())),
+    }
+}
+
+/// Check that the background can be read.
+fn check_background(config: &Config) -> CheckOutcome {
+    let path = if let Some(path) = config.get_background() {
+        path
+    } else {
+        return Ok("No background set".to_string());
+    };
+    match File::open(path) {
+        Ok(_) => Ok(format!("'{path}' is readable")),
+        Err(err) => Err(format!("Couldn't read '{path}': {err}")),
+    }
+}
+
+/// Check that GTK can connect to a display.
+fn check_gtk() -> CheckOutcome {
+    match gtk::init() {
+        Ok(()) => Ok("Initialized GTK".to_string()),
+        Err(err) => Err(format!(
+            "Couldn't initialize GTK, eg. because this isn't run inside a compositor: {err}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    mod check_writable_dir {
+        use super::*;
+
+        #[test]
+        fn writable() {
+            let dir = TempDir::new("doctor");
+            let outcome = check_writable_dir(&dir.join("cache"));
+            let left = dir.join("cache").read_dir().map(Iterator::count);
+
+            assert!(outcome.is_ok());
+            assert_eq!(left.ok(), Some(0));
+        }
+
+        #[test]
+        fn under_file() {
+            let dir = TempDir::new("doctor-file");
+            let file = dir.join("file");
+            write(&file, "").expect("Couldn't create test file");
+            let outcome = check_writable_dir(&file.join("cache"));
+
+            assert!(outcome.is_err());
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Handing off to another greeter when ReGreet can't show its UI
+//!
+//! greetd restarts a greeter that exits, so a greeter that can't start leaves the machine on a black screen without
+//! any way to log in. A fallback greeter, eg. a text-based one like tuigreet, is a safety net for this.
+//!
+//! Crashes that can't be caught, eg. in a GPU driver, are detected through a breadcrumb file counting the starts that
+//! haven't run long enough to be stable yet.
+
+use std::fs::{create_dir_all, read_to_string, remove_file, write};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::panic;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// Number of unstable starts in a row after which the fallback greeter is started instead
+pub const CRASH_LOOP_STARTS: u32 = 3;
+/// Time after which the greeter is considered stable, so that its start doesn't count towards a crash loop
+pub const STABLE_AFTER: Duration = Duration::from_secs(30);
+
+/// Replace the greeter's process with the fallback greeter.
+///
+/// The fallback greeter inherits the environment, including greetd's socket, so that it can log users in just like
+/// ReGreet. This only returns if it couldn't be started.
+pub fn exec_fallback_greeter(command: &[String]) -> io::Error {
+    let (program, args) = if let Some(split) = command.split_first() {
+        split
+    } else {
+        return io::Error::new(io::ErrorKind::InvalidInput, "The command is empty");
+    };
+    Command::new(program).args(args).exec()
+}
+
+/// Replace the greeter's process with the fallback greeter, saying so on the terminal, since the logs may be gone.
+///
+/// This only returns if it couldn't be started.
+pub fn hand_off(command: &[String]) {
+    eprintln!("Starting the fallback greeter: {}", command.join(" "));
+    let err = exec_fallback_greeter(command);
+    error!(error = %err, "Couldn't start the fallback greeter");
+    eprintln!("Couldn't start the fallback greeter: {err}");
+}
+
+/// Start the fallback greeter after any panic, eg. when greetd's socket is lost, once the panic has been reported.
+pub fn exec_on_panic(command: Vec<String>) {
+    let report_panic = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        report_panic(info);
+        error!(?command, "Starting the fallback greeter after a crash");
+        hand_off(&command);
+    }));
+}
+
+/// Count a start of the greeter in the breadcrumb file, returning the number of unstable starts in a row.
+///
+/// An unreadable count is treated as zero, since it's most likely from an interrupted write.
+pub fn record_start(path: &Path) -> io::Result<u32> {
+    let starts = read_to_string(path)
+        .ok()
+        .and_then(|text| text.trim().parse::<u32>().ok())
+        .unwrap_or(0)
+        .saturating_add(1);
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+    write(path, starts.to_string())?;
+    Ok(starts)
+}
+
+/// Remove the breadcrumb file, since the greeter is stable or the crash loop has been handed off.
+pub fn clear_breadcrumb(path: &Path) -> io::Result<()> {
+    match remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn missing_fallback_greeter() {
+        let err = exec_fallback_greeter(&["/nonexistent/greeter".to_string()]);
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn breadcrumb() {
+        let dir = TempDir::new("breadcrumb");
+        let path = dir.join("unstable-starts");
+        let starts: Vec<_> = (0..3).map(|_| record_start(&path).ok()).collect();
+        let cleared = clear_breadcrumb(&path).is_ok() && clear_breadcrumb(&path).is_ok();
+        let restarted = record_start(&path).ok();
+
+        assert_eq!(starts, [Some(1), Some(2), Some(3)]);
+        assert!(cleared);
+        assert_eq!(restarted, Some(1));
+    }
+}
@@ -0,0 +1,127 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Names of the login UI's widgets for assistive technologies, eg. screen readers
+//!
+//! Buttons with text are named after it by GTK, but entries, drop-downs and icon-only buttons need to be named
+//! explicitly.
+
+use relm4::gtk::{
+    self,
+    accessible::{Property, Relation},
+    prelude::*,
+};
+
+use super::templates::Ui;
+
+/// How an interactive widget is named for assistive technologies
+pub(super) enum AccessibleName {
+    /// Named after the visible label next to it
+    LabelledBy(gtk::Label),
+    /// Named with text that isn't shown otherwise, eg. for icon-only buttons
+    Label(&'static str),
+}
+
+/// Get the names of the interactive widgets that GTK can't name by itself.
+pub(super) fn accessible_names(ui: &Ui) -> Vec<(gtk::Widget, AccessibleName)> {
+    let labelled_by = |widget: &gtk::Widget, label: &gtk::Label| {
+        (widget.clone(), AccessibleName::LabelledBy(label.clone()))
+    };
+
+    vec![
+        labelled_by(ui.usernames_box.upcast_ref(), &ui.user_label),
+        labelled_by(ui.username_entry.upcast_ref(), &ui.user_label),
+        labelled_by(ui.sessions_box.upcast_ref(), &ui.session_label),
+        labelled_by(ui.session_entry.upcast_ref(), &ui.session_label),
+        labelled_by(ui.secret_entry.upcast_ref(), &ui.input_label),
+        labelled_by(ui.visible_entry.upcast_ref(), &ui.input_label),
+        labelled_by(
+            ui.combined_secret_entry.upcast_ref(),
+            &ui.combined_secret_label,
+        ),
+        (
+            ui.user_toggle.clone().upcast(),
+            AccessibleName::Label("Manually enter username"),
+        ),
+        (
+            ui.sess_toggle.clone().upcast(),
+            AccessibleName::Label("Manually enter session command"),
+        ),
+        (
+            ui.badge_entry.clone().upcast(),
+            AccessibleName::Label("Badge scanner input"),
+        ),
+    ]
+}
+
+/// Name the interactive widgets that GTK can't name by itself.
+pub(super) fn setup_accessibility(ui: &Ui) {
+    for (widget, name) in accessible_names(ui) {
+        match name {
+            AccessibleName::LabelledBy(label) => {
+                widget.update_relation(&[Relation::LabelledBy(&[label.upcast_ref()])])
+            }
+            AccessibleName::Label(text) => widget.update_property(&[Property::Label(text)]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod accessible_names {
+        use relm4::WidgetTemplate;
+
+        use super::super::*;
+
+        /// Collect the interactive widgets within the given widget.
+        fn interactive_widgets(widget: &gtk::Widget, found: &mut Vec<gtk::Widget>) {
+            if widget.is::<gtk::Button>()
+                || widget.is::<gtk::Entry>()
+                || widget.is::<gtk::PasswordEntry>()
+                || widget.is::<gtk::DropDown>()
+            {
+                found.push(widget.clone());
+                // Internal children, eg. the button of a drop-down, are named by GTK.
+                return;
+            }
+
+            let mut child = widget.first_child();
+            while let Some(widget) = child {
+                interactive_widgets(&widget, found);
+                child = widget.next_sibling();
+            }
+        }
+
+        #[test]
+        #[ignore = "needs a display"]
+        fn every_interactive_widget_is_named() {
+            gtk::init().expect("Couldn't initialize GTK");
+
+            let ui = Ui::init(());
+            let names = accessible_names(&ui);
+            let mut widgets = Vec::new();
+            interactive_widgets(ui.upcast_ref(), &mut widgets);
+            assert!(!widgets.is_empty(), "No interactive widgets found");
+
+            for widget in widgets {
+                let description = format!("{widget:?}");
+                assert_ne!(
+                    widget.accessible_role(),
+                    gtk::AccessibleRole::Generic,
+                    "{description} has no accessible role"
+                );
+
+                let has_own_label = widget
+                    .downcast_ref::<gtk::Button>()
+                    .and_then(|button| button.label())
+                    .is_some_and(|label| !label.is_empty());
+                let is_named = names.iter().any(|(named, _)| named == &widget);
+                assert!(
+                    has_own_label || is_named,
+                    "{description} has no accessible name"
+                );
+            }
+        }
+    }
+}
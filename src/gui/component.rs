@@ -4,81 +4,1115 @@
 
 //! Setup for using the greeter as a Relm4 component
 
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use relm4::{
     component::{AsyncComponent, AsyncComponentParts},
-    gtk::prelude::*,
+    gtk::{
+        accessible::{Property, Relation},
+        prelude::*,
+    },
     prelude::*,
     AsyncComponentSender,
 };
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 
-#[cfg(feature = "gtk4_8")]
-use crate::config::BgFit;
+use crate::client::{AuthStatus, GreetdTransport};
+use crate::config::{BgFit, Config, PanelValues, Toolkit};
+use crate::demo::DemoFlow;
 
-use super::messages::{CommandMsg, InputMsg, UserSessInfo};
-use super::model::{Greeter, InputMode, Updates};
+use super::accessibility::setup_accessibility;
+use super::css_nodes::css_node_tree;
+use super::dropdown::DropDownIdExt;
+use super::messages::{CommandMsg, InputMsg, PowerAction, UserSessInfo};
+use super::model::{current_theme_period, Greeter, Updates};
 use super::templates::Ui;
+use super::view_state::Focus;
+use super::widget::backdrop::BACKDROP_CSS;
+use super::widget::banner::{BannerMsg, BANNER_CSS};
+
+/// Content type of SVG backgrounds, which are rendered for the size of the monitor
+const SVG_CONTENT_TYPE: &str = "image/svg+xml";
 
 /// Load GTK settings from the greeter config.
+///
+/// This is also done before the window is shown, so that it doesn't briefly show up with the default theme.
 fn setup_settings(model: &Greeter, root: &gtk::ApplicationWindow) {
     let settings = root.settings();
-    let config = if let Some(config) = model.config.get_gtk_settings() {
-        config
+
+    if let Some(dark) = model.prefer_dark() {
+        debug!("Setting dark theme: {dark}");
+        set_dark_theme(model, &settings, dark);
+    }
+
+    if let Some(theme) = model.gtk_theme() {
+        debug!("Setting theme: {theme}");
+        settings.set_gtk_theme_name(Some(theme));
+    } else {
+        // Follow the system's theme again, eg. after the high-contrast theme is turned off.
+        settings.reset_property("gtk-theme-name");
+    };
+
+    if let Some(icon_theme) = model.config.get_icon_theme() {
+        debug!("Setting icon theme: {icon_theme}");
+        settings.set_gtk_icon_theme_name(Some(icon_theme));
+    };
+
+    if let Some(cursor_theme) = model.config.get_cursor_theme() {
+        debug!("Setting cursor theme: {cursor_theme}");
+        settings.set_gtk_cursor_theme_name(Some(cursor_theme));
+    };
+
+    if let Some(cursor_size) = model.config.get_cursor_size() {
+        debug!("Setting cursor size: {cursor_size}");
+        settings.set_gtk_cursor_theme_size(cursor_size);
+    };
+
+    if let Some(font) = model.config.get_font() {
+        debug!("Setting font: {font}");
+        settings.set_gtk_font_name(Some(font));
+    };
+
+    if !model.config.get_animations() {
+        debug!("Disabling animations");
+        settings.set_gtk_enable_animations(false);
+    }
+}
+
+/// Scale the font of the greeting message, eg. for screens that are viewed from afar.
+fn setup_greeting_scale(model: &Greeter, widgets: &GreeterWidgets) {
+    let scale = model.config.get_greeting_scale();
+    debug!("Scaling the greeting message by {scale}");
+
+    // Keep the boldface from the template.
+    let label = &widgets.ui.message_label;
+    let attrs = label.attributes().unwrap_or_else(gtk::pango::AttrList::new);
+    attrs.insert(gtk::pango::AttrFloat::new_scale(scale));
+    label.set_attributes(Some(&attrs));
+}
+
+/// Set whether the dark theme is preferred.
+#[cfg(not(feature = "libadwaita"))]
+fn set_dark_theme(_model: &Greeter, settings: &gtk::Settings, dark: bool) {
+    settings.set_gtk_application_prefer_dark_theme(dark);
+}
+
+/// Set whether the dark theme is preferred.
+#[cfg(feature = "libadwaita")]
+fn set_dark_theme(model: &Greeter, settings: &gtk::Settings, dark: bool) {
+    if model.toast_overlay.is_some() {
+        // libadwaita ignores the GTK setting for the dark theme, and complains when it's used.
+        adw::StyleManager::default().set_color_scheme(if dark {
+            adw::ColorScheme::ForceDark
+        } else {
+            adw::ColorScheme::Default
+        });
+    } else if model.config.get_toolkit() != &Toolkit::Adwaita {
+        settings.set_gtk_application_prefer_dark_theme(dark);
+    }
+    // Otherwise, libadwaita isn't set up yet, which is done after the window is first themed.
+}
+
+/// Switch to libadwaita styling, with notifications shown as toasts.
+#[cfg(feature = "libadwaita")]
+fn setup_adwaita(model: &mut Greeter, root: &gtk::ApplicationWindow) {
+    if let Err(err) = adw::init() {
+        warn!(error = %err, "Couldn't initialize libadwaita, so falling back to GTK");
+        return;
+    }
+
+    // Wrap the entire UI in an overlay for the toasts.
+    let overlay = adw::ToastOverlay::new();
+    let child = root.child();
+    root.set_child(None::<&gtk::Widget>);
+    overlay.set_child(child.as_ref());
+    root.set_child(Some(&overlay));
+
+    model.toast_overlay = Some(overlay);
+}
+
+/// Show the background image, or play the background if it's animated.
+fn setup_background(model: &Greeter, picture: &gtk::Picture) {
+    let path = if let Some(path) = model.background() {
+        path
     } else {
         return;
     };
 
+    // The content type is guessed from the file name, so that the file isn't read twice.
+    let (content_type, _) = gtk::gio::content_type_guess(Some(path), &[]);
+    if content_type.as_str() == SVG_CONTENT_TYPE {
+        render_svg_background(model, picture, path);
+        return;
+    }
+    if !(content_type.starts_with("video/") || content_type.as_str() == "image/gif") {
+        picture.set_filename(Some(path));
+        return;
+    }
+
+    debug!(path, %content_type, "Playing animated background");
+    let media = gtk::MediaFile::for_filename(path);
+    media.set_muted(true);
+    media.set_loop(true);
+
+    // Show the still image from the config if the background can't be decoded, eg. due to missing GStreamer plugins.
+    // Without one, GIFs can still be shown as their first frame, but there's nothing to show for videos.
+    let fallback = model
+        .config
+        .get_background_fallback()
+        .or((content_type.as_str() == "image/gif").then_some(path))
+        .map(String::from);
+    let fallback_picture = picture.clone();
+    media.connect_error_notify(move |media| {
+        let err = if let Some(err) = media.error() {
+            err
+        } else {
+            return;
+        };
+        if let Some(fallback) = &fallback {
+            warn!(
+                error = %err,
+                fallback,
+                "Couldn't play the background, so showing the fallback instead"
+            );
+            fallback_picture.set_filename(Some(fallback));
+        } else {
+            warn!(error = %err, "Couldn't play the background, and there's no still image to show instead");
+            fallback_picture.set_paintable(gtk::gdk::Paintable::NONE);
+        }
+    });
+
+    picture.set_paintable(Some(&media));
+    media.play();
+}
+
+/// Check whether the background is an SVG, which is rendered for the size of the monitor.
+fn is_svg_background(model: &Greeter) -> bool {
+    model.background().is_some_and(|path| {
+        gtk::gio::content_type_guess(Some(path), &[]).0.as_str() == SVG_CONTENT_TYPE
+    })
+}
+
+/// Render an SVG background at the pixel size of the monitor, since scaling its default raster is blurry on HiDPI.
+fn render_svg_background(model: &Greeter, picture: &gtk::Picture, path: &str) {
+    let (monitor, natural) = if let (Some(monitor), Some((_, width, height))) = (
+        &model.updates.monitor,
+        gtk::gdk_pixbuf::Pixbuf::file_info(path),
+    ) {
+        (monitor, (width, height))
+    } else {
+        // The monitor isn't known yet, or the file can't be read, so let GTK render and report it.
+        picture.set_filename(Some(path));
+        return;
+    };
+
+    let geometry = monitor.geometry();
+    #[cfg(feature = "gtk4_8")]
+    let fit = model.config.get_background_fit();
+    // Without GTK 4.8, pictures are always scaled to fit within the window.
+    #[cfg(not(feature = "gtk4_8"))]
+    let fit = &BgFit::Contain;
+    let (width, height) = svg_render_size(
+        natural,
+        (geometry.width(), geometry.height()),
+        monitor.scale_factor(),
+        fit,
+    );
+    debug!(path, width, height, "Rendering SVG background");
+    match gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(path, width, height, false) {
+        Ok(pixbuf) => picture.set_paintable(Some(&gtk::gdk::Texture::for_pixbuf(&pixbuf))),
+        Err(err) => {
+            warn!(error = %err, "Couldn't render the SVG background at the monitor's size");
+            picture.set_filename(Some(path));
+        }
+    }
+}
+
+/// Get the size in device pixels that an SVG with the given natural size is rendered at to cover a monitor like the
+/// background fit does, so that the picture doesn't need to scale it.
+fn svg_render_size(
+    natural: (i32, i32),
+    monitor: (i32, i32),
+    scale_factor: i32,
+    fit: &BgFit,
+) -> (i32, i32) {
+    let (width, height) = (f64::from(natural.0.max(1)), f64::from(natural.1.max(1)));
+    let contain = (f64::from(monitor.0) / width).min(f64::from(monitor.1) / height);
+    let scale = match fit {
+        BgFit::Fill => return (monitor.0 * scale_factor, monitor.1 * scale_factor),
+        BgFit::Contain => contain,
+        BgFit::Cover => (f64::from(monitor.0) / width).max(f64::from(monitor.1) / height),
+        BgFit::ScaleDown => contain.min(1.0),
+    } * f64::from(scale_factor);
+    (
+        (width * scale).round().max(1.0) as i32,
+        (height * scale).round().max(1.0) as i32,
+    )
+}
+
+/// Load the CSS file scheduled for the current part of the day, replacing the previously scheduled one.
+fn load_scheduled_css(model: &Greeter) {
+    if let Some(path) = model.scheduled_css() {
+        debug!(path, "Loading scheduled CSS from file");
+        model.theme_css.load_from_path(path);
+    } else {
+        model.theme_css.load_from_data("");
+    }
+}
+
+/// Blur and darken the background behind the login box, to make it readable over busy backgrounds.
+fn setup_backdrop(model: &Greeter, widgets: &GreeterWidgets) {
+    let (blur, darken) = model.config.get_panel_backdrop();
+    if blur <= 0.0 && darken <= 0.0 {
+        widgets.ui.backdrop.set_visible(false);
+        return;
+    }
+
     debug!(
-        "Setting dark theme: {}",
-        config.application_prefer_dark_theme
+        "Blurring the background behind the login box by {blur}px, and darkening it by {darken}"
+    );
+    widgets.ui.backdrop.blur_behind(
+        &widgets.ui.background,
+        &widgets.ui.login_frame,
+        blur,
+        darken,
     );
-    settings.set_gtk_application_prefer_dark_theme(config.application_prefer_dark_theme);
+    widgets
+        .ui
+        .login_frame
+        .add_css_class("regreet-backdrop-target");
+}
 
-    if let Some(cursor_theme) = &config.cursor_theme_name {
-        debug!("Setting cursor theme: {cursor_theme}");
-        settings.set_gtk_cursor_theme_name(config.cursor_theme_name.as_deref());
+/// Get the CSS for the opacity and the corners of the login box and the clock from the config.
+fn panel_css(model: &Greeter) -> String {
+    let (opacity, radius) = model.config.get_panel_style();
+    // The element name makes these more specific than the default styling of a backdrop's target.
+    let mut css = String::new();
+    if let Some(opacity) = opacity {
+        debug!("Setting the opacity of the panels to {opacity}");
+        css.push_str(&format!(
+            "frame.regreet-panel, frame.regreet-clock {{ background-color: alpha(@theme_bg_color, {opacity}); }}\n"
+        ));
+    }
+    if let Some(radius) = radius {
+        debug!("Setting the corner radius of the panels to {radius}px");
+        css.push_str(&format!(
+            "frame.regreet-panel, frame.regreet-clock {{ border-radius: {radius}px; }}\n"
+        ));
+    }
+    css
+}
+
+/// Get the CSS for the color or the gradient behind the background image from the config.
+fn background_css(model: &Greeter) -> String {
+    let colors = model.config.get_background_colors();
+    let background = match colors.as_slice() {
+        [] => return String::new(),
+        [color] => format!("background-color: {color};"),
+        [top, bottom, ..] => {
+            format!("background-image: linear-gradient(to bottom, {top}, {bottom});")
+        }
     };
+    debug!("Setting the background behind the image to: {background}");
+    format!("window.regreet-window {{ {background} }}\n")
+}
 
-    if let Some(font) = &config.font_name {
-        debug!("Setting font: {font}");
-        settings.set_gtk_font_name(config.font_name.as_deref());
+/// Get the direction of the layout, which is the locale's direction unless it's mirrored.
+fn layout_direction(model: &Greeter) -> gtk::TextDirection {
+    // GTK picks the default direction from the locale, eg. right-to-left for Arabic or Hebrew.
+    let direction = gtk::Widget::default_direction();
+    let mirror = model.config.get_mirror_layout();
+    debug!("Locale direction: {direction:?}, mirrored: {mirror}");
+    match (direction, mirror) {
+        (gtk::TextDirection::Rtl, true) => gtk::TextDirection::Ltr,
+        (_, true) => gtk::TextDirection::Rtl,
+        (direction, false) => direction,
+    }
+}
+
+/// Show the current message, as markup if it's the greeting message with markup.
+fn show_message(model: &Greeter, label: &gtk::Label) {
+    if model.message_is_markup() {
+        label.set_markup(&model.updates.message);
+    } else {
+        label.set_text(&model.updates.message);
+    }
+}
+
+/// Show the target of a link in the greeting message, since there's no browser to open it in.
+fn show_link_dialog(window: &gtk::ApplicationWindow, uri: &str, spacing: i32) {
+    info!("Link activated in the greeting message: {uri}");
+    show_text_dialog(
+        window,
+        spacing,
+        "Link",
+        "This link can't be opened here. Open it on another device:",
+        uri,
+    );
+}
+
+/// Show the session start request that was skipped in a dry run.
+fn show_dry_run_dialog(window: &gtk::ApplicationWindow, request: &str, spacing: i32) {
+    show_text_dialog(
+        window,
+        spacing,
+        "Dry Run",
+        "The session wasn't started, since this is a dry run. It would be started with:",
+        request,
+    );
+}
+
+/// Show a dialog with a description and a selectable text.
+fn show_text_dialog(
+    window: &gtk::ApplicationWindow,
+    spacing: i32,
+    title: &str,
+    description: &str,
+    text: &str,
+) {
+    let dialog = gtk::Window::builder()
+        .title(title)
+        .modal(true)
+        .transient_for(window)
+        .build();
+    // Dialogs are separate windows, so they don't inherit the direction of the greeter window.
+    dialog.set_direction(window.direction());
+
+    let label = gtk::Label::builder()
+        .label(text)
+        .selectable(true)
+        .wrap(true)
+        .xalign(0.0)
+        .build();
+    // Commands, environment variables and links are always written left-to-right.
+    label.set_direction(gtk::TextDirection::Ltr);
+    let close_button = gtk::Button::with_label("Close");
+    close_button.set_halign(gtk::Align::End);
+    let dialog_clone = dialog.clone();
+    close_button.connect_clicked(move |_| dialog_clone.close());
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(spacing)
+        .margin_top(spacing)
+        .margin_bottom(spacing)
+        .margin_start(spacing)
+        .margin_end(spacing)
+        .build();
+    content.append(&gtk::Label::new(Some(description)));
+    content.append(&label);
+    content.append(&close_button);
+
+    dialog.set_child(Some(&content));
+    dialog.present();
+}
+
+/// Show a dialog asking whether to do something, eg. to run the command of a custom button.
+fn show_confirm_dialog(
+    window: &gtk::ApplicationWindow,
+    spacing: i32,
+    question: &str,
+    confirm_label: &str,
+    on_confirm: impl Fn() + 'static,
+) {
+    let dialog = gtk::Window::builder()
+        .title("Confirm")
+        .modal(true)
+        .transient_for(window)
+        .build();
+    dialog.set_direction(window.direction());
+
+    let cancel_button = gtk::Button::with_label("Cancel");
+    let dialog_clone = dialog.clone();
+    cancel_button.connect_clicked(move |_| dialog_clone.close());
+    let confirm_button = gtk::Button::with_label(confirm_label);
+    confirm_button.add_css_class("destructive-action");
+    let dialog_clone = dialog.clone();
+    confirm_button.connect_clicked(move |_| {
+        dialog_clone.close();
+        on_confirm();
+    });
+
+    let buttons = gtk::Box::builder()
+        .halign(gtk::Align::End)
+        .spacing(spacing)
+        .build();
+    buttons.append(&cancel_button);
+    buttons.append(&confirm_button);
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(spacing)
+        .margin_top(spacing)
+        .margin_bottom(spacing)
+        .margin_start(spacing)
+        .margin_end(spacing)
+        .build();
+    content.append(&gtk::Label::new(Some(question)));
+    content.append(&buttons);
+
+    dialog.set_child(Some(&content));
+    dialog.present();
+    // Pressing Enter by accident shouldn't run the command.
+    cancel_button.grab_focus();
+}
+
+/// Hide the pointer over the window once it hasn't moved for the time from the config, eg. for kiosks.
+fn setup_cursor_hiding(model: &Greeter, root: &gtk::ApplicationWindow) {
+    let delay = if let Some(delay) = model.config.get_hide_cursor_after() {
+        delay
+    } else {
+        return;
     };
+    debug!("Hiding the pointer after {delay:?} without motion");
 
-    if let Some(icon_theme) = &config.icon_theme_name {
-        debug!("Setting icon theme: {icon_theme}");
-        settings.set_gtk_icon_theme_name(config.icon_theme_name.as_deref());
+    let timer = Rc::new(RefCell::new(None));
+    // The pointer might never move, so start hiding it right away.
+    restart_cursor_timer(root, &timer, delay);
+
+    let controller = gtk::EventControllerMotion::new();
+    let window = root.downgrade();
+    controller.connect_motion(move |_, _, _| {
+        if let Some(window) = window.upgrade() {
+            window.set_cursor(None);
+            restart_cursor_timer(&window, &timer, delay);
+        }
+    });
+    root.add_controller(controller);
+}
+
+/// Hide the pointer over the window after the delay, replacing any earlier timer.
+fn restart_cursor_timer(
+    window: &gtk::ApplicationWindow,
+    timer: &Rc<RefCell<Option<gtk::glib::SourceId>>>,
+    delay: Duration,
+) {
+    if let Some(source) = timer.take() {
+        source.remove();
+    }
+    let window = window.downgrade();
+    let fired = timer.clone();
+    let source = gtk::glib::timeout_add_local_once(delay, move || {
+        // The source is gone once it has fired, so it mustn't be removed anymore.
+        fired.take();
+        if let Some(window) = window.upgrade() {
+            window.set_cursor_from_name(Some("none"));
+        }
+    });
+    timer.replace(Some(source));
+}
+
+/// Add the custom buttons from the config next to the reboot and power-off buttons.
+fn setup_custom_buttons(
+    model: &Greeter,
+    widgets: &GreeterWidgets,
+    sender: &AsyncComponentSender<Greeter>,
+) {
+    let spacing = model.config.get_density().spacing();
+    for (index, settings) in model.config.get_sys_commands().buttons.iter().enumerate() {
+        let button = gtk::Button::builder().focusable(true).build();
+        button.set_widget_name(&format!("regreet-custom-button-{index}"));
+        button.add_css_class("regreet-custom-button");
+        if let Some(icon) = &settings.icon {
+            let content = gtk::Box::builder()
+                .halign(gtk::Align::Center)
+                .spacing(spacing / 2)
+                .build();
+            content.append(&gtk::Image::from_icon_name(icon));
+            content.append(&gtk::Label::new(Some(&settings.label)));
+            button.set_child(Some(&content));
+            // The button has no label of its own, which GTK would name it after.
+            button.update_property(&[Property::Label(&settings.label)]);
+        } else {
+            button.set_label(&settings.label);
+        }
+
+        let window = widgets.window.clone();
+        let label = settings.label.clone();
+        let require_confirm = settings.require_confirm;
+        let sender = sender.clone();
+        button.connect_clicked(move |_| {
+            if require_confirm {
+                let sender = sender.clone();
+                show_confirm_dialog(&window, spacing, &format!("{label}?"), &label, move || {
+                    sender.input(InputMsg::CustomButton(index))
+                });
+            } else {
+                sender.input(InputMsg::CustomButton(index));
+            }
+        });
+        widgets.ui.end_buttons_box.append(&button);
+    }
+}
+
+/// Add a button that asks an admin to authenticate, then opens the settings panel, if it's enabled in the config.
+fn setup_settings_panel(
+    model: &Greeter,
+    widgets: &GreeterWidgets,
+    sender: &AsyncComponentSender<Greeter>,
+) {
+    if !model.config.get_settings_panel_enabled() {
+        return;
+    }
+
+    let button = gtk::Button::builder()
+        .icon_name("emblem-system-symbolic")
+        .tooltip_text("Settings")
+        .focusable(true)
+        .build();
+    button.set_widget_name("regreet-settings-button");
+    button.add_css_class("regreet-settings-button");
+
+    // The panel itself is shown once the model has checked the credentials.
+    let window = widgets.window.clone();
+    let spacing = model.config.get_density().spacing();
+    let sender = sender.clone();
+    button.connect_clicked(move |_| {
+        let sender = sender.clone();
+        show_admin_login(&window, spacing, move |username, password| {
+            sender.input(InputMsg::UnlockSettings { username, password });
+        });
+    });
+    widgets.ui.end_buttons_box.append(&button);
+}
+
+/// Show a dialog asking an admin for their username and password, before the settings panel is shown.
+fn show_admin_login(
+    window: &gtk::ApplicationWindow,
+    spacing: i32,
+    on_submit: impl Fn(String, String) + 'static,
+) {
+    info!("Asking for an admin's credentials for the settings panel");
+    let dialog = gtk::Window::builder()
+        .title("Settings")
+        .modal(true)
+        .transient_for(window)
+        .build();
+    dialog.set_direction(window.direction());
+
+    let description = gtk::Label::builder()
+        .label("Log in as an admin to change the settings.")
+        .wrap(true)
+        .xalign(0.0)
+        .build();
+    let grid = gtk::Grid::builder()
+        .column_spacing(spacing)
+        .row_spacing(spacing)
+        .build();
+    let username_label = gtk::Label::builder().label("Username").xalign(1.0).build();
+    let username_entry = gtk::Entry::builder().hexpand(true).build();
+    username_entry.update_relation(&[Relation::LabelledBy(&[username_label.upcast_ref()])]);
+    let password_label = gtk::Label::builder().label("Password").xalign(1.0).build();
+    let password_entry = gtk::PasswordEntry::builder()
+        .hexpand(true)
+        .activates_default(true)
+        .build();
+    password_entry.update_relation(&[Relation::LabelledBy(&[password_label.upcast_ref()])]);
+    grid.attach(&username_label, 0, 0, 1, 1);
+    grid.attach(&username_entry, 1, 0, 1, 1);
+    grid.attach(&password_label, 0, 1, 1, 1);
+    grid.attach(&password_entry, 1, 1, 1, 1);
+
+    let cancel_button = gtk::Button::with_label("Cancel");
+    let dialog_clone = dialog.clone();
+    cancel_button.connect_clicked(move |_| dialog_clone.close());
+    let unlock_button = gtk::Button::with_label("Unlock");
+    unlock_button.add_css_class("suggested-action");
+    let dialog_clone = dialog.clone();
+    let username_clone = username_entry.clone();
+    unlock_button.connect_clicked(move |_| {
+        on_submit(
+            username_clone.text().to_string(),
+            password_entry.text().to_string(),
+        );
+        dialog_clone.close();
+    });
+    dialog.set_default_widget(Some(&unlock_button));
+
+    let buttons = gtk::Box::builder()
+        .halign(gtk::Align::End)
+        .spacing(spacing)
+        .build();
+    buttons.append(&cancel_button);
+    buttons.append(&unlock_button);
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(spacing)
+        .margin_top(spacing)
+        .margin_bottom(spacing)
+        .margin_start(spacing)
+        .margin_end(spacing)
+        .build();
+    content.append(&description);
+    content.append(&grid);
+    content.append(&buttons);
+
+    dialog.set_child(Some(&content));
+    dialog.present();
+    username_entry.grab_focus();
+}
+
+/// Add a button for turning the high-contrast theme on and off, unless it's hidden in the config.
+///
+/// libadwaita only follows the system's high-contrast setting, so the button isn't shown with its styling.
+fn setup_high_contrast(
+    model: &Greeter,
+    widgets: &GreeterWidgets,
+    sender: &AsyncComponentSender<Greeter>,
+) {
+    if !model.config.get_visibility().high_contrast_button
+        || (cfg!(feature = "libadwaita") && model.config.get_toolkit() == &Toolkit::Adwaita)
+    {
+        return;
+    }
+
+    let button = gtk::ToggleButton::builder()
+        .icon_name("preferences-desktop-accessibility-symbolic")
+        .tooltip_text("High contrast")
+        .active(model.updates.high_contrast)
+        .focusable(true)
+        .build();
+    button.set_widget_name("regreet-high-contrast-button");
+    button.add_css_class("regreet-high-contrast-button");
+    button.update_property(&[Property::Label("High contrast")]);
+    let sender = sender.clone();
+    button
+        .connect_toggled(move |button| sender.input(InputMsg::SetHighContrast(button.is_active())));
+    widgets.ui.end_buttons_box.append(&button);
+}
+
+/// Add a button for showing the on-screen keyboard, and show it when an entry is tapped, if set in the config.
+fn setup_osk(model: &Greeter, widgets: &GreeterWidgets, sender: &AsyncComponentSender<Greeter>) {
+    let osk = if let Some(osk) = model.config.get_osk() {
+        osk
+    } else {
+        return;
     };
 
-    if let Some(theme) = &config.theme_name {
-        debug!("Setting theme: {theme}");
-        settings.set_gtk_theme_name(config.theme_name.as_deref());
+    let button = gtk::Button::builder()
+        .icon_name("input-keyboard-symbolic")
+        .tooltip_text("On-screen keyboard")
+        .focusable(true)
+        .build();
+    button.set_widget_name("regreet-osk-button");
+    button.add_css_class("regreet-osk-button");
+    let button_sender = sender.clone();
+    button.connect_clicked(move |_| button_sender.input(InputMsg::ToggleOsk));
+    widgets.ui.end_buttons_box.append(&button);
+
+    if !osk.auto_show {
+        return;
+    }
+    // Taps are watched on the whole window, so that this works for entries that are added later too. The gesture
+    // never claims the taps, so they still reach the entries.
+    let gesture = gtk::GestureClick::builder()
+        .touch_only(true)
+        .propagation_phase(gtk::PropagationPhase::Capture)
+        .build();
+    let window = widgets.window.clone();
+    let sender = sender.clone();
+    gesture.connect_pressed(move |_, _, x, y| {
+        let tapped_entry = window
+            .pick(x, y, gtk::PickFlags::DEFAULT)
+            .and_then(|widget| widget.ancestor(gtk::Text::static_type()))
+            .is_some();
+        if tapped_entry {
+            sender.input(InputMsg::ShowOsk);
+        }
+    });
+    widgets.window.add_controller(gesture);
+}
+
+/// Preview config and CSS files that are dropped onto the window, in demo mode.
+fn setup_file_drop(
+    model: &Greeter,
+    root: &gtk::ApplicationWindow,
+    sender: &AsyncComponentSender<Greeter>,
+) {
+    if !model.demo {
+        return;
+    }
+
+    let drop_target =
+        gtk::DropTarget::new(gtk::gio::File::static_type(), gtk::gdk::DragAction::COPY);
+    let sender = sender.clone();
+    drop_target.connect_drop(move |_, value, _, _| {
+        let path = value
+            .get::<gtk::gio::File>()
+            .ok()
+            .and_then(|file| file.path());
+        if let Some(path) = path {
+            sender.input(InputMsg::DropFile(path));
+            true
+        } else {
+            false
+        }
+    });
+    root.add_controller(drop_target);
+
+    // The previewed CSS is loaded last, so that it overrides everything else.
+    gtk::style_context_add_provider_for_display(
+        &root.display(),
+        &model.preview_css,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+}
+
+/// Show a dialog for changing the settings from the settings panel.
+///
+/// When it's closed, either after saving or not, the admin has to authenticate again to change anything.
+fn show_settings_panel(
+    window: &gtk::ApplicationWindow,
+    spacing: i32,
+    values: PanelValues,
+    on_save: impl Fn(PanelValues) + 'static,
+    on_close: impl Fn() + 'static,
+) {
+    info!("Showing the settings panel");
+    let dialog = gtk::Window::builder()
+        .title("Settings")
+        .modal(true)
+        .transient_for(window)
+        .build();
+    dialog.set_direction(window.direction());
+    dialog.connect_close_request(move |_| {
+        on_close();
+        gtk::glib::Propagation::Proceed
+    });
+
+    let grid = gtk::Grid::builder()
+        .column_spacing(spacing)
+        .row_spacing(spacing)
+        .build();
+    let fields = [
+        ("Background", values.background.unwrap_or_default()),
+        ("Greeting", values.greeting_msg),
+        ("GTK theme", values.gtk_theme.unwrap_or_default()),
+    ];
+    let entries: Vec<_> = fields
+        .into_iter()
+        .enumerate()
+        .map(|(row, (label, text))| {
+            let label = gtk::Label::builder().label(label).xalign(1.0).build();
+            let entry = gtk::Entry::builder().text(text).hexpand(true).build();
+            entry.update_relation(&[Relation::LabelledBy(&[label.upcast_ref()])]);
+            grid.attach(&label, 0, row as i32, 1, 1);
+            grid.attach(&entry, 1, row as i32, 1, 1);
+            entry
+        })
+        .collect();
+    let hint = gtk::Label::builder()
+        .label("Empty fields fall back to the config file after a restart.")
+        .wrap(true)
+        .xalign(0.0)
+        .build();
+    hint.add_css_class("dim-label");
+
+    let cancel_button = gtk::Button::with_label("Cancel");
+    let dialog_clone = dialog.clone();
+    cancel_button.connect_clicked(move |_| dialog_clone.close());
+    let save_button = gtk::Button::with_label("Save");
+    save_button.add_css_class("suggested-action");
+    let dialog_clone = dialog.clone();
+    save_button.connect_clicked(move |_| {
+        let text = |index: usize| entries[index].text().to_string();
+        let optional = |index: usize| Some(text(index)).filter(|text| !text.is_empty());
+        // Save before closing, since the panel is locked once it's closed.
+        on_save(PanelValues {
+            background: optional(0),
+            greeting_msg: text(1),
+            gtk_theme: optional(2),
+        });
+        dialog_clone.close();
+    });
+
+    let buttons = gtk::Box::builder()
+        .halign(gtk::Align::End)
+        .spacing(spacing)
+        .build();
+    buttons.append(&cancel_button);
+    buttons.append(&save_button);
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(spacing)
+        .margin_top(spacing)
+        .margin_bottom(spacing)
+        .margin_start(spacing)
+        .margin_end(spacing)
+        .build();
+    content.append(&grid);
+    content.append(&hint);
+    content.append(&buttons);
+
+    dialog.set_child(Some(&content));
+    dialog.present();
+}
+
+/// Open the power menu with the keyboard shortcut from the config.
+fn setup_power_menu(
+    model: &Greeter,
+    root: &gtk::ApplicationWindow,
+    sender: &AsyncComponentSender<Greeter>,
+) {
+    let settings = model.config.get_power_menu();
+    let trigger = if let Some(trigger) = gtk::ShortcutTrigger::parse_string(&settings.shortcut) {
+        trigger
+    } else {
+        warn!("Invalid power menu shortcut: {}", settings.shortcut);
+        return;
+    };
+
+    let reboot_word = settings.reboot_word.clone();
+    let poweroff_word = settings.poweroff_word.clone();
+    let spacing = model.config.get_density().spacing();
+    let sender = sender.clone();
+    let action = gtk::CallbackAction::new(move |widget, _| {
+        if let Some(window) = widget.downcast_ref::<gtk::Window>() {
+            show_power_menu(window, &reboot_word, &poweroff_word, spacing, &sender);
+        }
+        gtk::glib::Propagation::Stop
+    });
+
+    let controller = gtk::ShortcutController::new();
+    controller.set_scope(gtk::ShortcutScope::Global);
+    controller.add_shortcut(gtk::Shortcut::new(Some(trigger), Some(action)));
+    root.add_controller(controller);
+}
+
+/// Require the secure attention key from the config to be pressed before logging in.
+fn setup_secure_attention_key(
+    model: &mut Greeter,
+    root: &gtk::ApplicationWindow,
+    sender: &AsyncComponentSender<Greeter>,
+) {
+    let shortcut = if let Some(shortcut) = model.config.get_secure_attention_key() {
+        shortcut
+    } else {
+        return;
+    };
+    let trigger = if let Some(trigger) = gtk::ShortcutTrigger::parse_string(shortcut) {
+        trigger
+    } else {
+        // Locking everyone out would be worse than not requiring the key.
+        error!("Invalid secure attention key, so it isn't required: {shortcut}");
+        return;
+    };
+
+    model.require_attention(&trigger.to_label(&root.display()));
+
+    let sender = sender.clone();
+    let action = gtk::CallbackAction::new(move |_, _| {
+        sender.input(InputMsg::SecureAttention);
+        gtk::glib::Propagation::Stop
+    });
+
+    let controller = gtk::ShortcutController::new();
+    controller.set_scope(gtk::ShortcutScope::Global);
+    controller.add_shortcut(gtk::Shortcut::new(Some(trigger), Some(action)));
+    root.add_controller(controller);
+}
+
+/// Show the legal notice, and only enable accepting it once it's scrolled to the end, so that it can't be accepted
+/// unread.
+///
+/// If the notice couldn't be loaded, this explains why logging in isn't possible, and it can't be accepted at all.
+fn setup_legal_notice(model: &Greeter, widgets: &GreeterWidgets) {
+    if model.notice_unavailable {
+        widgets.ui.notice_label.set_text(
+            "The legal notice couldn't be loaded, so logging in isn't possible. Please contact the administrator.",
+        );
+        widgets.ui.notice_button.set_visible(false);
+        return;
+    }
+    let notice = if let Some(notice) = &model.legal_notice {
+        notice
+    } else {
+        return;
     };
+    widgets.ui.notice_label.set_text(notice);
+
+    // The notice doesn't change, so the adjustment changes whenever the notice is laid out or scrolled.
+    let button = widgets.ui.notice_button.clone();
+    let update_sensitive = move |adjustment: &gtk::Adjustment| {
+        // Short notices fit without scrolling, and the end might be off by a fraction of a pixel. The adjustment is
+        // empty until the notice is laid out.
+        let at_end = adjustment.upper() > 0.0
+            && adjustment.value() + adjustment.page_size() >= adjustment.upper() - 1.0;
+        if at_end && !button.is_sensitive() {
+            button.set_sensitive(true);
+            button.grab_focus();
+        }
+    };
+    let adjustment = widgets.ui.notice_scroll.vadjustment();
+    adjustment.connect_value_changed(update_sensitive.clone());
+    adjustment.connect_changed(update_sensitive);
+}
+
+/// Show a dialog where typing a confirmation word reboots or powers off the system.
+///
+/// This is meant for machines without a pointer.
+fn show_power_menu(
+    window: &gtk::Window,
+    reboot_word: &str,
+    poweroff_word: &str,
+    spacing: i32,
+    sender: &AsyncComponentSender<Greeter>,
+) {
+    info!("Showing the power menu");
+    let dialog = gtk::Window::builder()
+        .title("Power Menu")
+        .modal(true)
+        .transient_for(window)
+        .build();
+    dialog.set_direction(window.direction());
+
+    // Close the dialog with the Escape key.
+    let controller = gtk::ShortcutController::new();
+    controller.add_shortcut(gtk::Shortcut::new(
+        gtk::ShortcutTrigger::parse_string("Escape"),
+        Some(gtk::NamedAction::new("window.close")),
+    ));
+    dialog.add_controller(controller);
+
+    let prompt = gtk::Label::new(Some(&format!(
+        "Type \"{reboot_word}\" to reboot or \"{poweroff_word}\" to power off, or press Escape to cancel:"
+    )));
+    let error_label = gtk::Label::new(None);
+    error_label.add_css_class("error");
+    let entry = gtk::Entry::new();
+
+    let dialog_clone = dialog.clone();
+    let error_label_clone = error_label.clone();
+    let reboot_word = reboot_word.to_string();
+    let poweroff_word = poweroff_word.to_string();
+    let sender = sender.clone();
+    entry.connect_activate(move |entry| {
+        let text = entry.text();
+        let msg = if text.as_str() == reboot_word {
+            InputMsg::Reboot
+        } else if text.as_str() == poweroff_word {
+            InputMsg::PowerOff
+        } else {
+            error_label_clone.set_label(&format!("Unknown action: {text}"));
+            entry.set_text("");
+            return;
+        };
+        dialog_clone.close();
+        sender.input(msg);
+    });
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(spacing)
+        .margin_top(spacing)
+        .margin_bottom(spacing)
+        .margin_start(spacing)
+        .margin_end(spacing)
+        .build();
+    content.append(&prompt);
+    content.append(&entry);
+    content.append(&error_label);
+
+    dialog.set_child(Some(&content));
+    dialog.present();
+    entry.grab_focus();
 }
 
-/// Populate the user and session combo boxes with entries.
+/// Set up how the credential entries handle what's typed, eg. with input methods.
+fn setup_input_methods(model: &Greeter, widgets: &GreeterWidgets) {
+    // Input methods can be used, but shouldn't learn from what's entered.
+    let hints = gtk::InputHints::NO_SPELLCHECK | gtk::InputHints::PRIVATE;
+    for entry in [&widgets.ui.username_entry, &widgets.ui.visible_entry] {
+        entry.set_input_hints(hints);
+    }
+
+    let disable_ime = model.config.get_disable_password_ime();
+    // Badges might contain credentials, so their entry is treated like the password entries.
+    let entries = [
+        &widgets.ui.secret_entry,
+        &widgets.ui.combined_secret_entry,
+        &widgets.ui.badge_entry,
+    ];
+    for entry in entries {
+        if model.demo {
+            // Demo mode is used for screenshots, which shouldn't be able to show what was typed.
+            entry.set_show_peek_icon(false);
+        }
+        // Password entries delegate the editing to a text widget, which talks to the input method.
+        let text = if let Some(text) = entry
+            .delegate()
+            .and_then(|delegate| delegate.downcast::<gtk::Text>().ok())
+        {
+            text
+        } else {
+            warn!("Couldn't find the text widget of a password entry");
+            continue;
+        };
+        text.set_input_purpose(gtk::InputPurpose::Password);
+        text.set_input_hints(hints);
+        if disable_ime {
+            // The simple input method still supports dead keys and compose sequences, but it doesn't use the
+            // compositor's input method, whose composition might show the password.
+            debug!("Disabling the input method for a password entry");
+            text.set_im_module(Some("gtk-im-context-simple"));
+        }
+    }
+}
+
+/// Populate the user and session drop-downs with entries.
 fn setup_users_sessions(model: &Greeter, widgets: &GreeterWidgets) {
     // The user that is shown during initial login
     let mut initial_username = None;
 
-    // Populate the usernames combo box.
+    // Populate the usernames drop-down, showing the full names of users.
+    let mut usernames = Vec::new();
+    let mut full_names = HashMap::new();
     for (user, username) in model.sys_util.get_users().iter() {
         debug!("Found user: {user}");
         if initial_username.is_none() {
             initial_username = Some(username.clone());
         }
-        widgets.ui.usernames_box.append(Some(username), user);
+        usernames.push(username.as_str());
+        full_names.insert(username.clone(), user.clone());
     }
+    widgets.ui.usernames_box.set_ids(usernames, full_names);
 
-    // Populate the sessions combo box.
-    for session in model.sys_util.get_sessions().keys() {
+    // Populate the sessions drop-down.
+    let sessions = model.sys_util.get_sessions().keys().map(|session| {
         debug!("Found session: {session}");
-        widgets.ui.sessions_box.append(Some(session), session);
-    }
+        session.as_str()
+    });
+    widgets.ui.sessions_box.set_ids(sessions, HashMap::new());
+
+    // Show where the selected session is from, to tell apart sessions with the same name from different directories.
+    let session_paths: HashMap<_, _> = model
+        .sys_util
+        .get_sessions()
+        .iter()
+        .filter_map(|(name, info)| Some((name.clone(), info.path.as_ref()?.display().to_string())))
+        .collect();
+    widgets
+        .ui
+        .sessions_box
+        .connect_selected_notify(move |sessions_box| {
+            let path = sessions_box
+                .active_id()
+                .and_then(|id| session_paths.get(id.as_str()));
+            sessions_box.set_tooltip_text(path.map(String::as_str));
+        });
 
-    // If the last user is known, show their login initially.
-    if let Some(last_user) = model.cache.get_last_user() {
+    // Users preselected for this machine override the last user, so that eg. cloned images land each machine on its
+    // designated account.
+    if let Some(rule) = &model.host_preselect {
+        initial_username = Some(rule.user.clone());
+    } else if let Some(last_user) = model.cache.get_last_user() {
+        // If the last user is known, show their login initially.
         initial_username = Some(last_user.to_string());
     } else if let Some(user) = &initial_username {
         info!("Using first found user '{user}' as initial user");
@@ -98,9 +1132,35 @@ fn setup_users_sessions(model: &Greeter, widgets: &GreeterWidgets) {
 
 /// The info required to initialize the greeter
 pub struct GreeterInit {
-    pub config_path: PathBuf,
+    /// The config, which is loaded before the greeter so that logging can be set up from it
+    pub config: Config,
     pub css_path: PathBuf,
-    pub demo: bool,
+    /// The demo flow to run instead of using greetd
+    pub demo: Option<DemoFlow>,
+    /// Show the session start request instead of starting the session
+    pub dry_run: bool,
+    /// Show every state of the login UI in turn, for manual testing
+    ///
+    /// This needs demo mode.
+    pub ui_tour: bool,
+    /// Print the tree of CSS nodes once the window is shown, then quit
+    ///
+    /// This needs demo mode.
+    pub dump_css_nodes: bool,
+    /// Open the GTK inspector along with the window, like `GTK_DEBUG=interactive`
+    ///
+    /// This needs demo mode.
+    pub inspector: bool,
+    /// Don't write any files, eg. the cache or the status file, on immutable systems
+    pub read_only: bool,
+    /// Turn off the background, animations and the clock, for old hardware
+    pub low_resource: bool,
+    /// Warnings that were logged before the greeter started, eg. about the config, to be shown to the user
+    pub warnings: Vec<String>,
+    /// Custom transport to communicate with greetd, instead of its UNIX socket
+    ///
+    /// This is ignored in demo mode.
+    pub transport: Option<Box<dyn GreetdTransport>>,
 }
 
 #[relm4::component(pub, async)]
@@ -115,28 +1175,88 @@ impl AsyncComponent for Greeter {
         #[name = "window"]
         gtk::ApplicationWindow {
             set_visible: true,
+            set_widget_name: "regreet-window",
+            add_css_class: "regreet-window",
 
             // Name the UI widget, otherwise the inner children cannot be accessed by name.
             #[name = "ui"]
             #[template]
             Ui {
                 #[template_child]
-                background { set_filename: model.config.get_background() },
+                login_frame {
+                    set_halign: model.config.get_panel_position().0.halign(),
+                    set_valign: model.config.get_panel_position().0.valign(),
+                    set_margin_all: model.config.get_panel_position().1,
+                },
 
                 #[template_child]
                 clock_frame {
+                    set_visible: model.config.widget.clock.enabled,
+                    set_halign: model.config.widget.clock.position.halign(),
+                    set_valign: model.config.widget.clock.position.valign(),
+                    inline_css: model.config.widget.clock.position.frame_css(),
+
                     model.clock.widget(),
                 },
 
                 #[template_child]
-                message_label {
+                demo_watermark {
+                    set_visible: model.demo,
+                    set_margin_top: spacing,
+                    set_margin_end: spacing,
+                },
+
+                #[template_child]
+                panel_grid {
+                    set_column_spacing: spacing as u32,
+                    set_row_spacing: spacing as u32,
+                    set_margin_all: spacing,
+                },
+                #[template_child]
+                message_scroll {
+                    set_margin_bottom: spacing,
                     #[track(model.updates.changed(Updates::message()))]
-                    set_label: &model.updates.message,
+                    set_visible: model.message_visible(),
+                },
+                #[template_child]
+                message_label {
+                    // The label is set by `show_message`, since only the greeting message can be markup.
+                    connect_activate_link[
+                        window = root.clone(),
+                        spacing,
+                    ] => move |_, uri| {
+                        show_link_dialog(&window, uri, spacing);
+                        gtk::glib::Propagation::Stop
+                    },
+                },
+                #[template_child]
+                login_form {
+                    set_column_spacing: spacing as u32,
+                    set_row_spacing: spacing as u32,
+                    #[track(model.updates.gates_changed())]
+                    set_visible: model.view_state().login_form,
+                },
+                #[template_child]
+                notice_box {
+                    set_spacing: spacing,
+                    #[track(model.updates.gates_changed())]
+                    set_visible: model.view_state().notice,
+                },
+                #[template_child]
+                notice_label {
+                    #[track(
+                        model.updates.gates_changed() && model.view_state().focus == Focus::Notice
+                    )]
+                    grab_focus: (),
+                },
+                #[template_child]
+                notice_button {
+                    connect_clicked => Self::Input::AcceptNotice,
                 },
                 #[template_child]
                 session_label {
                     #[track(model.updates.changed(Updates::input_mode()))]
-                    set_visible: !model.updates.is_input(),
+                    set_visible: model.view_state().session_label,
                 },
                 #[template_child]
                 usernames_box {
@@ -144,10 +1264,14 @@ impl AsyncComponent for Greeter {
                         model.updates.changed(Updates::manual_user_mode())
                         || model.updates.changed(Updates::input_mode())
                     )]
-                    set_sensitive: !model.updates.manual_user_mode && !model.updates.is_input(),
+                    set_sensitive: model.view_state().usernames_box,
                     #[track(model.updates.changed(Updates::manual_user_mode()))]
-                    set_visible: !model.updates.manual_user_mode,
-                    connect_changed[
+                    set_visible: model.view_state().usernames_box,
+                    #[track(model.updates.changed(Updates::selected_user()))]
+                    set_active_id: model.updates.selected_user.as_deref(),
+                    #[track(model.updates.changed(Updates::last_login()))]
+                    set_tooltip_text: model.updates.last_login.as_deref(),
+                    connect_selected_notify[
                         sender,
                         username_entry = ui.username_entry.clone(),
                         sessions_box = ui.sessions_box.clone(),
@@ -164,9 +1288,11 @@ impl AsyncComponent for Greeter {
                         model.updates.changed(Updates::manual_user_mode())
                         || model.updates.changed(Updates::input_mode())
                     )]
-                    set_sensitive: model.updates.manual_user_mode && !model.updates.is_input(),
+                    set_sensitive: model.view_state().username_entry_sensitive,
                     #[track(model.updates.changed(Updates::manual_user_mode()))]
-                    set_visible: model.updates.manual_user_mode,
+                    set_visible: model.view_state().username_entry,
+                    #[track(model.updates.changed(Updates::manual_username()))]
+                    set_text: model.updates.manual_username.as_deref().unwrap_or_default(),
                 },
                 #[template_child]
                 sessions_box {
@@ -174,7 +1300,7 @@ impl AsyncComponent for Greeter {
                         model.updates.changed(Updates::manual_sess_mode())
                         || model.updates.changed(Updates::input_mode())
                     )]
-                    set_visible: !model.updates.manual_sess_mode && !model.updates.is_input(),
+                    set_visible: model.view_state().sessions_box,
                     #[track(model.updates.changed(Updates::active_session_id()))]
                     set_active_id: model.updates.active_session_id.as_deref(),
                 },
@@ -184,35 +1310,44 @@ impl AsyncComponent for Greeter {
                         model.updates.changed(Updates::manual_sess_mode())
                         || model.updates.changed(Updates::input_mode())
                     )]
-                    set_visible: model.updates.manual_sess_mode && !model.updates.is_input(),
+                    set_visible: model.view_state().session_entry,
                 },
                 #[template_child]
                 input_label {
                     #[track(model.updates.changed(Updates::input_mode()))]
-                    set_visible: model.updates.is_input(),
+                    set_visible: model.view_state().input_label,
                     #[track(model.updates.changed(Updates::input_prompt()))]
                     set_label: &model.updates.input_prompt,
                 },
                 #[template_child]
                 secret_entry {
                     #[track(model.updates.changed(Updates::input_mode()))]
-                    set_visible: model.updates.input_mode == InputMode::Secret,
+                    set_visible: model.view_state().secret_entry,
+                    #[track(model.updates.changed(Updates::input_hint()))]
+                    set_placeholder_text: model.input_placeholder(),
+                    #[track(model.updates.changed(Updates::input_hint()))]
+                    set_tooltip_text: model.input_tooltip(),
                     #[track(
                         model.updates.changed(Updates::input_mode())
-                        && model.updates.input_mode == InputMode::Secret
+                        && model.view_state().focus == Focus::SecretEntry
                     )]
                     grab_focus: (),
                     #[track(model.updates.changed(Updates::input()))]
                     set_text: &model.updates.input,
                     connect_activate[
                         sender,
+                        submit_on_enter = model.config.get_submit_on_enter(),
                         usernames_box = ui.usernames_box.clone(),
                         username_entry = ui.username_entry.clone(),
                         sessions_box = ui.sessions_box.clone(),
                         session_entry = ui.session_entry.clone(),
                     ] => move |this| {
+                        if !submit_on_enter {
+                            return;
+                        }
                         sender.input(Self::Input::Login {
                             input: this.text().to_string(),
+                            next_secret: None,
                             info: UserSessInfo::extract(
                                 &usernames_box, &username_entry, &sessions_box, &session_entry
                             ),
@@ -222,23 +1357,70 @@ impl AsyncComponent for Greeter {
                 #[template_child]
                 visible_entry {
                     #[track(model.updates.changed(Updates::input_mode()))]
-                    set_visible: model.updates.input_mode == InputMode::Visible,
+                    set_visible: model.view_state().visible_entry,
+                    #[track(model.updates.changed(Updates::input_hint()))]
+                    set_placeholder_text: model.input_placeholder(),
+                    #[track(model.updates.changed(Updates::input_hint()))]
+                    set_tooltip_text: model.input_tooltip(),
                     #[track(
                         model.updates.changed(Updates::input_mode())
-                        && model.updates.input_mode == InputMode::Visible
+                        && model.view_state().focus == Focus::VisibleEntry
                     )]
                     grab_focus: (),
                     #[track(model.updates.changed(Updates::input()))]
                     set_text: &model.updates.input,
                     connect_activate[
                         sender,
+                        submit_on_enter = model.config.get_submit_on_enter(),
+                        combined_secret_entry = ui.combined_secret_entry.clone(),
                         usernames_box = ui.usernames_box.clone(),
                         username_entry = ui.username_entry.clone(),
                         sessions_box = ui.sessions_box.clone(),
                         session_entry = ui.session_entry.clone(),
                     ] => move |this| {
+                        if combined_secret_entry.is_visible() {
+                            // Let the user enter the secret before logging in.
+                            combined_secret_entry.grab_focus();
+                            return;
+                        }
+                        if !submit_on_enter {
+                            return;
+                        }
                         sender.input(Self::Input::Login {
                             input: this.text().to_string(),
+                            next_secret: None,
+                            info: UserSessInfo::extract(
+                                &usernames_box, &username_entry, &sessions_box, &session_entry
+                            ),
+                        })
+                    }
+                },
+                #[template_child]
+                combined_secret_label {
+                    #[track(model.updates.changed(Updates::input_mode()))]
+                    set_visible: model.view_state().combined_secret,
+                },
+                #[template_child]
+                combined_secret_entry {
+                    #[track(model.updates.changed(Updates::input_mode()))]
+                    set_visible: model.view_state().combined_secret,
+                    #[track(model.updates.changed(Updates::input()))]
+                    set_text: &model.updates.input,
+                    connect_activate[
+                        sender,
+                        submit_on_enter = model.config.get_submit_on_enter(),
+                        visible_entry = ui.visible_entry.clone(),
+                        usernames_box = ui.usernames_box.clone(),
+                        username_entry = ui.username_entry.clone(),
+                        sessions_box = ui.sessions_box.clone(),
+                        session_entry = ui.session_entry.clone(),
+                    ] => move |this| {
+                        if !submit_on_enter {
+                            return;
+                        }
+                        sender.input(Self::Input::Login {
+                            input: visible_entry.text().to_string(),
+                            next_secret: Some(this.text().to_string()),
                             info: UserSessInfo::extract(
                                 &usernames_box, &username_entry, &sessions_box, &session_entry
                             ),
@@ -246,34 +1428,109 @@ impl AsyncComponent for Greeter {
                     }
                 },
                 #[template_child]
+                layout_label {
+                    #[track(
+                        model.updates.changed(Updates::input_mode())
+                        || model.updates.changed(Updates::keyboard_layout())
+                    )]
+                    set_visible: model.view_state().layout_label,
+                    #[track(model.updates.changed(Updates::keyboard_layout()))]
+                    set_label: model.updates.keyboard_layout.as_deref().unwrap_or_default(),
+                },
+                #[template_child]
+                caps_lock_label {
+                    #[track(
+                        model.updates.changed(Updates::input_mode())
+                        || model.updates.changed(Updates::caps_lock())
+                    )]
+                    set_visible: model.view_state().caps_lock_warning,
+                },
+                #[template_child]
+                first_login_label {
+                    // The hint is for the user selected in the drop-down, not for manually entered ones.
+                    #[track(
+                        model.updates.changed(Updates::first_login_hint())
+                        || model.updates.changed(Updates::manual_user_mode())
+                    )]
+                    set_visible: model.updates.first_login_hint.is_some() && !model.updates.manual_user_mode,
+                    #[track(model.updates.changed(Updates::first_login_hint()))]
+                    set_label: model.updates.first_login_hint.as_deref().unwrap_or_default(),
+                },
+                #[template_child]
+                combined_layout_label {
+                    #[track(
+                        model.updates.changed(Updates::input_mode())
+                        || model.updates.changed(Updates::keyboard_layout())
+                    )]
+                    set_visible: model.view_state().combined_layout_label,
+                    #[track(model.updates.changed(Updates::keyboard_layout()))]
+                    set_label: model.updates.keyboard_layout.as_deref().unwrap_or_default(),
+                },
+                #[template_child]
+                combined_caps_lock_label {
+                    #[track(
+                        model.updates.changed(Updates::input_mode())
+                        || model.updates.changed(Updates::caps_lock())
+                    )]
+                    set_visible: model.view_state().combined_caps_lock_warning,
+                },
+                #[template_child]
+                badge_entry {
+                    #[track(
+                        model.updates.changed(Updates::input_mode())
+                        || model.updates.gates_changed()
+                    )]
+                    set_visible: model.view_state().badge_entry,
+                    #[track(
+                        (model.updates.changed(Updates::input_mode()) || model.updates.gates_changed())
+                        && model.view_state().focus == Focus::BadgeEntry
+                    )]
+                    grab_focus: (),
+                    connect_activate[sender] => move |this| {
+                        let scan = this.text().to_string();
+                        this.set_text("");
+                        sender.input(Self::Input::BadgeScanned(scan));
+                    },
+                },
+                #[template_child]
                 user_toggle {
                     #[track(model.updates.changed(Updates::input_mode()))]
-                    set_sensitive: !model.updates.is_input(),
+                    set_sensitive: model.view_state().user_toggle_sensitive,
+                    // The manual mode can also be restored from the cache or set by a badge scan.
+                    #[track(model.updates.changed(Updates::manual_user_mode()))]
+                    set_active: model.updates.manual_user_mode,
                     connect_clicked => Self::Input::ToggleManualUser,
                 },
                 #[template_child]
                 sess_toggle {
                     #[track(model.updates.changed(Updates::input_mode()))]
-                    set_visible: !model.updates.is_input(),
+                    set_visible: model.view_state().sess_toggle,
+                    #[track(model.updates.changed(Updates::manual_sess_mode()))]
+                    set_active: model.updates.manual_sess_mode,
                     connect_clicked => Self::Input::ToggleManualSess,
                 },
                 #[template_child]
+                action_buttons { set_spacing: spacing },
+                #[template_child]
                 cancel_button {
                     #[track(model.updates.changed(Updates::input_mode()))]
-                    set_visible: model.updates.is_input(),
+                    set_visible: model.view_state().cancel_button,
                     connect_clicked => Self::Input::Cancel,
                 },
                 #[template_child]
                 login_button {
                     #[track(
-                        model.updates.changed(Updates::input_mode())
-                        && !model.updates.is_input()
+                        (model.updates.changed(Updates::input_mode()) || model.updates.gates_changed())
+                        && model.view_state().focus == Focus::LoginButton
                     )]
                     grab_focus: (),
+                    #[track(model.updates.changed(Updates::input_mode()))]
+                    set_label: model.view_state().login_label,
                     connect_clicked[
                         sender,
                         secret_entry = ui.secret_entry.clone(),
                         visible_entry = ui.visible_entry.clone(),
+                        combined_secret_entry = ui.combined_secret_entry.clone(),
                         usernames_box = ui.usernames_box.clone(),
                         username_entry = ui.username_entry.clone(),
                         sessions_box = ui.sessions_box.clone(),
@@ -290,6 +1547,9 @@ impl AsyncComponent for Greeter {
                                 // This should correspond to `InputMode::None`.
                                 String::new()
                             },
+                            next_secret: combined_secret_entry
+                                .is_visible()
+                                .then(|| combined_secret_entry.text().to_string()),
                             info: UserSessInfo::extract(
                                 &usernames_box, &username_entry, &sessions_box, &session_entry
                             ),
@@ -297,29 +1557,148 @@ impl AsyncComponent for Greeter {
                     }
                 },
                 #[template_child]
-                error_info {
-                    #[track(model.updates.changed(Updates::error()))]
-                    set_revealed: model.updates.error.is_some(),
+                bottom_box {
+                    set_margin_bottom: spacing,
+                    set_spacing: spacing,
+                },
+                #[template_child]
+                boot_banner_box {
+                    model.boot_banner.widget(),
+                },
+                #[template_child]
+                schedule_banner_box {
+                    model.schedule_banner.widget(),
                 },
                 #[template_child]
-                error_label {
-                    #[track(model.updates.changed(Updates::error()))]
-                    set_label: model.updates.error.as_ref().unwrap_or(&"".to_string()),
+                clock_banner_box {
+                    model.clock_banner.widget(),
                 },
                 #[template_child]
-                reboot_button { connect_clicked => Self::Input::Reboot },
+                switch_banner_box {
+                    model.switch_banner.widget(),
+                },
+                #[template_child]
+                duplicate_login_banner_box {
+                    model.duplicate_login_banner.widget(),
+                },
                 #[template_child]
-                poweroff_button { connect_clicked => Self::Input::PowerOff },
+                power_banner_box {
+                    model.power_banner.widget(),
+                },
+                #[template_child]
+                error_banner_box {
+                    model.error_banner.widget(),
+                },
+                #[template_child]
+                end_buttons_box {
+                    set_spacing: spacing,
+                    set_visible: model.config.get_visibility().end_buttons,
+                },
+                #[template_child]
+                suspend_button {
+                    set_visible: model.config.get_visibility().suspend_button,
+                    connect_clicked => Self::Input::Suspend,
+                },
+                #[template_child]
+                hibernate_button {
+                    set_visible: model.config.get_visibility().hibernate_button,
+                    connect_clicked => Self::Input::Hibernate,
+                },
+                #[template_child]
+                reboot_button {
+                    set_visible: model.config.get_visibility().reboot_button,
+                    connect_clicked => Self::Input::Reboot,
+                },
+                #[template_child]
+                poweroff_button {
+                    set_visible: model.config.get_visibility().poweroff_button,
+                    connect_clicked => Self::Input::PowerOff,
+                },
             }
         }
     }
 
     fn post_view() {
+        if model.updates.changed(Updates::badge_scan()) {
+            if let Some(scan) = &model.updates.badge_scan {
+                // Log in once the scanned user is shown, like with the login button.
+                sender.input(InputMsg::Login {
+                    input: String::new(),
+                    next_secret: scan.credential.clone(),
+                    info: UserSessInfo::extract(
+                        &widgets.ui.usernames_box,
+                        &widgets.ui.username_entry,
+                        &widgets.ui.sessions_box,
+                        &widgets.ui.session_entry,
+                    ),
+                });
+            }
+        }
+
         if model.updates.changed(Updates::monitor()) {
             if let Some(monitor) = &model.updates.monitor {
                 widgets.window.fullscreen_on_monitor(monitor);
                 // For some reason, the GTK settings are reset when changing monitors, so re-apply them.
                 setup_settings(self, &widgets.window);
+                // The new monitor may have a different size or scale.
+                if is_svg_background(self) {
+                    setup_background(self, &widgets.ui.background);
+                }
+            }
+        }
+
+        if model.updates.changed(Updates::message()) {
+            show_message(self, &widgets.ui.message_label);
+        }
+
+        if model.updates.changed(Updates::panel_values()) {
+            setup_settings(self, &widgets.window);
+            setup_background(self, &widgets.ui.background);
+        }
+
+        if model.updates.changed(Updates::high_contrast()) {
+            setup_settings(self, &widgets.window);
+        }
+
+        if model.updates.changed(Updates::preview_config()) {
+            setup_settings(self, &widgets.window);
+            setup_background(self, &widgets.ui.background);
+            load_scheduled_css(self);
+        }
+
+        if model.updates.changed(Updates::theme_period()) {
+            info!(
+                "Switching to the theme scheduled for: {:?}",
+                model.updates.theme_period
+            );
+            setup_settings(self, &widgets.window);
+            setup_background(self, &widgets.ui.background);
+            load_scheduled_css(self);
+        }
+
+        if model.updates.changed(Updates::error()) {
+            model.show_error_notification();
+        }
+
+        if model.updates.changed(Updates::settings_unlocked()) && model.updates.settings_unlocked {
+            let save_sender = sender.clone();
+            let close_sender = sender.clone();
+            show_settings_panel(
+                &widgets.window,
+                model.config.get_density().spacing(),
+                model.config.get_panel_values(),
+                move |values| save_sender.input(InputMsg::SaveSettings(values)),
+                move || close_sender.input(InputMsg::LockSettings),
+            );
+        }
+
+        if model.updates.changed(Updates::dry_run_request()) {
+            if let Some(request) = &model.updates.dry_run_request {
+                show_dry_run_dialog(
+                    &widgets.window,
+                    request,
+                    model.config.get_density().spacing(),
+                );
             }
         }
     }
@@ -330,12 +1709,39 @@ impl AsyncComponent for Greeter {
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
-        let mut model = Self::new(&input.config_path, input.demo).await;
+        let mut config = input.config;
+        if input.low_resource {
+            info!("Turning off the background, animations and the clock in low-resource mode");
+            config.limit_resources();
+        }
+        let mut model = Self::new(
+            &sender,
+            config,
+            input.demo,
+            input.dry_run,
+            input.ui_tour,
+            input.read_only,
+            input.transport,
+        )
+        .await;
+        model.startup_warnings_handler(&sender, input.warnings);
+        // The login form is hidden from the start if the secure attention key is required.
+        setup_secure_attention_key(&mut model, &root, &sender);
+        // The window is shown when the widgets are created.
+        setup_settings(&model, &root);
+        let spacing = model.config.get_density().spacing();
         let widgets = view_output!();
 
-        // Make the info bar permanently visible, since it was made invisible during init. The
-        // actual visuals are controlled by `InfoBar::set_revealed`.
-        widgets.ui.error_info.set_visible(true);
+        if model.config.get_toolkit() == &Toolkit::Adwaita {
+            #[cfg(feature = "libadwaita")]
+            setup_adwaita(&mut model, &root);
+            #[cfg(not(feature = "libadwaita"))]
+            warn!("Adwaita styling requested, but ReGreet wasn't compiled with libadwaita support");
+        }
+
+        // GTK mirrors the alignments, the order of children and the slide transitions for right-to-left widgets,
+        // and all widgets inherit the direction of the window.
+        root.set_direction(layout_direction(&model));
 
         // cfg directives don't work inside Relm4 view! macro.
         #[cfg(feature = "gtk4_8")]
@@ -351,7 +1757,7 @@ impl AsyncComponent for Greeter {
 
         // Cancel any previous session, just in case someone started one.
         if let Err(err) = model.greetd_client.lock().await.cancel_session().await {
-            warn!("Couldn't cancel greetd session: {err}");
+            warn!(error = %err, "Couldn't cancel greetd session");
         };
 
         model.choose_monitor(widgets.ui.display().name().as_str(), &sender);
@@ -368,7 +1774,34 @@ impl AsyncComponent for Greeter {
         // full-screening.
         setup_settings(&model, &root);
         setup_users_sessions(&model, &widgets);
+        setup_input_methods(&model, &widgets);
+        setup_greeting_scale(&model, &widgets);
+        setup_background(&model, &widgets.ui.background);
+        setup_backdrop(&model, &widgets);
+        setup_legal_notice(&model, &widgets);
+        show_message(&model, &widgets.ui.message_label);
+        setup_accessibility(&widgets.ui);
+        setup_power_menu(&model, &root, &sender);
+        setup_custom_buttons(&model, &widgets, &sender);
+        setup_settings_panel(&model, &widgets, &sender);
+        setup_osk(&model, &widgets, &sender);
+        setup_high_contrast(&model, &widgets, &sender);
+        setup_cursor_hiding(&model, &root);
+
+        let provider = gtk::CssProvider::new();
+        provider.load_from_data(&format!(
+            "{}{}{}",
+            concatcp!(BANNER_CSS, BACKDROP_CSS),
+            background_css(&model),
+            panel_css(&model)
+        ));
+        gtk::style_context_add_provider_for_display(
+            &widgets.ui.display(),
+            &provider,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
 
+        // The custom CSS is loaded last, so that it can override the default styling.
         if input.css_path.exists() {
             debug!("Loading custom CSS from file: {}", input.css_path.display());
             let provider = gtk::CssProvider::new();
@@ -380,9 +1813,82 @@ impl AsyncComponent for Greeter {
             );
         };
 
+        // The scheduled CSS is loaded after the custom CSS, so that it can override it at night or by day.
+        load_scheduled_css(&model);
+        gtk::style_context_add_provider_for_display(
+            &widgets.ui.display(),
+            &model.theme_css,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+
+        setup_file_drop(&model, &root, &sender);
+
+        if input.dump_css_nodes {
+            // Wait until the window is shown, so that all widgets have been added.
+            let window = root.clone();
+            let quit_sender = sender.clone();
+            gtk::glib::idle_add_local_once(move || {
+                print!("{}", css_node_tree(window.upcast_ref()));
+                quit_sender.input(InputMsg::Quit);
+            });
+        }
+
+        if input.inspector {
+            // Also allow reopening the inspector with Ctrl+Shift+I after closing it.
+            root.settings().set_gtk_enable_inspector_keybinding(true);
+            // Open it once the window is shown, so that it starts out inspecting the window.
+            gtk::glib::idle_add_local_once(|| gtk::Window::set_interactive_debugging(true));
+        }
+
         // Set the default behaviour of pressing the Return key to act like the login button.
         root.set_default_widget(Some(&widgets.ui.login_button));
 
+        // Time logins from the first key press, which is recorded without a message, since it's needed on every key.
+        let key_controller = gtk::EventControllerKey::new();
+        key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let first_key_press = model.first_key_press.clone();
+        key_controller.connect_key_pressed(move |_, _, _, _| {
+            if first_key_press.get().is_none() {
+                first_key_press.set(Some(Instant::now()));
+            }
+            gtk::glib::Propagation::Proceed
+        });
+        root.add_controller(key_controller);
+
+        // The active layout group is only known from key events, so watch all of them.
+        if !model.keyboard_layouts.is_empty() {
+            let key_controller = gtk::EventControllerKey::new();
+            key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+            let layout_sender = sender.clone();
+            let last_group = Cell::new(0);
+            key_controller.connect_key_released(move |controller, _, _, _| {
+                let group = controller.group();
+                if last_group.replace(group) != group {
+                    layout_sender.input(InputMsg::KeyboardLayoutChanged(group));
+                }
+            });
+            root.add_controller(key_controller);
+        }
+
+        // Caps Lock is the usual reason for a password that doesn't work, so warn about it.
+        if let Some(keyboard) = gtk::gdk::Display::default()
+            .and_then(|display| display.default_seat())
+            .and_then(|seat| seat.keyboard())
+        {
+            sender.input(InputMsg::CapsLockChanged(keyboard.caps_lock_state()));
+            let caps_lock_sender = sender.clone();
+            keyboard.connect_caps_lock_state_notify(move |keyboard| {
+                caps_lock_sender.input(InputMsg::CapsLockChanged(keyboard.caps_lock_state()));
+            });
+        }
+
+        // Quit only after cancelling any created session.
+        let quit_sender = sender.clone();
+        root.connect_close_request(move |_| {
+            quit_sender.input(InputMsg::Quit);
+            gtk::glib::Propagation::Stop
+        });
+
         AsyncComponentParts { model, widgets }
     }
 
@@ -398,14 +1904,26 @@ impl AsyncComponent for Greeter {
         self.updates.reset();
 
         match msg {
-            Self::Input::Login { input, info } => {
+            Self::Input::Login {
+                input,
+                next_secret,
+                info,
+            } => {
                 self.sess_info = Some(info);
-                self.login_click_handler(&sender, input).await
+                self.login_click_handler(&sender, input, next_secret).await
             }
             Self::Input::Cancel => self.cancel_click_handler().await,
             Self::Input::UserChanged(info) => {
-                self.sess_info = Some(info);
-                self.user_change_handler();
+                let auth_status = self.greetd_client.lock().await.get_auth_status().clone();
+                if let AuthStatus::NotStarted = auth_status {
+                    self.sess_info = Some(info);
+                    self.user_change_handler();
+                } else {
+                    self.queue_user_switch(info);
+                }
+            }
+            Self::Input::ConfirmUserSwitch(confirmed) => {
+                self.confirm_user_switch(&sender, confirmed).await
             }
             Self::Input::ToggleManualUser => self
                 .updates
@@ -413,8 +1931,33 @@ impl AsyncComponent for Greeter {
             Self::Input::ToggleManualSess => self
                 .updates
                 .set_manual_sess_mode(!self.updates.manual_sess_mode),
-            Self::Input::Reboot => self.reboot_click_handler(&sender),
-            Self::Input::PowerOff => self.poweroff_click_handler(&sender),
+            Self::Input::Reboot => self.power_click_handler(PowerAction::Reboot, &sender),
+            Self::Input::PowerOff => self.power_click_handler(PowerAction::PowerOff, &sender),
+            Self::Input::AbortPowerAction => self.abort_power_handler(),
+            Self::Input::Suspend => self.suspend_click_handler(&sender).await,
+            Self::Input::Hibernate => self.hibernate_click_handler(&sender).await,
+            Self::Input::CustomButton(index) => self.custom_button_handler(index, &sender),
+            Self::Input::UnlockSettings { username, password } => {
+                self.unlock_settings_handler(&sender, username, password)
+                    .await
+            }
+            Self::Input::SaveSettings(values) => self.save_settings_handler(values, &sender),
+            Self::Input::LockSettings => self.lock_settings_handler(),
+            Self::Input::SetHighContrast(high_contrast) => {
+                self.high_contrast_handler(high_contrast)
+            }
+            Self::Input::DropFile(path) => self.drop_file_handler(path, &sender),
+            Self::Input::ToggleOsk => self.toggle_osk_handler(),
+            Self::Input::ShowOsk => self.show_osk_handler(),
+            Self::Input::DuplicateLogin(action) => {
+                self.duplicate_login_handler(&sender, action).await
+            }
+            Self::Input::BadgeScanned(scan) => self.badge_scan_handler(scan).await,
+            Self::Input::SecureAttention => self.attention_handler(),
+            Self::Input::AcceptNotice => self.notice_handler(),
+            Self::Input::KeyboardLayoutChanged(group) => self.keyboard_layout_handler(group),
+            Self::Input::CapsLockChanged(caps_lock) => self.caps_lock_handler(caps_lock),
+            Self::Input::Quit => self.quit_handler().await,
         }
     }
 
@@ -432,12 +1975,40 @@ impl AsyncComponent for Greeter {
 
         match msg {
             Self::CommandOutput::ClearErr => self.updates.set_error(None),
+            Self::CommandOutput::DismissBootNotice => self.boot_banner.emit(BannerMsg::Hide),
             Self::CommandOutput::HandleGreetdResponse(response) => {
                 self.handle_greetd_response(&sender, response).await
             }
+            Self::CommandOutput::WriteStatus => self.write_status(),
+            Self::CommandOutput::CheckThemeSchedule => self
+                .updates
+                .set_theme_period(current_theme_period(&self.config)),
+            Self::CommandOutput::CheckScheduledShutdown => self.check_scheduled_shutdown().await,
+            Self::CommandOutput::CheckClock => self.check_clock_handler().await,
+            Self::CommandOutput::TourStep(step) => self.tour_step_handler(step),
+            Self::CommandOutput::StartSession => self.delayed_session_start_handler(&sender).await,
+            Self::CommandOutput::PowerCountdown(action, remaining) => {
+                self.power_countdown_handler(action, remaining)
+            }
+            Self::CommandOutput::RunPowerAction(action) => {
+                self.run_power_action(action, &sender).await
+            }
             Self::CommandOutput::MonitorRemoved(display_name) => {
                 self.choose_monitor(display_name.as_str(), &sender)
             }
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case(BgFit::Fill => (3840, 2160); "fill")]
+    #[test_case(BgFit::Contain => (2880, 2160); "contain")]
+    #[test_case(BgFit::Cover => (3840, 2880); "cover")]
+    #[test_case(BgFit::ScaleDown => (1600, 1200); "scale down")]
+    fn svg_render_size(fit: BgFit) -> (i32, i32) {
+        super::svg_render_size((800, 600), (1920, 1080), 2, &fit)
+    }
+}
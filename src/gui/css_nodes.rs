@@ -0,0 +1,72 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Reference of the greeter's CSS nodes, for theme authors
+//!
+//! This is printed by `regreet --dump-css-nodes`, so that themes can target widgets without reverse-engineering the
+//! widget hierarchy with the GTK inspector. Each node is shown as a selector that matches it, indented by its depth.
+
+use std::fmt::Write;
+
+use relm4::gtk::{self, prelude::*};
+
+/// Describe a CSS node as a selector, eg. `button#regreet-login-button.suggested-action`.
+fn selector(css_name: &str, id: Option<&str>, classes: &[&str]) -> String {
+    let mut selector = css_name.to_string();
+    if let Some(id) = id {
+        selector.push('#');
+        selector.push_str(id);
+    }
+    for class in classes {
+        selector.push('.');
+        selector.push_str(class);
+    }
+    selector
+}
+
+/// Append the node of the widget and its children to the tree.
+fn write_node(widget: &gtk::Widget, depth: usize, tree: &mut String) {
+    // GTK names widgets after their type, unless they're named explicitly.
+    let name = widget.widget_name();
+    let id = Some(name.as_str()).filter(|name| *name != widget.type_().name());
+    let classes = widget.css_classes();
+    let classes: Vec<_> = classes.iter().map(|class| class.as_str()).collect();
+    let hidden = if widget.is_visible() { "" } else { " (hidden)" };
+    let _ = writeln!(
+        tree,
+        "{:indent$}{}{hidden}",
+        "",
+        selector(&widget.css_name(), id, &classes),
+        indent = depth * 2
+    );
+
+    let mut child = widget.first_child();
+    while let Some(widget) = child {
+        write_node(&widget, depth + 1, tree);
+        child = widget.next_sibling();
+    }
+}
+
+/// Describe the tree of CSS nodes under the widget, with one node per line.
+pub(super) fn css_node_tree(widget: &gtk::Widget) -> String {
+    let mut tree = String::new();
+    write_node(widget, 0, &mut tree);
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case("box", None, &[] => "box"; "plain")]
+    #[test_case("button", Some("regreet-login-button"), &[] => "button#regreet-login-button"; "with ID")]
+    #[test_case(
+        "button", Some("regreet-login-button"), &["suggested-action", "regreet-login-button"]
+        => "button#regreet-login-button.suggested-action.regreet-login-button";
+        "with ID and classes"
+    )]
+    fn selector(css_name: &str, id: Option<&str>, classes: &[&str]) -> String {
+        super::selector(css_name, id, classes)
+    }
+}
@@ -0,0 +1,90 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! ID-based selection for drop-downs
+//!
+//! `gtk::ComboBoxText` is deprecated, but its API of selecting items by ID is convenient. This provides the same API
+//! for a `gtk::DropDown` whose items are IDs, with a separate label displayed for each ID.
+
+use std::collections::HashMap;
+
+use relm4::gtk::{
+    glib::GString, prelude::*, ClosureExpression, DropDown, Expression, StringList, StringObject,
+    INVALID_LIST_POSITION,
+};
+
+pub(super) trait DropDownIdExt {
+    /// Replace the items with the given IDs.
+    ///
+    /// Each ID is displayed with its label, or as-is if it has none. Initially, nothing is selected.
+    fn set_ids<'a>(&self, ids: impl IntoIterator<Item = &'a str>, labels: HashMap<String, String>);
+
+    /// Get the ID of the selected item.
+    fn active_id(&self) -> Option<GString>;
+
+    /// Select the item with the given ID, or select nothing if the ID is `None`.
+    ///
+    /// Returns whether an item with a matching ID was found.
+    fn set_active_id(&self, id: Option<&str>) -> bool;
+}
+
+impl DropDownIdExt for DropDown {
+    fn set_ids<'a>(&self, ids: impl IntoIterator<Item = &'a str>, labels: HashMap<String, String>) {
+        let model = StringList::new(&[]);
+        for id in ids {
+            model.append(id);
+        }
+
+        let expression = ClosureExpression::with_callback(Expression::NONE, move |values| {
+            let id = values[0]
+                .get::<StringObject>()
+                .expect("Drop-down items must be string objects")
+                .string();
+            labels
+                .get(id.as_str())
+                .cloned()
+                .unwrap_or_else(|| id.to_string())
+        });
+
+        self.set_expression(Some(expression));
+        self.set_model(Some(&model));
+        // Mimic `gtk::ComboBoxText`, which initially has no active item.
+        self.set_selected(INVALID_LIST_POSITION);
+    }
+
+    fn active_id(&self) -> Option<GString> {
+        self.selected_item()
+            .and_downcast::<StringObject>()
+            .map(|item| item.string())
+    }
+
+    fn set_active_id(&self, id: Option<&str>) -> bool {
+        let id = if let Some(id) = id {
+            id
+        } else {
+            self.set_selected(INVALID_LIST_POSITION);
+            return true;
+        };
+
+        let model = if let Some(model) = self.model() {
+            model
+        } else {
+            return false;
+        };
+
+        let position = (0..model.n_items()).find(|&position| {
+            model
+                .item(position)
+                .and_downcast::<StringObject>()
+                .is_some_and(|item| item.string().as_str() == id)
+        });
+
+        if let Some(position) = position {
+            self.set_selected(position);
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -4,9 +4,16 @@
 
 //! Message definitions for communication between the view and the model
 
+use std::path::PathBuf;
+
 use educe::Educe;
 use greetd_ipc::Response;
-use relm4::gtk::{glib::GString, prelude::*, ComboBoxText, Entry};
+use relm4::gtk::{glib::GString, prelude::*, DropDown, Entry};
+
+use crate::config::PanelValues;
+
+use super::dropdown::DropDownIdExt;
+use super::tour::TourStep;
 
 #[derive(Debug)]
 /// Info about the current user and chosen session
@@ -24,9 +31,9 @@ pub struct UserSessInfo {
 impl UserSessInfo {
     /// Extract session and user info from the relevant widgets.
     pub(super) fn extract(
-        usernames_box: &ComboBoxText,
+        usernames_box: &DropDown,
         username_entry: &Entry,
-        sessions_box: &ComboBoxText,
+        sessions_box: &DropDown,
         session_entry: &Entry,
     ) -> Self {
         Self {
@@ -38,6 +45,23 @@ impl UserSessInfo {
     }
 }
 
+/// Choices for logging in a user who already has an active session
+#[derive(Debug)]
+pub enum DuplicateLoginAction {
+    /// Log in anyway, starting a second session.
+    Continue,
+    /// Switch to the existing session instead.
+    Activate,
+    Cancel,
+}
+
+/// Actions that shut down the system, which can be aborted during a countdown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerAction {
+    Reboot,
+    PowerOff,
+}
+
 /// The messages sent by the view to the model
 #[derive(Educe)]
 #[educe(Debug)]
@@ -46,18 +70,61 @@ pub enum InputMsg {
     Login {
         #[educe(Debug = "ignore")]
         input: String,
+        /// Secret entered along with a visible input, to be sent for the next secret prompt
+        #[educe(Debug = "ignore")]
+        next_secret: Option<String>,
         info: UserSessInfo,
     },
     /// Cancel the login request
     Cancel,
     /// The current user was changed in the GUI.
     UserChanged(UserSessInfo),
+    /// Whether to switch the user while a login attempt is in progress.
+    ConfirmUserSwitch(bool),
+    /// What to do about logging in a user who already has an active session.
+    DuplicateLogin(DuplicateLoginAction),
     /// Toggle manual entry of user.
     ToggleManualUser,
     /// Toggle manual entry of session.
     ToggleManualSess,
     Reboot,
     PowerOff,
+    /// Abort the countdown before rebooting or powering off.
+    AbortPowerAction,
+    Suspend,
+    Hibernate,
+    /// A custom button from the config was clicked, which is identified by its index.
+    CustomButton(usize),
+    /// An admin entered their credentials to open the settings panel.
+    UnlockSettings {
+        username: String,
+        #[educe(Debug = "ignore")]
+        password: String,
+    },
+    /// Settings were saved from the settings panel.
+    SaveSettings(PanelValues),
+    /// The settings panel was closed, so it has to be unlocked again.
+    LockSettings,
+    /// The high-contrast theme was turned on or off.
+    SetHighContrast(bool),
+    /// A file was dropped onto the window in demo mode, to preview it.
+    DropFile(PathBuf),
+    /// The button for the on-screen keyboard was clicked.
+    ToggleOsk,
+    /// An entry was tapped on a touchscreen, so the on-screen keyboard is needed.
+    ShowOsk,
+    /// A badge was scanned, eg. with a barcode scanner attached as a keyboard.
+    BadgeScanned(#[educe(Debug = "ignore")] String),
+    /// The secure attention key was pressed.
+    SecureAttention,
+    /// The legal notice was accepted.
+    AcceptNotice,
+    /// The keyboard layout group was changed, as seen in a key event.
+    KeyboardLayoutChanged(u32),
+    /// Caps Lock was turned on or off.
+    CapsLockChanged(bool),
+    /// Quit the greeter, eg. when the window is closed.
+    Quit,
 }
 
 #[derive(Debug)]
@@ -65,8 +132,26 @@ pub enum InputMsg {
 pub enum CommandMsg {
     /// Clear the error message.
     ClearErr,
+    /// Dismiss the notification about the previous boot.
+    DismissBootNotice,
     /// Handle a response received from greetd
     HandleGreetdResponse(Response),
+    /// Write the current status to the status file.
+    WriteStatus,
+    /// Switch the theme if another one is scheduled for the current part of the day.
+    CheckThemeSchedule,
+    /// Check whether a shutdown is scheduled with logind.
+    CheckScheduledShutdown,
+    /// Check whether the system clock looks wrong.
+    CheckClock,
+    /// Show the next state in the UI tour.
+    TourStep(TourStep),
+    /// Start the session after authentication succeeded.
+    StartSession,
+    /// Show the seconds left before rebooting or powering off.
+    PowerCountdown(PowerAction, u64),
+    /// Reboot or power off after the countdown.
+    RunPowerAction(PowerAction),
     /// Notify the greeter that a monitor was removed.
     // The Gstring is the name of the display.
     MonitorRemoved(GString),
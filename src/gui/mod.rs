@@ -4,11 +4,18 @@
 
 //! The main GUI for the greeter
 
+mod accessibility;
 mod component;
+mod css_nodes;
+mod dropdown;
 mod messages;
 mod model;
 mod templates;
-pub(crate) mod widget {
+mod tour;
+mod view_state;
+pub mod widget {
+    pub mod backdrop;
+    pub mod banner;
     pub mod clock;
 }
 
@@ -8,38 +8,92 @@
 
 //! The main logic for the greeter
 
-use std::path::Path;
-use std::process::Command;
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::rc::Rc;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use greetd_ipc::{AuthMessageType, ErrorType, Response};
+use jiff::{Timestamp, Zoned};
 use relm4::{
     gtk::{
+        self,
         gdk::{Display, Monitor},
         prelude::*,
     },
     AsyncComponentSender, Component, Controller,
 };
-use tokio::{sync::Mutex, time::sleep};
+use tokio::{
+    sync::Mutex,
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
 
-use crate::cache::Cache;
-use crate::client::{AuthStatus, GreetdClient};
-use crate::config::Config;
-use crate::sysutil::{SessionInfo, SessionType, SysUtil};
+use crate::admin::{AdminAuth, AdminError};
+use crate::audit::{AuditLog, Outcome};
+use crate::cache::{time_ago, Cache};
+use crate::client::{AuthStatus, GreetdClient, GreetdTransport};
+use crate::config::{
+    fill_placeholders, Config, HostPreselect, PanelValues, PromptHint, ThemePeriod, ThemeVariant,
+};
+use crate::constants::CRASH_BREADCRUMB_PATH;
+use crate::demo::DemoFlow;
+use crate::fallback::clear_breadcrumb;
+use crate::logind::{
+    activate_session, scheduled_shutdown, user_sessions, ScheduledShutdown, UserSession,
+};
+use crate::metrics::{Metrics, MetricsFile};
+use crate::notify::{Level, Notifier};
+use crate::sanitize::sanitize;
+use crate::session::{ResolvedSession, SessionChoice, SessionResolver};
+use crate::status::{State, Status, StatusFile};
+use crate::sysutil::{home_is_empty, hostname, SysUtil};
+use crate::timedate::{check_clock, ClockProblem};
 
 use super::{
-    messages::{CommandMsg, UserSessInfo},
-    widget::clock::Clock,
+    messages::{CommandMsg, DuplicateLoginAction, InputMsg, PowerAction, UserSessInfo},
+    tour::{
+        start_tour, TourStep, TOUR_MSG_ERROR, TOUR_MSG_INFO, TOUR_MSG_LONG_INFO, TOUR_MSG_SECRET,
+        TOUR_MSG_VISIBLE,
+    },
+    view_state::ViewState,
+    widget::{
+        banner::{BannerKind, BannerMsg, BannerOutput, MessageBanner},
+        clock::Clock,
+    },
 };
 
 const ERROR_MSG_CLEAR_DELAY: u64 = 5;
+/// Informative messages longer than this need to be acknowledged, instead of being skipped automatically
+const LONG_INFO_MSG_LEN: usize = 200;
+/// Time to wait for greetd to cancel the session before exiting
+const EXIT_CANCEL_TIMEOUT: u64 = 2;
+/// Time for which the login duration is shown before starting the session, since the greeter exits then
+const LOGIN_DURATION_DISPLAY_DELAY: Duration = Duration::from_millis(1500);
+/// Time for which it's shown that no password is required before starting the session
+const PASSWORDLESS_DISPLAY_DELAY: Duration = Duration::from_secs(3);
+/// Seconds to count down before rebooting or powering off, during which it can be aborted
+const POWER_COUNTDOWN_SECS: u64 = 5;
+/// How often to check whether the scheduled theme has to be switched
+const THEME_SCHEDULE_INTERVAL: Duration = Duration::from_secs(60);
+/// Name of GTK's built-in high-contrast theme
+const HIGH_CONTRAST_THEME: &str = "HighContrast";
+/// How often to ask logind whether a shutdown is scheduled
+const SCHEDULED_SHUTDOWN_INTERVAL: Duration = Duration::from_secs(30);
+/// How often to check whether the system clock looks wrong
+///
+/// The first check is also delayed by this, since NTP usually synchronizes the clock shortly after booting.
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(PartialEq)]
 pub(super) enum InputMode {
     None,
     Secret,
     Visible,
+    /// A long informative message needs to be acknowledged by the user.
+    Acknowledge,
 }
 
 // Fields only set by the model, that are meant to be read only by the widgets
@@ -57,6 +111,8 @@ pub(super) struct Updates {
     pub(super) manual_sess_mode: bool,
     /// Input prompt sent by greetd for text input
     pub(super) input_prompt: String,
+    /// Placeholder and tooltip from the config for the entry of the input prompt
+    pub(super) input_hint: Option<PromptHint>,
     /// Whether the user is currently entering a secret, something visible or nothing
     pub(super) input_mode: InputMode,
     /// ID of the active session
@@ -65,17 +121,106 @@ pub(super) struct Updates {
     pub(super) time: String,
     /// Monitor where the window is displayed
     pub(super) monitor: Option<Monitor>,
+    /// Session start request that was skipped in a dry run, to be shown to the user
+    pub(super) dry_run_request: Option<String>,
+    /// Username to select in the drop-down, when reverting a change
+    // The same user may be selected again, after the drop-down was changed.
+    #[no_eq]
+    pub(super) selected_user: Option<String>,
+    /// Short name of the active keyboard layout, if known
+    pub(super) keyboard_layout: Option<String>,
+    /// When the selected user last logged in, to be shown in the tooltip of the drop-down
+    pub(super) last_login: Option<String>,
+    /// Username to put in the manual entry, eg. from a scanned badge
+    #[no_eq]
+    pub(super) manual_username: Option<String>,
+    /// Badge that was scanned, to log in with once the scanned user is shown
+    #[no_eq]
+    pub(super) badge_scan: Option<BadgeScan>,
+    /// Whether the secure attention key has to be pressed before the login form is shown
+    pub(super) awaiting_attention: bool,
+    /// Whether the legal notice has to be accepted before the login form is shown
+    pub(super) notice_pending: bool,
+    /// Part of the day whose theme is shown, if the theme is scheduled
+    pub(super) theme_period: Option<ThemePeriod>,
+    /// Hint shown when the selected user seems to log in for the first time
+    pub(super) first_login_hint: Option<String>,
+    /// Whether an admin has authenticated for the settings panel, which is shown when this is turned on
+    pub(super) settings_unlocked: bool,
+    /// Settings last saved from the settings panel, which are applied to the UI when changed
+    #[no_eq]
+    pub(super) panel_values: Option<PanelValues>,
+    /// Whether the high-contrast theme is turned on, which overrides the configured theme
+    pub(super) high_contrast: bool,
+    /// Config file dropped onto the window in demo mode, which is applied to the UI when changed
+    #[no_eq]
+    pub(super) preview_config: Option<PathBuf>,
+    /// Whether Caps Lock is on, which is warned about when entering a secret
+    pub(super) caps_lock: bool,
+}
+
+/// Login requested by scanning a badge
+pub(super) struct BadgeScan {
+    /// Credential from the badge, to be sent for the first secret prompt
+    pub(super) credential: Option<String>,
+}
+
+/// Process of the on-screen keyboard, which is killed when it's dropped, so that it doesn't outlive the greeter
+pub(super) struct OskProcess(Child);
+
+impl Drop for OskProcess {
+    fn drop(&mut self) {
+        info!("Hiding the on-screen keyboard");
+        // Killing fails if it has already exited, but it still has to be reaped.
+        let _ = self.0.kill();
+        if let Err(err) = self.0.wait() {
+            warn!(error = %err, "Couldn't wait for the on-screen keyboard to exit");
+        }
+    }
 }
 
 impl Updates {
     pub(super) fn is_input(&self) -> bool {
         self.input_mode != InputMode::None
     }
+
+    /// Whether the login form was just hidden or shown by the secure attention key or the legal notice.
+    pub(super) fn gates_changed(&self) -> bool {
+        self.changed(Self::awaiting_attention()) || self.changed(Self::notice_pending())
+    }
+}
+
+/// Get the part of the day whose theme should be shown now, if the theme is scheduled.
+pub(super) fn current_theme_period(config: &Config) -> Option<ThemePeriod> {
+    let schedule = config.get_theme_schedule()?;
+    let now = Zoned::now();
+    let period = schedule.period_at(now.hour() as u16 * 60 + now.minute() as u16);
+    if period.is_none() {
+        warn!(
+            day_start = schedule.day_start,
+            night_start = schedule.night_start,
+            "Ignoring the theme schedule, since its times aren't formatted as HH:MM"
+        );
+    }
+    period
 }
 
 /// Capitalize the first letter of the string.
 fn capitalize(string: &str) -> String {
-    string[0..1].to_uppercase() + &string[1..]
+    let mut chars = string.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Describe root or a system user for error messages.
+fn describe_system_user(username: &str) -> String {
+    if username == "root" {
+        username.to_string()
+    } else {
+        format!("system user '{username}'")
+    }
 }
 
 /// Greeter model that holds its state
@@ -92,49 +237,458 @@ pub struct Greeter {
     pub(super) sess_info: Option<UserSessInfo>,
     /// The updates from the model that are read by the view
     pub(super) updates: Updates,
+    /// Secret entered along with a visible input, to be sent for the next secret prompt
+    pub(super) pending_secret: Option<String>,
     /// Is it run as demo
     pub(super) demo: bool,
+    /// Whether to show the session start request instead of sending it to greetd
+    pub(super) dry_run: bool,
+    /// Don't write any files, eg. in demo mode or on immutable systems
+    pub(super) read_only: bool,
+    /// Overlay that shows notifications as toasts, which is only set when using libadwaita
+    #[cfg(feature = "libadwaita")]
+    pub(super) toast_overlay: Option<adw::ToastOverlay>,
 
     pub(super) clock: Controller<Clock>,
+    /// Banner that shows error messages
+    pub(super) error_banner: Controller<MessageBanner>,
+    /// Forwards notifications to the sinks in the config
+    pub(super) notifier: Notifier,
+    /// Banner that shows notices that can be dismissed, eg. about the previous boot
+    pub(super) boot_banner: Controller<MessageBanner>,
+    /// Level of the notice shown in the boot banner, if any
+    pub(super) boot_level: Option<Level>,
+    /// Banner that asks whether to switch the user while a login attempt is in progress
+    pub(super) switch_banner: Controller<MessageBanner>,
+    /// User selected while a login attempt is in progress, to be switched to on confirmation
+    pub(super) pending_user_switch: Option<UserSessInfo>,
+    /// Banner that asks whether to log in a user who already has an active session
+    pub(super) duplicate_login_banner: Controller<MessageBanner>,
+    /// Banner that shows the maintenance window, or a shutdown scheduled with logind
+    pub(super) schedule_banner: Controller<MessageBanner>,
+    /// Shutdown scheduled with logind that's shown in the banner
+    pub(super) scheduled_shutdown: Option<ScheduledShutdown>,
+    /// Banner that warns if the system clock looks wrong
+    pub(super) clock_banner: Controller<MessageBanner>,
+    /// Problem with the system clock that's shown in the banner
+    pub(super) clock_problem: Option<ClockProblem>,
+    /// Banner that counts down before rebooting or powering off, where it can be aborted
+    pub(super) power_banner: Controller<MessageBanner>,
+    /// Task counting down before rebooting or powering off, which is aborted to abort the action
+    pub(super) power_countdown: Option<JoinHandle<()>>,
+    /// Existing session of the user being logged in, which can be switched to
+    pub(super) existing_session: Option<UserSession>,
+    /// File that the status is periodically written to, if enabled in the config
+    pub(super) status_file: Option<StatusFile>,
+    /// When the greeter was started
+    pub(super) started: Instant,
+    /// The last error shown to the user
+    pub(super) last_error: Option<String>,
+    /// Short names of the keyboard layouts, in the order of the XKB layout groups
+    pub(super) keyboard_layouts: Vec<String>,
+    /// Counters about login attempts
+    pub(super) metrics: Metrics,
+    /// File that the metrics are written to, if enabled in the config
+    pub(super) metrics_file: Option<MetricsFile>,
+    /// Log that login attempts are appended to, if enabled in the config
+    pub(super) audit_log: Option<AuditLog>,
+    /// When the current login attempt was started
+    pub(super) auth_started: Option<Instant>,
+    /// Whether greetd sent any message during the current login attempt, which it doesn't for users without a password
+    pub(super) auth_prompted: bool,
+    /// Task that starts the session after a delay, which is aborted if the login attempt is cancelled
+    pub(super) session_start: Option<JoinHandle<()>>,
+    /// When a key was first pressed for the current login, which is set by a key event controller
+    pub(super) first_key_press: Rc<Cell<Option<Instant>>>,
+    /// User and session to preselect on this machine, instead of the cached ones
+    pub(super) host_preselect: Option<HostPreselect>,
+    /// Message asking to press the secure attention key, which is only set if it's required
+    pub(super) attention_msg: Option<String>,
+    /// Legal notice that has to be accepted before logging in
+    pub(super) legal_notice: Option<String>,
+    /// Whether the legal notice couldn't be loaded, in which case logging in is blocked
+    pub(super) notice_unavailable: bool,
+    /// Styling from the CSS file scheduled for the current part of the day
+    pub(super) theme_css: gtk::CssProvider,
+    /// Styling from a CSS file dropped onto the window in demo mode
+    pub(super) preview_css: gtk::CssProvider,
+    /// Greeting message for the selected user, which is the default one unless it's set for them in the config
+    pub(super) greeting: String,
+    /// Process of the on-screen keyboard, while it's shown
+    pub(super) osk: Option<OskProcess>,
+    /// Admin who has authenticated for the settings panel, while it's shown
+    pub(super) admin_auth: AdminAuth,
 }
 
 impl Greeter {
-    pub(super) async fn new(config_path: &Path, demo: bool) -> Self {
-        let config = Config::new(config_path);
+    pub(super) async fn new(
+        sender: &AsyncComponentSender<Self>,
+        config: Config,
+        demo: Option<DemoFlow>,
+        dry_run: bool,
+        ui_tour: bool,
+        read_only: bool,
+        transport: Option<Box<dyn GreetdTransport>>,
+    ) -> Self {
+        let keyboard_layouts = config.get_xkb_layouts();
+        let host_preselect = hostname().and_then(|hostname| {
+            let rule = config.get_host_preselect(&hostname)?;
+            info!("Preselecting user '{}' for host '{hostname}'", rule.user);
+            Some(rule.clone())
+        });
 
+        let read_only = demo.is_some() || read_only;
+        // The cache is still kept in memory for the current run.
+        let cache = if read_only {
+            Cache::read_only()
+        } else {
+            Cache::new()
+        };
+
+        // Restore whether the user prefers entering the username and session command manually.
+        let (manual_user_mode, manual_sess_mode) = cache.get_manual_modes();
+        let manual_username = if manual_user_mode {
+            host_preselect
+                .as_ref()
+                .map(|rule| rule.user.as_str())
+                .or_else(|| cache.get_last_user())
+                .map(String::from)
+        } else {
+            None
+        };
+
+        let (legal_notice, notice_problem) = match config.get_legal_notice() {
+            Ok(notice) => (notice, None),
+            Err(problem) => (None, Some(problem)),
+        };
         let updates = Updates {
             message: config.get_default_message(),
             error: None,
             input: String::new(),
-            manual_user_mode: false,
-            manual_sess_mode: false,
+            manual_user_mode,
+            manual_sess_mode,
             input_mode: InputMode::None,
             input_prompt: String::new(),
+            input_hint: None,
             active_session_id: None,
             tracker: 0,
             time: "".to_string(),
             monitor: None,
+            dry_run_request: None,
+            selected_user: None,
+            // Assume that the first layout is active until a key event says otherwise.
+            keyboard_layout: keyboard_layouts.first().cloned(),
+            last_login: None,
+            manual_username,
+            badge_scan: None,
+            // This is only required once the shortcut from the config is known to be valid.
+            awaiting_attention: false,
+            notice_pending: legal_notice.is_some() || notice_problem.is_some(),
+            theme_period: current_theme_period(&config),
+            first_login_hint: None,
+            settings_unlocked: false,
+            panel_values: None,
+            high_contrast: cache.get_high_contrast(),
+            preview_config: None,
+            caps_lock: false,
         };
-        let greetd_client = Arc::new(Mutex::new(
-            GreetdClient::new(demo)
+        let greetd_client = match (demo, transport) {
+            (None, Some(transport)) => GreetdClient::with_transport(transport),
+            _ => GreetdClient::new(demo)
                 .await
                 .expect("Couldn't initialize greetd client"),
-        ));
+        };
+        let greetd_client = Arc::new(Mutex::new(greetd_client));
+
+        let clock = Clock::builder().launch(config.get_clock_config()).detach();
+        let error_banner = MessageBanner::builder().launch(Vec::new()).detach();
+        let notifier = Notifier::new(config.get_notification_sinks());
+
+        let boot_banner = MessageBanner::builder()
+            .launch(vec!["Dismiss".to_string()])
+            .connect_receiver(|sender, BannerOutput::Action(_)| sender.emit(BannerMsg::Hide));
+        // Level of the notification shown in the banner, if any
+        let mut boot_level = None;
+        let marker = config.get_unclean_shutdown_marker();
+        let unclean_shutdown = marker.exists();
+        if unclean_shutdown {
+            warn!(
+                "Previous shutdown was unclean, since '{}' exists",
+                marker.display()
+            );
+            let message = config.get_unclean_shutdown_msg();
+            notifier.notify(Level::Info, message);
+            if config.get_notifications_enabled() {
+                boot_banner.emit(BannerMsg::Show(BannerKind::Info, message.to_string()));
+                boot_level = Some(Level::Info);
+            }
+        }
+
+        let sys_util = SysUtil::new(&config).expect("Couldn't read available users and sessions");
+        let malformed_sessions = sys_util.get_malformed_sessions();
+        if !malformed_sessions.is_empty() {
+            // The files have already been logged while scanning them.
+            let message = format!(
+                "Some session files are malformed: {}",
+                malformed_sessions
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            notifier.notify(Level::Warning, &message);
+            // Don't hide the message about the unclean shutdown, which is more important.
+            if config.get_notifications_enabled() && !unclean_shutdown {
+                boot_banner.emit(BannerMsg::Show(BannerKind::Warning, message));
+                boot_level = Some(Level::Warning);
+            }
+        }
+        if let Some(problem) = &notice_problem {
+            // Logging in without the notice could break the rules that require it, so the login form stays hidden.
+            error!(%problem, "Couldn't load the legal notice, so logging in is blocked");
+            let message = "The legal notice couldn't be loaded, so logging in isn't possible";
+            notifier.notify(Level::Error, message);
+            boot_banner.emit(BannerMsg::Show(BannerKind::Error, message.to_string()));
+            boot_level = Some(Level::Error);
+        }
+        if let Some(timeout) = boot_level.and_then(|level| config.get_dismiss_timeout(level)) {
+            sender.oneshot_command(async move {
+                sleep(timeout).await;
+                CommandMsg::DismissBootNotice
+            });
+        }
+
+        let switch_banner = MessageBanner::builder()
+            .launch(vec![
+                "Switch User".to_string(),
+                "Continue Login".to_string(),
+            ])
+            .forward(sender.input_sender(), |output| match output {
+                BannerOutput::Action(index) => InputMsg::ConfirmUserSwitch(index == 0),
+            });
+
+        let duplicate_login_banner = MessageBanner::builder()
+            .launch(vec![
+                "Log In Again".to_string(),
+                "Switch to Session".to_string(),
+                "Cancel".to_string(),
+            ])
+            .forward(sender.input_sender(), |output| match output {
+                BannerOutput::Action(0) => InputMsg::DuplicateLogin(DuplicateLoginAction::Continue),
+                BannerOutput::Action(1) => InputMsg::DuplicateLogin(DuplicateLoginAction::Activate),
+                BannerOutput::Action(_) => InputMsg::DuplicateLogin(DuplicateLoginAction::Cancel),
+            });
+
+        let schedule_banner = MessageBanner::builder().launch(Vec::new()).detach();
+        if let Some(message) = &config.get_maintenance().message {
+            schedule_banner.emit(BannerMsg::Show(BannerKind::Info, message.clone()));
+        }
+        // logind isn't asked in demo mode, since a demo isn't about the machine it runs on.
+        if config.get_maintenance().check_scheduled_shutdown && demo.is_none() {
+            sender.command(|sender, shutdown| {
+                shutdown
+                    .register(async move {
+                        loop {
+                            if sender.send(CommandMsg::CheckScheduledShutdown).is_err() {
+                                error!("No longer checking for scheduled shutdowns because `send` failed");
+                                break;
+                            }
+                            sleep(SCHEDULED_SHUTDOWN_INTERVAL).await;
+                        }
+                    })
+                    .drop_on_shutdown()
+            });
+        }
+
+        let clock_banner = MessageBanner::builder()
+            .launch(vec!["Dismiss".to_string()])
+            .connect_receiver(|sender, BannerOutput::Action(_)| sender.emit(BannerMsg::Hide));
+        if config.get_clock_warning_enabled() && demo.is_none() {
+            sender.command(|sender, shutdown| {
+                shutdown
+                    .register(async move {
+                        loop {
+                            sleep(CLOCK_CHECK_INTERVAL).await;
+                            if sender.send(CommandMsg::CheckClock).is_err() {
+                                error!("No longer checking the system clock because `send` failed");
+                                break;
+                            }
+                        }
+                    })
+                    .drop_on_shutdown()
+            });
+        }
+
+        let power_banner = MessageBanner::builder()
+            .launch(vec!["Abort".to_string()])
+            .forward(sender.input_sender(), |output| match output {
+                BannerOutput::Action(_) => InputMsg::AbortPowerAction,
+            });
+
+        // Like the cache, the status file, the metrics and the audit log aren't written in read-only mode.
+        if read_only
+            && [
+                config.get_status_path(),
+                config.get_metrics_path(),
+                config.get_audit_path(),
+            ]
+            .iter()
+            .any(Option::is_some)
+        {
+            info!("Read-only mode, so not writing the status file, the metrics or the audit log");
+        }
+        let status_file = config
+            .get_status_path()
+            .filter(|_| !read_only)
+            .map(StatusFile::new);
+        if status_file.is_some() {
+            let interval = config.get_status_interval();
+            sender.command(move |sender, shutdown| {
+                shutdown
+                    .register(async move {
+                        loop {
+                            if sender.send(CommandMsg::WriteStatus).is_err() {
+                                error!("No longer writing the status file because `send` failed");
+                                break;
+                            }
+                            sleep(interval).await;
+                        }
+                    })
+                    .drop_on_shutdown()
+            });
+        }
+
+        // The schedule is ignored if its times are invalid, so it's only checked if they're valid.
+        if updates.theme_period.is_some() {
+            sender.command(|sender, shutdown| {
+                shutdown
+                    .register(async move {
+                        loop {
+                            sleep(THEME_SCHEDULE_INTERVAL).await;
+                            if sender.send(CommandMsg::CheckThemeSchedule).is_err() {
+                                error!(
+                                    "No longer checking the theme schedule because `send` failed"
+                                );
+                                break;
+                            }
+                        }
+                    })
+                    .drop_on_shutdown()
+            });
+        }
+
+        if ui_tour {
+            if demo.is_some() {
+                start_tour(sender);
+            } else {
+                warn!("Not starting the UI tour, since it needs demo mode");
+            }
+        }
 
-        let clock = Clock::builder()
-            .launch(config.widget.clock.clone())
-            .detach();
+        let metrics_file = config
+            .get_metrics_path()
+            .filter(|_| !read_only)
+            .map(MetricsFile::new);
+        if let Some(metrics_file) = &metrics_file {
+            // Write the file right away, so that it exists before the first login attempt.
+            if let Err(err) = metrics_file.write(&Metrics::default()) {
+                error!(error = %err, "Couldn't write metrics file");
+            }
+        }
+
+        let audit_log = config
+            .get_audit_path()
+            .filter(|_| !read_only)
+            .and_then(|path| match AuditLog::open(path) {
+                Ok(audit_log) => Some(audit_log),
+                Err(err) => {
+                    error!(path = %path.display(), error = %err, "Couldn't open audit log");
+                    None
+                }
+            });
 
         Self {
             greetd_client,
-            sys_util: SysUtil::new(&config).expect("Couldn't read available users and sessions"),
-            cache: Cache::new(),
+            sys_util,
+            cache,
             sess_info: None,
             config,
             updates,
-            demo,
+            pending_secret: None,
+            demo: demo.is_some(),
+            dry_run,
+            read_only,
+            #[cfg(feature = "libadwaita")]
+            toast_overlay: None,
             clock,
+            error_banner,
+            notifier,
+            boot_banner,
+            boot_level,
+            switch_banner,
+            pending_user_switch: None,
+            duplicate_login_banner,
+            schedule_banner,
+            scheduled_shutdown: None,
+            clock_banner,
+            clock_problem: None,
+            power_banner,
+            power_countdown: None,
+            existing_session: None,
+            status_file,
+            started: Instant::now(),
+            last_error: None,
+            keyboard_layouts,
+            metrics: Metrics::default(),
+            metrics_file,
+            audit_log,
+            auth_started: None,
+            auth_prompted: false,
+            session_start: None,
+            first_key_press: Rc::default(),
+            host_preselect,
+            attention_msg: None,
+            legal_notice,
+            notice_unavailable: notice_problem.is_some(),
+            theme_css: gtk::CssProvider::new(),
+            preview_css: gtk::CssProvider::new(),
+            greeting: config.get_default_message(),
+            osk: None,
+            admin_auth: AdminAuth::default(),
+        }
+    }
+
+    /// Get the GTK theme name, preferring the one scheduled for the current part of the day.
+    pub(super) fn gtk_theme(&self) -> Option<&str> {
+        if self.updates.high_contrast {
+            return Some(HIGH_CONTRAST_THEME);
         }
+        self.theme_variant()
+            .and_then(|variant| variant.gtk_theme.as_deref())
+            .or_else(|| self.config.get_gtk_theme())
+    }
+
+    /// Get whether the dark theme is preferred, preferring the setting scheduled for the current part of the day.
+    pub(super) fn prefer_dark(&self) -> Option<bool> {
+        self.theme_variant()
+            .and_then(|variant| variant.prefer_dark)
+            .or_else(|| self.config.get_prefer_dark())
+    }
+
+    /// Get the background path, preferring the one scheduled for the current part of the day.
+    pub(super) fn background(&self) -> Option<&str> {
+        self.theme_variant()
+            .and_then(|variant| variant.background.as_deref())
+            .or_else(|| self.config.get_background())
+    }
+
+    /// Get the path to the CSS file scheduled for the current part of the day.
+    pub(super) fn scheduled_css(&self) -> Option<&str> {
+        self.theme_variant()?.css.as_deref()
+    }
+
+    fn theme_variant(&self) -> Option<&ThemeVariant> {
+        self.config.get_theme_variant(self.updates.theme_period)
     }
 
     /// Make the greeter full screen over the first monitor.
@@ -177,7 +731,14 @@ impl Greeter {
     }
 
     /// Run a command and log any errors in a background thread.
-    fn run_cmd(command: &[String], sender: &AsyncComponentSender<Self>) {
+    ///
+    /// In demo mode, commands are never run, so that a demo on a real seat can't eg. reboot it.
+    fn run_cmd(&self, command: &[String], sender: &AsyncComponentSender<Self>) {
+        if self.demo {
+            info!("demo: skip running command: {command:?}");
+            return;
+        }
+
         let mut process = Command::new(&command[0]);
         process.args(command[1..].iter());
         // Run the command and check its output in a separate thread, so as to not block the GUI.
@@ -185,40 +746,414 @@ impl Greeter {
             Ok(output) => {
                 if !output.status.success() {
                     if let Ok(err) = std::str::from_utf8(&output.stderr) {
-                        error!("Failed to launch command: {err}")
+                        error!(stderr = err, "Failed to launch command")
                     } else {
-                        error!("Failed to launch command: {:?}", output.stderr)
+                        error!(stderr = ?output.stderr, "Failed to launch command")
                     }
                 }
             }
-            Err(err) => error!("Failed to launch command: {err}"),
+            Err(err) => error!(error = %err, "Failed to launch command"),
         });
     }
 
-    /// Event handler for clicking the "Reboot" button
+    /// Prepare for the greeter to exit.
+    ///
+    /// This cancels any in-flight greetd session, so that greetd isn't left holding an orphaned session. To not hang
+    /// while exiting, greetd is only waited on for a short time.
+    async fn prepare_exit(&mut self) {
+        let cancel = async {
+            let mut client = self.greetd_client.lock().await;
+            if let AuthStatus::NotStarted = client.get_auth_status() {
+                return;
+            }
+            info!("Cancelling greetd session before exiting");
+            if let Err(err) = client.cancel_session().await {
+                warn!(error = %err, "Couldn't cancel greetd session");
+            }
+        };
+        if timeout(Duration::from_secs(EXIT_CANCEL_TIMEOUT), cancel)
+            .await
+            .is_err()
+        {
+            warn!("Timed out while cancelling greetd session");
+        }
+
+        // The greeter keeps running if exiting fails (eg. if the reboot command fails), or after resuming from suspend.
+        self.reset_auth_state();
+        self.hide_osk();
+        self.clear_crash_breadcrumb();
+    }
+
+    /// Reset the count of unstable starts, since the greeter exits cleanly.
+    ///
+    /// Otherwise, quick logins (eg. of kiosk users) would count as a crash loop.
+    fn clear_crash_breadcrumb(&self) {
+        if self.read_only {
+            return;
+        }
+        let path = Path::new(CRASH_BREADCRUMB_PATH);
+        if let Err(err) = clear_breadcrumb(path) {
+            warn!(path = %path.display(), error = %err, "Couldn't remove the crash breadcrumb");
+        }
+    }
+
+    /// Event handler for closing the window
     ///
-    /// This reboots the PC.
+    /// This quits the greeter.
     #[instrument(skip_all)]
-    pub(super) fn reboot_click_handler(&self, sender: &AsyncComponentSender<Self>) {
-        if self.demo {
-            info!("demo: skip reboot");
+    pub(super) async fn quit_handler(&mut self) {
+        info!("Quitting");
+        self.prepare_exit().await;
+        relm4::main_application().quit();
+    }
+
+    /// Event handler for clicking the "Reboot" or "Power-Off" buttons
+    ///
+    /// This counts down before running the action, so that it can still be aborted. Any earlier countdown is replaced.
+    #[instrument(skip(self, sender))]
+    pub(super) fn power_click_handler(
+        &mut self,
+        action: PowerAction,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        if let Some(countdown) = self.power_countdown.take() {
+            countdown.abort();
+        }
+        info!("Counting down before running the power action");
+        let sender = sender.command_sender().clone();
+        self.power_countdown = Some(relm4::spawn(async move {
+            for remaining in (1..=POWER_COUNTDOWN_SECS).rev() {
+                sender.emit(CommandMsg::PowerCountdown(action, remaining));
+                sleep(Duration::from_secs(1)).await;
+            }
+            sender.emit(CommandMsg::RunPowerAction(action));
+        }));
+    }
+
+    /// Show the seconds left before running the power action.
+    pub(super) fn power_countdown_handler(&self, action: PowerAction, remaining: u64) {
+        // The countdown may have been aborted after the message was sent.
+        if self.power_countdown.is_none() {
+            return;
+        }
+        let verb = match action {
+            PowerAction::Reboot => "Rebooting",
+            PowerAction::PowerOff => "Powering off",
+        };
+        let unit = if remaining == 1 { "second" } else { "seconds" };
+        self.power_banner.emit(BannerMsg::Show(
+            BannerKind::Warning,
+            format!("{verb} in {remaining} {unit}"),
+        ));
+    }
+
+    /// Event handler for clicking "Abort" during the countdown before rebooting or powering off
+    #[instrument(skip_all)]
+    pub(super) fn abort_power_handler(&mut self) {
+        if let Some(countdown) = self.power_countdown.take() {
+            info!("Aborting the power action");
+            countdown.abort();
+        }
+        self.power_banner.emit(BannerMsg::Hide);
+    }
+
+    /// Run the power action after the countdown.
+    pub(super) async fn run_power_action(
+        &mut self,
+        action: PowerAction,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        // The countdown may have been aborted after the message was sent.
+        if self.power_countdown.take().is_none() {
             return;
         }
+        self.power_banner.emit(BannerMsg::Hide);
+        match action {
+            PowerAction::Reboot => self.reboot_handler(sender).await,
+            PowerAction::PowerOff => self.poweroff_handler(sender).await,
+        }
+    }
+
+    /// Reboot the PC.
+    #[instrument(skip_all)]
+    async fn reboot_handler(&mut self, sender: &AsyncComponentSender<Self>) {
         info!("Rebooting");
-        Self::run_cmd(&self.config.get_sys_commands().reboot, sender);
+        self.prepare_exit().await;
+        self.run_cmd(&self.config.get_sys_commands().reboot, sender);
     }
 
-    /// Event handler for clicking the "Power-Off" button
+    /// Shut down the PC.
+    #[instrument(skip_all)]
+    async fn poweroff_handler(&mut self, sender: &AsyncComponentSender<Self>) {
+        info!("Shutting down");
+        self.prepare_exit().await;
+        self.run_cmd(&self.config.get_sys_commands().poweroff, sender);
+    }
+
+    /// Event handler for clicking the "Suspend" button
     ///
-    /// This shuts down the PC.
+    /// This suspends the PC. The greeter keeps running, so any login attempt is cancelled to not resume into it.
     #[instrument(skip_all)]
-    pub(super) fn poweroff_click_handler(&self, sender: &AsyncComponentSender<Self>) {
-        if self.demo {
-            info!("demo: skip shutdown");
+    pub(super) async fn suspend_click_handler(&mut self, sender: &AsyncComponentSender<Self>) {
+        info!("Suspending");
+        self.prepare_exit().await;
+        self.run_cmd(&self.config.get_sys_commands().suspend, sender);
+    }
+
+    /// Event handler for clicking the "Hibernate" button
+    ///
+    /// This hibernates the PC. The greeter keeps running, so any login attempt is cancelled to not resume into it.
+    #[instrument(skip_all)]
+    pub(super) async fn hibernate_click_handler(&mut self, sender: &AsyncComponentSender<Self>) {
+        info!("Hibernating");
+        self.prepare_exit().await;
+        self.run_cmd(&self.config.get_sys_commands().hibernate, sender);
+    }
+
+    /// Event handler for clicking a custom button from the config
+    ///
+    /// This runs the button's command.
+    #[instrument(skip(self, sender))]
+    pub(super) fn custom_button_handler(&self, index: usize, sender: &AsyncComponentSender<Self>) {
+        let button = if let Some(button) = self.config.get_sys_commands().buttons.get(index) {
+            button
+        } else {
+            return;
+        };
+        if button.command.is_empty() {
+            warn!("No command set for the button '{}'", button.label);
             return;
         }
-        info!("Shutting down");
-        Self::run_cmd(&self.config.get_sys_commands().poweroff, sender);
+        info!("Running the command of the button '{}'", button.label);
+        self.run_cmd(&button.command, sender);
+    }
+
+    /// Event handler for turning the high-contrast theme on or off
+    ///
+    /// The choice is saved right away, so that it's kept even if nobody logs in.
+    #[instrument(skip(self))]
+    pub(super) fn high_contrast_handler(&mut self, high_contrast: bool) {
+        if high_contrast == self.updates.high_contrast {
+            return;
+        }
+        info!(
+            "Turning the high-contrast theme {}",
+            if high_contrast { "on" } else { "off" }
+        );
+        self.updates.set_high_contrast(high_contrast);
+        self.cache.set_high_contrast(high_contrast);
+        if let Err(err) = self.cache.save() {
+            error!(error = %err, "Error saving cache to disk");
+        }
+    }
+
+    /// Event handler for dropping a file onto the window in demo mode
+    ///
+    /// Config files replace the config, and CSS files are loaded over all other CSS, so that themes can be previewed
+    /// without restarting the greeter.
+    #[instrument(skip(self, sender))]
+    pub(super) fn drop_file_handler(&mut self, path: PathBuf, sender: &AsyncComponentSender<Self>) {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("css") => {
+                info!(path = %path.display(), "Previewing CSS file");
+                self.preview_css.load_from_path(&path);
+            }
+            Some("toml") => match Config::load_preview(&path) {
+                Ok(config) => {
+                    info!(path = %path.display(), "Previewing config file");
+                    self.config = config;
+                    // Only replace the greeting if it's shown, and not eg. a message from PAM.
+                    let showing_greeting = self.updates.message == self.greeting;
+                    self.greeting = self.config.get_default_message();
+                    if showing_greeting {
+                        self.show_idle_message();
+                    }
+                    self.updates.set_preview_config(Some(path));
+                }
+                Err(err) => self.display_error(
+                    sender,
+                    "Couldn't load the dropped config file",
+                    &format!("Couldn't load config file '{}': {err}", path.display()),
+                ),
+            },
+            _ => self.display_error(
+                sender,
+                "Only config and CSS files can be dropped",
+                &format!(
+                    "Ignoring dropped file '{}', which isn't a TOML or CSS file",
+                    path.display()
+                ),
+            ),
+        }
+    }
+
+    /// Check whether the on-screen keyboard is shown, which it no longer is if it was closed by itself.
+    fn osk_shown(&mut self) -> bool {
+        self.osk
+            .as_mut()
+            .is_some_and(|osk| matches!(osk.0.try_wait(), Ok(None)))
+    }
+
+    /// Event handler for showing the on-screen keyboard, eg. when an entry is tapped
+    ///
+    /// Unlike other commands, this is also run in demo mode, since the keyboard can't do any harm.
+    #[instrument(skip_all)]
+    pub(super) fn show_osk_handler(&mut self) {
+        if self.osk_shown() {
+            return;
+        }
+        let command = if let Some(osk) = self.config.get_osk() {
+            &osk.command
+        } else {
+            return;
+        };
+        info!("Showing the on-screen keyboard: {command:?}");
+        match Command::new(&command[0]).args(&command[1..]).spawn() {
+            Ok(child) => self.osk = Some(OskProcess(child)),
+            Err(err) => error!(error = %err, "Failed to launch the on-screen keyboard"),
+        }
+    }
+
+    /// Hide the on-screen keyboard by killing it, if it's shown.
+    ///
+    /// This has to be done before exiting the process, since the keyboard isn't dropped then.
+    fn hide_osk(&mut self) {
+        self.osk = None;
+    }
+
+    /// Event handler for clicking the button for the on-screen keyboard
+    #[instrument(skip_all)]
+    pub(super) fn toggle_osk_handler(&mut self) {
+        if self.osk_shown() {
+            self.hide_osk();
+        } else {
+            self.show_osk_handler();
+        }
+    }
+
+    /// Event handler for entering an admin's credentials, which shows the settings panel if they're correct
+    #[instrument(skip_all)]
+    pub(super) async fn unlock_settings_handler(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+        username: String,
+        password: String,
+    ) {
+        // greetd only has one session at a time, so a login attempt in progress is cancelled.
+        let auth_status = self.greetd_client.lock().await.get_auth_status().clone();
+        if !matches!(auth_status, AuthStatus::NotStarted) {
+            self.cancel_click_handler().await;
+        }
+
+        let result = self
+            .admin_auth
+            .authenticate(
+                &mut *self.greetd_client.lock().await,
+                &self.config,
+                &username,
+                password,
+            )
+            .await;
+        if let Err(err) = result {
+            // Don't tell whether the user is an admin.
+            self.display_error(
+                sender,
+                "Couldn't open the settings",
+                &format!("Couldn't authenticate '{username}' for the settings panel: {err}"),
+            );
+            return;
+        }
+        self.updates.set_settings_unlocked(true);
+    }
+
+    /// Show the warnings that were logged before the greeter started, eg. about the config or the log timezone.
+    pub(super) fn startup_warnings_handler(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+        warnings: Vec<String>,
+    ) {
+        if warnings.is_empty() {
+            return;
+        }
+        // The warnings have already been logged while replaying the early events.
+        let message = warnings.join("\n");
+        self.notifier.notify(Level::Warning, &message);
+        // Don't hide the notices from the boot, which are more important.
+        if !self.config.get_notifications_enabled() || self.boot_level.is_some() {
+            return;
+        }
+        self.boot_banner
+            .emit(BannerMsg::Show(BannerKind::Warning, message));
+        self.boot_level = Some(Level::Warning);
+        if let Some(timeout) = self.config.get_dismiss_timeout(Level::Warning) {
+            sender.oneshot_command(async move {
+                sleep(timeout).await;
+                CommandMsg::DismissBootNotice
+            });
+        }
+    }
+
+    /// Event handler for closing the settings panel, which has to be unlocked again to be shown again
+    pub(super) fn lock_settings_handler(&mut self) {
+        self.admin_auth.lock();
+        self.updates.set_settings_unlocked(false);
+    }
+
+    /// Apply the settings from the settings panel, and save them for later starts of the greeter.
+    ///
+    /// This is rejected unless an admin has authenticated. In read-only mode, the settings are only applied.
+    pub(super) fn save_settings_handler(
+        &mut self,
+        values: PanelValues,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        match self
+            .admin_auth
+            .save(&self.config, values.clone(), self.read_only)
+        {
+            Ok(()) => (),
+            Err(AdminError::NotAuthenticated) => {
+                self.display_error(
+                    sender,
+                    "Couldn't save the settings",
+                    "Rejected the settings from the settings panel, since no admin has authenticated",
+                );
+                return;
+            }
+            Err(AdminError::ReadOnly) => {
+                info!("Not saving the settings from the settings panel in read-only mode");
+                self.boot_banner.emit(BannerMsg::Show(
+                    BannerKind::Warning,
+                    "The settings are only applied until the greeter restarts, since it's read-only"
+                        .to_string(),
+                ));
+            }
+            Err(err) => self.display_error(
+                sender,
+                "Couldn't save the settings",
+                &format!("Couldn't save the settings from the settings panel: {err}"),
+            ),
+        }
+
+        info!("Applying the settings from the settings panel: {values:?}");
+
+        self.config.set_panel_values(values.clone());
+        self.updates.set_panel_values(Some(values));
+
+        // Only replace the greeting if it's shown, and not eg. a message from PAM.
+        let showing_greeting = self.updates.message == self.greeting;
+        self.greeting = if let Some(username) = self.get_current_username() {
+            self.config.get_user_greeting(&username)
+        } else {
+            self.config.get_default_message()
+        };
+        if showing_greeting {
+            self.show_idle_message();
+        }
     }
 
     /// Event handler for clicking the "Cancel" button
@@ -227,11 +1162,79 @@ impl Greeter {
     #[instrument(skip_all)]
     pub(super) async fn cancel_click_handler(&mut self) {
         if let Err(err) = self.greetd_client.lock().await.cancel_session().await {
-            warn!("Couldn't cancel greetd session: {err}");
+            warn!(error = %err, "Couldn't cancel greetd session");
         };
+        self.reset_auth_state();
+    }
+
+    /// Go back to the user/session chooser.
+    ///
+    /// If the secure attention key is required, it has to be pressed again before the next login attempt.
+    fn reset_auth_state(&mut self) {
+        if let Some(session_start) = self.session_start.take() {
+            session_start.abort();
+        }
+        self.pending_secret = None;
+        self.first_key_press.set(None);
         self.updates.set_input(String::new());
         self.updates.set_input_mode(InputMode::None);
-        self.updates.set_message(self.config.get_default_message())
+        if self.attention_msg.is_some() {
+            self.updates.set_awaiting_attention(true);
+        }
+        self.show_idle_message();
+    }
+
+    /// Show the message for when no login attempt is in progress.
+    ///
+    /// This asks to press the secure attention key, if it's required.
+    fn show_idle_message(&mut self) {
+        let message = match &self.attention_msg {
+            Some(message) if self.updates.awaiting_attention => message.clone(),
+            _ => self.greeting.clone(),
+        };
+        self.updates.set_message(message);
+    }
+
+    /// Require the secure attention key to be pressed before the login form is shown.
+    ///
+    /// The label describes the key combination to the user, eg. "Ctrl+Alt+Delete".
+    pub(super) fn require_attention(&mut self, key_label: &str) {
+        self.attention_msg = Some(fill_placeholders(
+            &self.config.get_messages().attention,
+            &[("key", key_label)],
+        ));
+        self.updates.set_awaiting_attention(true);
+        self.show_idle_message();
+    }
+
+    /// Event handler for pressing the secure attention key
+    ///
+    /// This shows the login form, if it's hidden.
+    #[instrument(skip_all)]
+    pub(super) fn attention_handler(&mut self) {
+        if !self.updates.awaiting_attention {
+            return;
+        }
+        info!("Secure attention key pressed");
+        self.updates.set_awaiting_attention(false);
+        self.show_idle_message();
+    }
+
+    /// Event handler for accepting the legal notice
+    ///
+    /// The notice only has to be accepted once, even if the secure attention key has to be pressed again.
+    #[instrument(skip_all)]
+    pub(super) fn notice_handler(&mut self) {
+        if !self.updates.notice_pending || self.updates.awaiting_attention {
+            return;
+        }
+        if self.notice_unavailable {
+            // The button is hidden, so this shouldn't happen.
+            warn!("Not accepting the legal notice, since it couldn't be loaded");
+            return;
+        }
+        info!("Legal notice accepted");
+        self.updates.set_notice_pending(false);
     }
 
     /// Create a greetd session, i.e. start a login attempt for the current user.
@@ -259,7 +1262,21 @@ impl Greeter {
             debug!("Manually entered session command is parsable");
         };
 
+        if self.config.get_deny_system_users() && self.sys_util.is_system_user(&username) {
+            self.display_error(
+                sender,
+                &format!(
+                    "Logging in as {} isn't allowed here",
+                    describe_system_user(&username)
+                ),
+                &format!("Refusing to log in system user '{username}'"),
+            );
+            return;
+        }
+
         info!("Creating session for user: {username}");
+        self.update_metrics(Metrics::login_started);
+        self.auth_started = Some(Instant::now());
 
         // Create a session for the current user.
         let response = self
@@ -272,15 +1289,125 @@ impl Greeter {
                 panic!("Failed to create session for username '{username}': {err}",)
             });
 
+        self.auth_prompted = false;
         self.handle_greetd_response(sender, response).await;
     }
 
+    /// Start the session after a while, unless the login attempt is cancelled before.
+    fn delay_session_start(&mut self, sender: &AsyncComponentSender<Self>, delay: Duration) {
+        let sender = sender.command_sender().clone();
+        self.session_start = Some(relm4::spawn(async move {
+            sleep(delay).await;
+            sender.emit(CommandMsg::StartSession);
+        }));
+    }
+
+    /// Start the session once the delay before it is over.
+    pub(super) async fn delayed_session_start_handler(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        // The login attempt may have been cancelled after the message was sent.
+        if self.session_start.take().is_none() {
+            return;
+        }
+        self.start_session(sender).await;
+    }
+
+    /// Check whether the authenticated user already has an active session, and if so, ask whether to log in anyway or
+    /// to switch to that session.
+    ///
+    /// This must only be checked once greetd has authenticated the user, since anyone at the greeter could find out who
+    /// is logged in otherwise.
+    async fn has_active_session(&mut self, username: &str) -> bool {
+        if self.demo || !self.config.get_check_duplicate_login() {
+            return false;
+        }
+
+        let sessions = match user_sessions(username).await {
+            Ok(sessions) => sessions,
+            Err(err) => {
+                // logind might just not be running, so don't block the login.
+                warn!(user = username, error = %err, "Couldn't check for active sessions of user");
+                return false;
+            }
+        };
+        let session = if let Some(session) = sessions.into_iter().next() {
+            session
+        } else {
+            return false;
+        };
+
+        info!("User '{username}' already has an active session: {session:?}");
+        self.duplicate_login_banner.emit(BannerMsg::Show(
+            BannerKind::Warning,
+            format!("{username} is already logged in on {}", session.location()),
+        ));
+        self.existing_session = Some(session);
+        true
+    }
+
+    /// Event handler for choosing what to do about logging in a user who already has an active session
+    ///
+    /// The user can be logged in again, the existing session can be switched to, or nothing can be done.
+    #[instrument(skip_all)]
+    pub(super) async fn duplicate_login_handler(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+        action: DuplicateLoginAction,
+    ) {
+        self.duplicate_login_banner.emit(BannerMsg::Hide);
+        let session = self.existing_session.take();
+
+        match action {
+            DuplicateLoginAction::Continue => self.start_session(sender).await,
+            DuplicateLoginAction::Activate => {
+                let session = if let Some(session) = session {
+                    session
+                } else {
+                    return;
+                };
+                // Only switch to a session of the user that greetd has just authenticated.
+                let authenticated = matches!(
+                    self.greetd_client.lock().await.get_auth_status(),
+                    AuthStatus::Done
+                );
+                if !authenticated || self.get_current_username().as_ref() != Some(&session.user) {
+                    warn!(
+                        session = session.id,
+                        user = session.user,
+                        "Not switching to a session of a user who isn't authenticated"
+                    );
+                    self.cancel_click_handler().await;
+                    return;
+                }
+
+                info!("Switching to existing session: {session:?}");
+                if let Err(err) = activate_session(&session).await {
+                    self.display_error(
+                        sender,
+                        "Couldn't switch to the existing session",
+                        &format!("Couldn't activate session {}: {err}", session.id),
+                    );
+                }
+                // The greeter's own login for the user isn't needed anymore.
+                self.cancel_click_handler().await;
+            }
+            DuplicateLoginAction::Cancel => {
+                info!("Not logging in a user who's already logged in");
+                self.cancel_click_handler().await;
+            }
+        }
+    }
+
     /// This function handles a greetd response as follows:
     /// - if the response indicates authentication success, start the session
     /// - if the response is an authentication message:
     ///     - for info and error messages (no input request), display/log the text and send an empty authentication response to greetd.
     ///       This allows for immediate greetd updates when using authentication procedures that don't use text input.
     ///       Also reset input mode to `None`
+    ///     - for long info messages (e.g. legal notices), wait until the user acknowledges them before responding
+    ///     - for secret input requests, send the secret if it was already entered along with the previous visible input
     ///     - for input requests (visible/secret), set the input mode accordingly and return
     /// - if the response is an error, display it and return
     ///
@@ -292,26 +1419,75 @@ impl Greeter {
         sender: &AsyncComponentSender<Self>,
         response: Response,
     ) {
+        // The pending secret is only meant for the prompt right after the visible input.
+        let pending_secret = self.pending_secret.take();
+
         match response {
             Response::Success => {
                 // Authentication was successful and the session may be started.
                 // This may happen on the first request, in which case logging in
                 // as the given user requires no authentication.
                 info!("Successfully logged in; starting session");
-                self.start_session(sender).await;
+                self.clear_crash_breadcrumb();
+                let auth_started = self.auth_started.take();
+                if let Some(auth_started) = auth_started {
+                    self.update_metrics(|metrics| metrics.auth_succeeded(auth_started.elapsed()));
+                }
+                self.audit(Outcome::Success);
+                if let Some(username) = self.get_current_username() {
+                    if self.has_active_session(&username).await {
+                        // The session is started or cancelled once the user chooses what to do.
+                        return;
+                    }
+                }
+                if !self.auth_prompted && self.config.get_announce_passwordless() {
+                    // Say that the user needs no password (eg. a kiosk user), before starting their session.
+                    let username = self.get_current_username().unwrap_or_default();
+                    info!("No password required for user '{username}'; starting session after announcing it");
+                    self.updates.set_message(fill_placeholders(
+                        &self.config.get_messages().passwordless,
+                        &[("user", &username)],
+                    ));
+                    self.delay_session_start(sender, PASSWORDLESS_DISPLAY_DELAY);
+                } else if self.report_login_duration(auth_started) {
+                    // Start the session once the login duration has been shown.
+                    self.delay_session_start(sender, LOGIN_DURATION_DISPLAY_DELAY);
+                } else {
+                    self.start_session(sender).await;
+                }
                 return;
             }
             Response::AuthMessage {
                 auth_message,
                 auth_message_type,
             } => {
+                self.auth_prompted = true;
+                // The message comes from a PAM module, so it isn't trusted.
+                let auth_message = sanitize(&auth_message);
                 match auth_message_type {
+                    AuthMessageType::Secret if pending_secret.is_some() => {
+                        // The secret was already entered along with the previous visible input.
+                        info!("Sending the secret entered earlier for: {auth_message}");
+                        let client = Arc::clone(&self.greetd_client);
+                        sender.oneshot_command(async move {
+                            let response = client
+                                .lock()
+                                .await
+                                .send_auth_response(pending_secret)
+                                .await
+                                .unwrap_or_else(|err| panic!("Failed to respond to greetd: {err}"));
+                            CommandMsg::HandleGreetdResponse(response)
+                        });
+                        return;
+                    }
                     AuthMessageType::Secret => {
                         // Greetd has requested input that should be hidden
                         // e.g.: a password
                         info!("greetd asks for a secret auth input: {auth_message}");
                         self.updates.set_input_mode(InputMode::Secret);
                         self.updates.set_input(String::new());
+                        self.updates
+                            .set_input_hint(self.config.get_prompt_hint(&auth_message).cloned());
                         self.updates
                             .set_input_prompt(auth_message.trim_end().to_string());
                         return;
@@ -321,6 +1497,8 @@ impl Greeter {
                         info!("greetd asks for a visible auth input: {auth_message}");
                         self.updates.set_input_mode(InputMode::Visible);
                         self.updates.set_input(String::new());
+                        self.updates
+                            .set_input_hint(self.config.get_prompt_hint(&auth_message).cloned());
                         self.updates
                             .set_input_prompt(auth_message.trim_end().to_string());
                         return;
@@ -329,6 +1507,14 @@ impl Greeter {
                         // Greetd has sent an info message that should be displayed
                         // e.g.: asking for a fingerprint
                         info!("greetd sent an info: {auth_message}");
+                        let auth_message = auth_message.trim_end().to_string();
+                        if auth_message.contains('\n') || auth_message.len() > LONG_INFO_MSG_LEN {
+                            // Long messages (e.g. legal notices) need time to be read, so wait until the user
+                            // acknowledges it.
+                            self.updates.set_input_mode(InputMode::Acknowledge);
+                            self.updates.set_message(auth_message);
+                            return;
+                        }
                         self.updates.set_input_mode(InputMode::None);
                         self.updates.set_message(auth_message);
                     }
@@ -336,7 +1522,7 @@ impl Greeter {
                         // Greetd has sent an error message that should be displayed and logged
                         self.updates.set_input_mode(InputMode::None);
                         // Reset outdated info message, if any
-                        self.updates.set_message(self.config.get_default_message());
+                        self.updates.set_message(self.greeting.clone());
                         self.display_error(
                             sender,
                             &capitalize(&auth_message),
@@ -349,22 +1535,45 @@ impl Greeter {
                 description,
                 error_type,
             } => {
+                let description = sanitize(&description);
+                // PAM commonly refuses root and system users (eg. through `pam_securetty`), and the error from greetd
+                // doesn't say why. It can't be told apart from a wrong password though, so only hint at it.
+                let messages = self.config.get_messages();
+                let display_text = match self.get_current_username() {
+                    Some(username) if self.sys_util.is_system_user(&username) => fill_placeholders(
+                        &messages.system_user_login_failed,
+                        &[("user", &describe_system_user(&username))],
+                    ),
+                    _ => fill_placeholders(
+                        &messages.login_failed,
+                        &[("reason", &capitalize(&description))],
+                    ),
+                };
                 // some general response error. This can be an authentication failure or a general error
                 self.display_error(
                     sender,
-                    &format!("Login failed: {}", capitalize(&description)),
+                    &display_text,
                     &format!("Error from greetd: {description}"),
                 );
 
                 // In case this is an authentication error (e.g. wrong password), the session should be cancelled.
                 if let ErrorType::AuthError = error_type {
+                    self.update_metrics(Metrics::auth_failed);
+                    self.audit(Outcome::AuthFailure);
                     self.cancel_click_handler().await
+                } else {
+                    self.update_metrics(Metrics::greetd_error);
+                    self.audit(Outcome::Error);
                 }
                 return;
             }
         }
 
-        debug!("Sending empty auth response to greetd");
+        self.send_empty_response(sender);
+    }
+
+    /// Send an empty auth response to greetd, to continue after a message that doesn't ask for input.
+    fn send_empty_response(&self, sender: &AsyncComponentSender<Self>) {
         let client = Arc::clone(&self.greetd_client);
         sender.oneshot_command(async move {
             debug!("Sending empty auth response to greetd");
@@ -378,9 +1587,77 @@ impl Greeter {
         });
     }
 
-    /// Event handler for selecting a different username in the `ComboBoxText`
+    /// Show a warning if a shutdown is scheduled with logind, or the maintenance window otherwise.
+    pub(super) async fn check_scheduled_shutdown(&mut self) {
+        let shutdown = match scheduled_shutdown().await {
+            Ok(shutdown) => shutdown,
+            Err(err) => {
+                // logind might just not be running, so this isn't worth more than a debug log.
+                debug!(error = %err, "Couldn't check for a scheduled shutdown");
+                return;
+            }
+        };
+        if shutdown == self.scheduled_shutdown {
+            return;
+        }
+
+        if let Some(shutdown) = &shutdown {
+            let message = shutdown.describe(Timestamp::now(), &self.config.get_time_formatter());
+            info!("Shutdown scheduled: {message}");
+            self.schedule_banner
+                .emit(BannerMsg::Show(BannerKind::Warning, message));
+        } else if let Some(message) = &self.config.get_maintenance().message {
+            info!("Scheduled shutdown was cancelled");
+            self.schedule_banner
+                .emit(BannerMsg::Show(BannerKind::Info, message.clone()));
+        } else {
+            info!("Scheduled shutdown was cancelled");
+            self.schedule_banner.emit(BannerMsg::Hide);
+        }
+        self.scheduled_shutdown = shutdown;
+    }
+
+    /// Warn if the system clock looks wrong, and hide the warning once it's fixed.
+    pub(super) async fn check_clock_handler(&mut self) {
+        let problem = check_clock(Timestamp::now()).await;
+        if problem == self.clock_problem {
+            return;
+        }
+        self.clock_problem = problem;
+
+        let problem = if let Some(problem) = problem {
+            problem
+        } else {
+            info!("The system clock no longer looks wrong");
+            self.clock_banner.emit(BannerMsg::Hide);
+            return;
+        };
+        let message = problem.describe(Timestamp::now(), &self.config.get_time_formatter());
+        warn!(problem = %message, "The system clock looks wrong");
+        self.notifier.notify(Level::Warning, &message);
+        if self.config.get_notifications_enabled() {
+            self.clock_banner
+                .emit(BannerMsg::Show(BannerKind::Warning, message));
+        }
+    }
+
+    /// Event handler for turning Caps Lock on or off
+    pub(super) fn caps_lock_handler(&mut self, caps_lock: bool) {
+        debug!("Caps Lock is {}", if caps_lock { "on" } else { "off" });
+        self.updates.set_caps_lock(caps_lock);
+    }
+
+    /// Event handler for a change of the keyboard layout group
+    pub(super) fn keyboard_layout_handler(&mut self, group: u32) {
+        let layout = self.keyboard_layouts.get(group as usize).cloned();
+        debug!("Keyboard layout group changed to {group}: {layout:?}");
+        self.updates.set_keyboard_layout(layout);
+    }
+
+    /// Event handler for selecting a different username in the `DropDown`
     ///
-    /// This changes the session in the combo box according to the last used session of the current user.
+    /// This changes the session in the drop-down according to the last used session of the current user, and shows
+    /// when they last logged in.
     #[instrument(skip_all)]
     pub(super) fn user_change_handler(&mut self) {
         let username = if let Some(username) = self.get_current_username() {
@@ -389,17 +1666,137 @@ impl Greeter {
             // No username found (which shouldn't happen), so we can't change the session.
             return;
         };
+        self.select_last_session(&username);
+
+        let greeting = self.config.get_user_greeting(&username);
+        if greeting != self.greeting {
+            // Only replace the old greeting, and not eg. an error from a cancelled login.
+            let showing_greeting = self.updates.message == self.greeting;
+            self.greeting = greeting;
+            if showing_greeting {
+                self.show_idle_message();
+            }
+        }
 
-        if let Some(last_session) = self.cache.get_last_session(&username) {
-            // Set the last session used by this user in the session combo box.
+        let time_formatter = self.config.get_time_formatter();
+        let last_login = self.cache.get_last_login(&username).map(|time| {
+            let elapsed = Timestamp::now().as_second() - time.as_second();
+            format!(
+                "Last logged in {} ({})",
+                time_ago(elapsed),
+                time_formatter.date_time(time)
+            )
+        });
+        self.updates.set_last_login(last_login);
+
+        let first_login_hint = self
+            .config
+            .get_first_login_hint()
+            .filter(|_| self.is_first_login(&username))
+            .map(String::from);
+        self.updates.set_first_login_hint(first_login_hint);
+    }
+
+    /// Check whether the user seems to log in for the first time, from their home directory or else from the cache.
+    fn is_first_login(&self, username: &str) -> bool {
+        home_is_empty(username).unwrap_or_else(|| !self.cache.has_logged_in(username))
+    }
+
+    /// Select the session preselected for this machine, or else the last one used by the given user, in the session
+    /// drop-down.
+    fn select_last_session(&mut self, username: &str) {
+        if let Some(HostPreselect {
+            user,
+            session: Some(session),
+            ..
+        }) = &self.host_preselect
+        {
+            if user == username {
+                // The session for this machine overrides the last used one.
+                self.updates.set_active_session_id(Some(session.clone()));
+                return;
+            }
+        }
+
+        if let Some(last_session) = self.cache.get_last_session(username) {
+            // Set the last session used by this user in the session drop-down.
             self.updates
                 .set_active_session_id(Some(last_session.to_string()));
+        } else if let Some(session) = self.config.get_user_session(username) {
+            // The user hasn't started a session yet, so use the one from the config, if any.
+            self.updates
+                .set_active_session_id(Some(session.to_string()));
+        } else if let Some(session) = self
+            .config
+            .get_default_session()
+            .and_then(|id| self.sys_util.get_session_by_id(id))
+        {
+            self.updates
+                .set_active_session_id(Some(session.to_string()));
         } else {
             // Last session not found, so skip changing the session.
             info!("Last session for user '{username}' missing");
         };
     }
 
+    /// Event handler for selecting a different user in the `DropDown` while a login attempt is in progress
+    ///
+    /// The switch is queued, and the user is asked whether to cancel the current login attempt.
+    pub(super) fn queue_user_switch(&mut self, info: UserSessInfo) {
+        let current = self.get_current_username();
+        let new = info.user_id.as_ref().map(|user| user.to_string());
+        if new == current {
+            // This happens when the drop-down is reset to the current user.
+            self.pending_user_switch = None;
+            self.switch_banner.emit(BannerMsg::Hide);
+            return;
+        }
+
+        let current = current.unwrap_or_default();
+        let new = new.unwrap_or_default();
+        info!("Asking whether to switch from user '{current}' to '{new}'");
+        self.switch_banner.emit(BannerMsg::Show(
+            BannerKind::Warning,
+            format!("Cancel the current login for {current} and switch to {new}?"),
+        ));
+        self.pending_user_switch = Some(info);
+    }
+
+    /// Event handler for answering whether to switch the user while a login attempt is in progress
+    ///
+    /// On confirmation, the current login attempt is cancelled and one is started for the new user. Otherwise, the
+    /// drop-down is reset to the current user.
+    #[instrument(skip_all)]
+    pub(super) async fn confirm_user_switch(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+        confirmed: bool,
+    ) {
+        self.switch_banner.emit(BannerMsg::Hide);
+        let info = if let Some(info) = self.pending_user_switch.take() {
+            info
+        } else {
+            return;
+        };
+
+        if confirmed {
+            self.cancel_click_handler().await;
+            self.sess_info = Some(info);
+            self.user_change_handler();
+            // The session drop-down hasn't been updated yet, so use the new user's last session.
+            if let (Some(info), Some(session)) =
+                (&mut self.sess_info, &self.updates.active_session_id)
+            {
+                info.sess_id = Some(session.as_str().into());
+            }
+            self.create_session(sender).await;
+        } else {
+            info!("Continuing the current login attempt");
+            let username = self.get_current_username();
+            self.updates.set_selected_user(username);
+        }
+    }
+
     /// Event handler for clicking the "Login" button
     ///
     /// This does one of the following, depending of the state of authentication:
@@ -410,7 +1807,15 @@ impl Greeter {
         &mut self,
         sender: &AsyncComponentSender<Self>,
         input: String,
+        next_secret: Option<String>,
     ) {
+        if self.updates.input_mode == InputMode::Acknowledge {
+            info!("Informative message acknowledged");
+            self.updates.set_input_mode(InputMode::None);
+            self.send_empty_response(sender);
+            return;
+        }
+
         // Check if a password is needed. If not, then directly start the session.
         let auth_status = self.greetd_client.lock().await.get_auth_status().clone();
         match auth_status {
@@ -421,14 +1826,65 @@ impl Greeter {
                 self.start_session(sender).await;
             }
             AuthStatus::InProgress => {
+                self.pending_secret = next_secret;
                 self.send_input(sender, input).await;
             }
+            AuthStatus::NotStarted if self.updates.awaiting_attention => {
+                // The login form is hidden, so this shouldn't happen.
+                warn!("Not logging in, since the secure attention key wasn't pressed");
+            }
+            AuthStatus::NotStarted if self.updates.notice_pending => {
+                // The login form is hidden, so this shouldn't happen.
+                warn!("Not logging in, since the legal notice wasn't accepted");
+            }
             AuthStatus::NotStarted => {
+                // A badge might have a credential for the first secret prompt.
+                self.pending_secret = next_secret;
                 self.create_session(sender).await;
             }
         };
     }
 
+    /// Event handler for scanning a badge, eg. with a barcode scanner attached as a keyboard
+    ///
+    /// The scanned user is selected, and the login attempt is started once the view shows them.
+    #[instrument(skip_all)]
+    pub(super) async fn badge_scan_handler(&mut self, scan: String) {
+        let auth_status = self.greetd_client.lock().await.get_auth_status().clone();
+        if !matches!(auth_status, AuthStatus::NotStarted) {
+            info!("Ignoring badge scanned during a login attempt");
+            return;
+        }
+        if self.updates.awaiting_attention || self.updates.notice_pending {
+            info!("Ignoring badge scanned before the login form is shown");
+            return;
+        }
+
+        let (username, credential) = self.config.get_badge_login().split_scan(&scan);
+        if username.is_empty() {
+            return;
+        }
+        info!("Badge scanned for user: {username}");
+
+        // Users that aren't listed in the drop-down (eg. from LDAP) are entered manually.
+        let listed = self
+            .sys_util
+            .get_users()
+            .values()
+            .any(|user| user == username);
+        self.updates.set_manual_user_mode(!listed);
+        if listed {
+            self.updates.set_selected_user(Some(username.to_string()));
+        } else {
+            self.updates.set_manual_username(Some(username.to_string()));
+        }
+        // The session drop-down has to be updated along with the user, since the login starts right after.
+        self.select_last_session(username);
+        self.updates.set_badge_scan(Some(BadgeScan {
+            credential: credential.map(String::from),
+        }));
+    }
+
     /// Send the entered input for logging in.
     async fn send_input(&mut self, sender: &AsyncComponentSender<Self>, input: String) {
         // Reset the password field, for convenience when the user has to re-enter a password.
@@ -465,109 +1921,113 @@ impl Greeter {
         }
     }
 
-    /// Get the currently selected session name (if available) and command.
-    fn get_current_session_info(
-        &mut self,
-        sender: &AsyncComponentSender<Self>,
-    ) -> (Option<String>, Option<SessionInfo>) {
+    /// Get how the user chose the session in the current state of the GUI.
+    fn get_session_choice(&self) -> SessionChoice {
         let info = self.sess_info.as_ref().expect("No session info set yet");
         if self.updates.manual_sess_mode {
-            debug!(
-                "Retrieved session command '{}' through manual entry",
-                info.sess_text
-            );
-            if let Some(cmd) = shlex::split(info.sess_text.as_str()) {
-                (
-                    None,
-                    Some(SessionInfo {
-                        command: cmd,
-                        sess_type: SessionType::Unknown,
-                    }),
-                )
-            } else {
-                // This must be an invalid command.
-                self.display_error(
-                    sender,
-                    "Invalid session command",
-                    &format!("Invalid session command: {}", info.sess_text),
-                );
-                (None, None)
-            }
+            SessionChoice::Manual(info.sess_text.as_str())
         } else if let Some(session) = &info.sess_id {
-            // Get the currently selected session.
-            debug!("Retrieved current session: {session}");
-            if let Some(sess_info) = self.sys_util.get_sessions().get(session.as_str()) {
-                (Some(session.to_string()), Some(sess_info.clone()))
-            } else {
-                // Shouldn't happen, unless there are no sessions available.
-                let error_msg = format!("Session '{session}' not found");
+            SessionChoice::Selected(session.as_str())
+        } else {
+            SessionChoice::None
+        }
+    }
+
+    /// Resolve the currently selected session into the command and environment to start it with.
+    fn get_current_session(
+        &mut self,
+        sender: &AsyncComponentSender<Self>,
+    ) -> Option<ResolvedSession> {
+        let choice = self.get_session_choice();
+
+        let username = self.get_current_username();
+        let env = self.config.get_user_env(username.as_deref());
+        let allowlist = self.config.get_manual_session_allowlist();
+        let resolver = SessionResolver::new(
+            self.sys_util.get_sessions(),
+            self.sys_util.get_shells(),
+            &env,
+        )
+        .with_manual_allowlist(allowlist);
+        match resolver.resolve(username.as_deref(), choice) {
+            Ok(session) => Some(session),
+            Err(err) => {
+                let error_msg = err.to_string();
                 self.display_error(sender, &error_msg, &error_msg);
-                (None, None)
+                None
             }
-        } else {
-            let username = if let Some(username) = self.get_current_username() {
-                username
+        }
+    }
+
+    /// Log how long the login took, eg. for diagnosing slow PAM modules, and show it if enabled in the config.
+    ///
+    /// This returns whether the login duration is shown.
+    fn report_login_duration(&mut self, auth_started: Option<Instant>) -> bool {
+        // Logins without any key press, eg. by clicking through, are timed from the login attempt.
+        let login_started =
+            if let Some(login_started) = self.first_key_press.take().or(auth_started) {
+                login_started
             } else {
-                // This shouldn't happen, because a session should've been created with a username.
-                unimplemented!("Trying to create session without a username");
+                return false;
             };
-            warn!("No entry found; using default login shell of user: {username}",);
-            if let Some(cmd) = self.sys_util.get_shells().get(username.as_str()) {
-                (
-                    None,
-                    Some(SessionInfo {
-                        command: cmd.clone(),
-                        sess_type: SessionType::Unknown,
-                    }),
-                )
-            } else {
-                // No login shell exists.
-                let error_msg = "No session or login shell found";
-                self.display_error(sender, error_msg, error_msg);
-                (None, None)
-            }
+        let duration = login_started.elapsed();
+        if let Some(auth_started) = auth_started {
+            info!(
+                "Login took {duration:.1?} since the first key press, of which authentication took {:.1?}",
+                auth_started.elapsed()
+            );
+        } else {
+            info!("Login took {duration:.1?} since the first key press");
+        }
+
+        if !self.config.get_show_login_duration() {
+            return false;
         }
+        let seconds = format!("{:.1}", duration.as_secs_f64());
+        self.updates.set_message(fill_placeholders(
+            &self.config.get_messages().logged_in,
+            &[("seconds", &seconds)],
+        ));
+        true
     }
 
     /// Start the session for the selected user.
-    async fn start_session(&mut self, sender: &AsyncComponentSender<Self>) {
-        // Get the session command.
-        let (session, info) = if let (session, Some(info)) = self.get_current_session_info(sender) {
-            (session, info)
+    pub(super) async fn start_session(&mut self, sender: &AsyncComponentSender<Self>) {
+        let session = if let Some(session) = self.get_current_session(sender) {
+            session
         } else {
-            // Error handling should be inside `get_current_session_info`, so simply return.
+            // Error handling should be inside `get_current_session`, so simply return.
             return;
         };
 
-        // Generate env string that will be passed to greetd when starting the session
-        let env = self.config.get_env();
-        let mut environment = Vec::with_capacity(env.len() + 1);
-        match info.sess_type {
-            SessionType::X11 => {
-                environment.push("XDG_SESSION_TYPE=x11".to_string());
-            }
-            SessionType::Wayland => {
-                environment.push("XDG_SESSION_TYPE=wayland".to_string());
-            }
-            SessionType::Unknown => {}
-        };
-        for (k, v) in env {
-            environment.push(format!("{}={}", k, v));
+        if self.dry_run {
+            info!(
+                "dry run: skip starting session with command: {:?}, env: {:?}",
+                session.command, session.env
+            );
+            self.updates.set_dry_run_request(Some(format!(
+                "Command: {}\n\nEnvironment:\n{}",
+                shlex::try_join(session.command.iter().map(String::as_str))
+                    .unwrap_or_else(|_| format!("{:?}", session.command)),
+                session.env.join("\n")
+            )));
+            self.cancel_click_handler().await;
+            return;
         }
 
         if let Some(username) = self.get_current_username() {
             self.cache.set_last_user(&username);
-            if let Some(session) = session {
-                self.cache.set_last_session(&username, &session);
+            self.cache.set_last_login(&username, Timestamp::now());
+            if let Some(name) = &session.name {
+                self.cache.set_last_session(&username, name);
             }
             debug!("Updated cache with current user: {username}");
         }
+        self.cache
+            .set_manual_modes(self.updates.manual_user_mode, self.updates.manual_sess_mode);
 
-        if !self.demo {
-            info!("Saving cache to disk");
-            if let Err(err) = self.cache.save() {
-                error!("Error saving cache to disk: {err}");
-            }
+        if let Err(err) = self.cache.save() {
+            error!(error = %err, "Error saving cache to disk");
         }
 
         // Start the session.
@@ -575,29 +2035,184 @@ impl Greeter {
             .greetd_client
             .lock()
             .await
-            .start_session(info.command, environment)
+            .start_session(session.command, session.env)
             .await
             .unwrap_or_else(|err| panic!("Failed to start session: {err}"));
 
         match response {
             Response::Success => {
                 info!("Session successfully started");
+                self.hide_osk();
                 std::process::exit(0);
             }
 
             Response::AuthMessage { .. } => unimplemented!(),
 
             Response::Error { description, .. } => {
+                let description = sanitize(&description);
                 self.cancel_click_handler().await;
+                let display_text = self.config.get_messages().session_start_failed.clone();
                 self.display_error(
                     sender,
-                    "Failed to start session",
+                    &display_text,
                     &format!("Failed to start session; error: {description}"),
                 );
             }
         }
     }
 
+    /// Show a state of the login UI in the UI tour.
+    pub(super) fn tour_step_handler(&mut self, step: TourStep) {
+        info!("UI tour: showing {step:?}");
+        self.updates.set_awaiting_attention(false);
+        self.updates.set_notice_pending(false);
+        self.updates.set_error(None);
+        self.updates.set_input(String::new());
+        self.updates.set_message(self.greeting.clone());
+
+        match step {
+            TourStep::Idle => self.updates.set_input_mode(InputMode::None),
+            TourStep::SecretPrompt => {
+                self.updates.set_input_mode(InputMode::Secret);
+                self.updates.set_input_prompt(TOUR_MSG_SECRET.to_string());
+            }
+            TourStep::VisiblePrompt => {
+                self.updates.set_input_mode(InputMode::Visible);
+                self.updates.set_input_prompt(TOUR_MSG_VISIBLE.to_string());
+            }
+            TourStep::Info => {
+                self.updates.set_input_mode(InputMode::None);
+                self.updates.set_message(TOUR_MSG_INFO.to_string());
+            }
+            TourStep::LongInfo => {
+                self.updates.set_input_mode(InputMode::Acknowledge);
+                self.updates.set_message(TOUR_MSG_LONG_INFO.to_string());
+            }
+            TourStep::Error => {
+                self.updates.set_input_mode(InputMode::None);
+                // Errors are normally cleared after a delay, but here the next step clears it.
+                self.updates.set_error(Some(TOUR_MSG_ERROR.to_string()));
+            }
+        }
+    }
+
+    /// Write the current status to the status file, if it's enabled.
+    pub(super) fn write_status(&self) {
+        let status_file = if let Some(status_file) = &self.status_file {
+            status_file
+        } else {
+            return;
+        };
+
+        // Requests to greetd hold the lock until they're answered, so don't wait for it.
+        let state = if let Ok(client) = self.greetd_client.try_lock() {
+            match client.get_auth_status() {
+                AuthStatus::NotStarted => State::Idle,
+                AuthStatus::InProgress => State::Authenticating,
+                AuthStatus::Done => State::Authenticated,
+            }
+        } else {
+            State::WaitingForGreetd
+        };
+
+        let status = Status {
+            state,
+            uptime_secs: self.started.elapsed().as_secs(),
+            last_error: self.last_error.as_deref(),
+            // Errors when talking to greetd are fatal, so the greeter is connected as long as it's running.
+            greetd_connected: !self.demo,
+        };
+        if let Err(err) = status_file.write(&status) {
+            error!(error = %err, "Couldn't write status file");
+        }
+    }
+
+    /// Record the outcome of the current login attempt in the audit log, if it's enabled.
+    fn audit(&mut self, outcome: Outcome) {
+        if self.audit_log.is_none() {
+            return;
+        }
+        let username = self.get_current_username().unwrap_or_default();
+        let session = match self.get_session_choice() {
+            SessionChoice::Manual(session) | SessionChoice::Selected(session) => {
+                Some(session.to_string())
+            }
+            SessionChoice::None => None,
+        };
+        if let Some(audit_log) = &mut self.audit_log {
+            if let Err(err) = audit_log.record(&username, session.as_deref(), outcome) {
+                error!(error = %err, "Couldn't write audit log");
+            }
+        }
+    }
+
+    /// Update the metrics, and write them to the metrics file if it's enabled.
+    pub(super) fn update_metrics(&mut self, update: impl FnOnce(&mut Metrics)) {
+        update(&mut self.metrics);
+        if let Some(metrics_file) = &self.metrics_file {
+            if let Err(err) = metrics_file.write(&self.metrics) {
+                error!(error = %err, "Couldn't write metrics file");
+            }
+        }
+    }
+
+    /// Placeholder for the entry of the current input prompt
+    pub(super) fn input_placeholder(&self) -> Option<&str> {
+        self.updates.input_hint.as_ref()?.placeholder.as_deref()
+    }
+
+    /// Tooltip for the entry of the current input prompt
+    pub(super) fn input_tooltip(&self) -> Option<&str> {
+        self.updates.input_hint.as_ref()?.tooltip.as_deref()
+    }
+
+    /// What the login UI shows in the current state
+    pub(super) fn view_state(&self) -> ViewState {
+        ViewState::new(
+            &self.updates,
+            self.config.get_combine_prompts(),
+            self.config.get_badge_login().enabled,
+            self.config.get_visibility().session_selector,
+        )
+    }
+
+    /// Whether the message label is shown, which it isn't for the greeting message if that's hidden in the config
+    pub(super) fn message_visible(&self) -> bool {
+        self.config.get_visibility().greeting || self.updates.message != self.greeting
+    }
+
+    /// Whether the current message is the greeting message with Pango markup
+    ///
+    /// Messages from greetd aren't trusted, so they're never shown as markup.
+    pub(super) fn message_is_markup(&self) -> bool {
+        self.config.get_greeting_markup() && self.updates.message == self.greeting
+    }
+
+    /// Show the current error message in a notification, or hide it if there's none.
+    pub(super) fn show_error_notification(&self) {
+        if !self.config.get_notifications_enabled() {
+            // The error has already been logged.
+            return;
+        }
+
+        // Toasts replace the error banner when using libadwaita, and they hide themselves.
+        #[cfg(feature = "libadwaita")]
+        if let Some(overlay) = &self.toast_overlay {
+            if let Some(error) = &self.updates.error {
+                // Toast titles are shown as Pango markup.
+                overlay.add_toast(adw::Toast::new(&gtk::glib::markup_escape_text(error)));
+            }
+            return;
+        }
+
+        self.error_banner
+            .emit(if let Some(error) = &self.updates.error {
+                BannerMsg::Show(BannerKind::Error, error.clone())
+            } else {
+                BannerMsg::Hide
+            });
+    }
+
     /// Show an error message to the user.
     fn display_error(
         &mut self,
@@ -606,6 +2221,8 @@ impl Greeter {
         log_text: &str,
     ) {
         self.updates.set_error(Some(display_text.to_string()));
+        self.notifier.notify(Level::Error, display_text);
+        self.last_error = Some(display_text.to_string());
         error!("{log_text}");
 
         sender.oneshot_command(async move {
@@ -615,17 +2232,13 @@ impl Greeter {
     }
 }
 
-impl Drop for Greeter {
-    fn drop(&mut self) {
-        // Cancel any created session, just to be safe.
-        let client = Arc::clone(&self.greetd_client);
-        tokio::spawn(async move {
-            client
-                .lock()
-                .await
-                .cancel_session()
-                .await
-                .expect("Couldn't cancel session on exit.")
-        });
+#[cfg(test)]
+mod tests {
+    #[test_case("authentication failed" => "Authentication failed"; "ascii")]
+    #[test_case("älter als 90 Tage" => "Älter als 90 Tage"; "multi-byte letter")]
+    #[test_case("🔒 account locked" => "🔒 account locked"; "emoji")]
+    #[test_case("" => ""; "empty")]
+    fn capitalize(string: &str) -> String {
+        super::capitalize(string)
     }
 }
@@ -8,6 +8,8 @@
 use gtk::prelude::*;
 use relm4::{gtk, RelmWidgetExt, WidgetTemplate};
 
+use super::widget::backdrop::Backdrop;
+
 /// Button that ends the greeter (eg. Reboot)
 #[relm4::widget_template(pub)]
 impl WidgetTemplate for EndButton {
@@ -15,11 +17,12 @@ impl WidgetTemplate for EndButton {
         gtk::Button {
             set_focusable: true,
             add_css_class: "destructive-action",
+            add_css_class: "regreet-end-button",
         }
     }
 }
 
-/// Label for an entry/combo box
+/// Label for an entry/drop-down
 #[relm4::widget_template(pub)]
 impl WidgetTemplate for EntryLabel {
     view! {
@@ -31,189 +34,426 @@ impl WidgetTemplate for EntryLabel {
 }
 
 /// Main UI of the greeter
+///
+/// The spacing between and around the widgets depends on the density in the config, so it's set by the greeter.
 #[relm4::widget_template(pub)]
 impl WidgetTemplate for Ui {
     view! {
         gtk::Overlay {
             /// Background image
             #[name = "background"]
-            gtk::Picture,
+            gtk::Picture {
+                set_widget_name: "regreet-background",
+            },
+
+            /// Blurred background behind the main login box, which is enabled by the greeter config
+            #[name = "backdrop"]
+            add_overlay = &Backdrop {
+                set_widget_name: "regreet-backdrop",
+                set_can_target: false,
+            },
 
-            /// Main login box
+            /// Main login box, which is positioned by the greeter config
+            #[name = "login_frame"]
             add_overlay = &gtk::Frame {
-                set_halign: gtk::Align::Center,
-                set_valign: gtk::Align::Center,
+                set_widget_name: "regreet-panel",
                 add_css_class: "background",
+                add_css_class: "regreet-panel",
 
+                #[name = "panel_grid"]
                 gtk::Grid {
-                    set_column_spacing: 15,
-                    set_margin_bottom: 15,
-                    set_margin_end: 15,
-                    set_margin_start: 15,
-                    set_margin_top: 15,
-                    set_row_spacing: 15,
                     set_width_request: 500,
 
-                    /// Widget to display messages to the user
-                    #[name = "message_label"]
-                    attach[0, 0, 3, 1] = &gtk::Label {
-                        set_margin_bottom: 15,
-
-                        // Format all messages in boldface.
-                        #[wrap(Some)]
-                        set_attributes = &gtk::pango::AttrList {
-                            insert: {
-                                let mut font_desc = gtk::pango::FontDescription::new();
-                                font_desc.set_weight(gtk::pango::Weight::Bold);
-                                gtk::pango::AttrFontDesc::new(&font_desc)
+                    /// Scrollable view for long messages, eg. legal notices from PAM
+                    #[name = "message_scroll"]
+                    attach[0, 0, 3, 1] = &gtk::ScrolledWindow {
+                        set_widget_name: "regreet-message-scroll",
+                        set_hscrollbar_policy: gtk::PolicyType::Never,
+                        set_max_content_height: 300,
+                        set_propagate_natural_height: true,
+
+                        /// Widget to display messages to the user
+                        #[name = "message_label"]
+                        gtk::Label {
+                            set_widget_name: "regreet-message",
+                            add_css_class: "regreet-message",
+                            set_wrap: true,
+                            set_wrap_mode: gtk::pango::WrapMode::WordChar,
+                            // Let messages be copied, eg. for searching for errors.
+                            set_selectable: true,
+
+                            // Format all messages in boldface.
+                            #[wrap(Some)]
+                            set_attributes = &gtk::pango::AttrList {
+                                insert: {
+                                    let mut font_desc = gtk::pango::FontDescription::new();
+                                    font_desc.set_weight(gtk::pango::Weight::Bold);
+                                    gtk::pango::AttrFontDesc::new(&font_desc)
+                                },
                             },
                         },
                     },
 
-                    #[template]
-                    attach[0, 1, 1, 1] = &EntryLabel {
-                        set_label: "User:",
-                        set_height_request: 45,
-                    },
+                    /// Legal notice from the greeter config, which has to be accepted before the login form is shown
+                    #[name = "notice_box"]
+                    attach[0, 1, 3, 1] = &gtk::Box {
+                        set_widget_name: "regreet-notice-box",
+                        add_css_class: "regreet-notice",
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_visible: false,
 
-                    /// Label for the sessions widget
-                    #[name = "session_label"]
-                    #[template]
-                    attach[0, 2, 1, 1] = &EntryLabel {
-                        set_label: "Session:",
-                        set_height_request: 45,
+                        /// Scrollable view for the notice, which has to be scrolled to the end
+                        #[name = "notice_scroll"]
+                        gtk::ScrolledWindow {
+                            set_widget_name: "regreet-notice-scroll",
+                            set_hscrollbar_policy: gtk::PolicyType::Never,
+                            set_max_content_height: 300,
+                            set_propagate_natural_height: true,
+
+                            /// Text of the notice
+                            #[name = "notice_label"]
+                            gtk::Label {
+                                set_widget_name: "regreet-notice-label",
+                                set_wrap: true,
+                                set_wrap_mode: gtk::pango::WrapMode::WordChar,
+                                set_xalign: 0.0,
+                                // This also lets the notice be scrolled with the keyboard.
+                                set_selectable: true,
+                            },
+                        },
+
+                        /// Button to accept the notice, which is enabled once it's scrolled to the end
+                        #[name = "notice_button"]
+                        gtk::Button {
+                            set_widget_name: "regreet-notice-button",
+                            set_halign: gtk::Align::End,
+                            set_focusable: true,
+                            set_label: "Accept",
+                            set_sensitive: false,
+                            add_css_class: "suggested-action",
+                        },
                     },
 
-                    /// Widget containing the usernames
-                    #[name = "usernames_box"]
-                    attach[1, 1, 1, 1] = &gtk::ComboBoxText { set_hexpand: true },
+                    /// User, session and input widgets, which are hidden until the secure attention key is pressed
+                    #[name = "login_form"]
+                    attach[0, 2, 3, 1] = &gtk::Grid {
+                        set_widget_name: "regreet-login-form",
+                        add_css_class: "regreet-login-form",
+
+                        /// Label for the user widgets
+                        #[name = "user_label"]
+                        #[template]
+                        attach[0, 0, 1, 1] = &EntryLabel {
+                            set_widget_name: "regreet-user-label",
+                            set_label: "User:",
+                            set_height_request: 45,
+                        },
 
-                    /// Widget where the user enters the username
-                    #[name = "username_entry"]
-                    attach[1, 1, 1, 1] = &gtk::Entry { set_hexpand: true },
+                        /// Label for the sessions widget
+                        #[name = "session_label"]
+                        #[template]
+                        attach[0, 1, 1, 1] = &EntryLabel {
+                            set_widget_name: "regreet-session-label",
+                            set_label: "Session:",
+                            set_height_request: 45,
+                        },
 
-                    /// Widget containing the sessions
-                    #[name = "sessions_box"]
-                    attach[1, 2, 1, 1] = &gtk::ComboBoxText,
+                        /// Widget containing the usernames
+                        #[name = "usernames_box"]
+                        attach[1, 0, 1, 1] = &gtk::DropDown {
+                            set_widget_name: "regreet-user-selector",
+                            add_css_class: "regreet-user-selector",
+                            set_hexpand: true,
+                        },
 
-                    /// Widget where the user enters the session
-                    #[name = "session_entry"]
-                    attach[1, 2, 1, 1] = &gtk::Entry,
+                        /// Widget where the user enters the username
+                        #[name = "username_entry"]
+                        attach[1, 0, 1, 1] = &gtk::Entry {
+                            set_widget_name: "regreet-user-entry",
+                            add_css_class: "regreet-user-entry",
+                            set_hexpand: true,
+                        },
 
-                    /// Label for the password widget
-                    #[name = "input_label"]
-                    #[template]
-                    attach[0, 2, 1, 1] = &EntryLabel {
-                        set_height_request: 45,
-                    },
+                        /// Widget containing the sessions
+                        #[name = "sessions_box"]
+                        attach[1, 1, 1, 1] = &gtk::DropDown {
+                            set_widget_name: "regreet-session-selector",
+                            add_css_class: "regreet-session-selector",
+                        },
 
-                    /// Widget where the user enters a secret
-                    #[name = "secret_entry"]
-                    attach[1, 2, 1, 1] = &gtk::PasswordEntry { set_show_peek_icon: true },
+                        /// Widget where the user enters the session
+                        #[name = "session_entry"]
+                        attach[1, 1, 1, 1] = &gtk::Entry {
+                            set_widget_name: "regreet-session-entry",
+                            add_css_class: "regreet-session-entry",
+                        },
 
-                    /// Widget where the user enters something visible
-                    #[name = "visible_entry"]
-                    attach[1, 2, 1, 1] = &gtk::Entry,
+                        /// Label for the password widget
+                        #[name = "input_label"]
+                        #[template]
+                        attach[0, 1, 1, 1] = &EntryLabel {
+                            set_widget_name: "regreet-input-label",
+                            set_height_request: 45,
+                        },
 
-                    /// Button to toggle manual user entry
-                    #[name = "user_toggle"]
-                    attach[2, 1, 1, 1] = &gtk::ToggleButton {
-                        set_icon_name: "document-edit-symbolic",
-                        set_tooltip_text: Some("Manually enter username"),
-                    },
+                        /// Widget where the user enters a secret
+                        #[name = "secret_entry"]
+                        attach[1, 1, 1, 1] = &gtk::PasswordEntry {
+                            set_widget_name: "regreet-secret-entry",
+                            add_css_class: "regreet-secret-entry",
+                            set_show_peek_icon: true,
+                        },
 
-                    /// Button to toggle manual session entry
-                    #[name = "sess_toggle"]
-                    attach[2, 2, 1, 1] = &gtk::ToggleButton {
-                        set_icon_name: "document-edit-symbolic",
-                        set_tooltip_text: Some("Manually enter session command"),
-                    },
+                        /// Widget where the user enters something visible
+                        #[name = "visible_entry"]
+                        attach[1, 1, 1, 1] = &gtk::Entry {
+                            set_widget_name: "regreet-visible-entry",
+                            add_css_class: "regreet-visible-entry",
+                        },
 
-                    /// Collection of action buttons (eg. Login)
-                    attach[1, 3, 2, 1] = &gtk::Box {
-                        set_halign: gtk::Align::End,
-                        set_spacing: 15,
+                        /// Label showing the active keyboard layout next to the secret entry
+                        #[name = "layout_label"]
+                        attach[2, 1, 1, 1] = &gtk::Label {
+                            set_widget_name: "regreet-layout-label",
+                            set_tooltip_text: Some("Keyboard layout"),
+                            add_css_class: "dim-label",
+                            set_visible: false,
+                        },
 
-                        /// Button to cancel password entry
-                        #[name = "cancel_button"]
-                        gtk::Button {
-                            set_focusable: true,
-                            set_label: "Cancel",
+                        /// Warning shown next to the secret entry while Caps Lock is on
+                        #[name = "caps_lock_label"]
+                        attach[3, 1, 1, 1] = &gtk::Label {
+                            set_widget_name: "regreet-caps-lock-label",
+                            set_label: "Caps Lock is on",
+                            add_css_class: "warning",
+                            add_css_class: "regreet-caps-lock-warning",
+                            set_visible: false,
                         },
 
-                        /// Button to enter the password and login
-                        #[name = "login_button"]
-                        gtk::Button {
-                            set_focusable: true,
-                            set_label: "Login",
-                            set_receives_default: true,
-                            add_css_class: "suggested-action",
+                        /// Label for the secret entered along with a visible input
+                        #[name = "combined_secret_label"]
+                        #[template]
+                        attach[0, 2, 1, 1] = &EntryLabel {
+                            set_widget_name: "regreet-combined-secret-label",
+                            set_label: "Password:",
+                            set_height_request: 45,
+                            set_visible: false,
+                        },
+
+                        /// Widget where the user enters a secret along with a visible input
+                        #[name = "combined_secret_entry"]
+                        attach[1, 2, 1, 1] = &gtk::PasswordEntry {
+                            set_widget_name: "regreet-combined-secret-entry",
+                            add_css_class: "regreet-secret-entry",
+                            set_show_peek_icon: true,
+                            set_visible: false,
+                        },
+
+                        /// Label showing the active keyboard layout next to the secret entered along with a visible input
+                        #[name = "combined_layout_label"]
+                        attach[2, 2, 1, 1] = &gtk::Label {
+                            set_widget_name: "regreet-combined-layout-label",
+                            set_tooltip_text: Some("Keyboard layout"),
+                            add_css_class: "dim-label",
+                            set_visible: false,
+                        },
+
+                        /// Warning shown next to the secret entered along with a visible input while Caps Lock is on
+                        #[name = "combined_caps_lock_label"]
+                        attach[3, 2, 1, 1] = &gtk::Label {
+                            set_widget_name: "regreet-combined-caps-lock-label",
+                            set_label: "Caps Lock is on",
+                            add_css_class: "warning",
+                            add_css_class: "regreet-caps-lock-warning",
+                            set_visible: false,
+                        },
+
+                        /// Button to toggle manual user entry
+                        #[name = "user_toggle"]
+                        attach[2, 0, 1, 1] = &gtk::ToggleButton {
+                            set_widget_name: "regreet-user-toggle",
+                            set_icon_name: "document-edit-symbolic",
+                            set_tooltip_text: Some("Manually enter username"),
+                        },
+
+                        /// Button to toggle manual session entry
+                        #[name = "sess_toggle"]
+                        attach[2, 1, 1, 1] = &gtk::ToggleButton {
+                            set_widget_name: "regreet-sess-toggle",
+                            set_icon_name: "document-edit-symbolic",
+                            set_tooltip_text: Some("Manually enter session command"),
+                        },
+
+                        /// Hint shown when the selected user seems to log in for the first time
+                        #[name = "first_login_label"]
+                        attach[0, 4, 3, 1] = &gtk::Label {
+                            set_widget_name: "regreet-first-login-label",
+                            add_css_class: "dim-label",
+                            add_css_class: "regreet-first-login-hint",
+                            set_wrap: true,
+                            set_visible: false,
+                        },
+
+                        /// Collection of action buttons (eg. Login)
+                        #[name = "action_buttons"]
+                        attach[1, 3, 2, 1] = &gtk::Box {
+                            set_widget_name: "regreet-action-buttons",
+                            set_halign: gtk::Align::End,
+
+                            /// Button to cancel password entry
+                            #[name = "cancel_button"]
+                            gtk::Button {
+                                set_widget_name: "regreet-cancel-button",
+                                add_css_class: "regreet-cancel-button",
+                                set_focusable: true,
+                                set_label: "Cancel",
+                            },
+
+                            /// Button to enter the password and login
+                            #[name = "login_button"]
+                            gtk::Button {
+                                set_widget_name: "regreet-login-button",
+                                set_focusable: true,
+                                set_label: "Login",
+                                set_receives_default: true,
+                                add_css_class: "suggested-action",
+                                add_css_class: "regreet-login-button",
+                            },
                         },
                     },
                 },
             },
 
-            /// Clock widget
+            /// Clock widget, which is positioned by the greeter config
             #[name = "clock_frame"]
             add_overlay = &gtk::Frame {
-                set_halign: gtk::Align::Center,
-                set_valign: gtk::Align::Start,
-
+                set_widget_name: "regreet-clock",
                 add_css_class: "background",
+                add_css_class: "regreet-clock",
+            },
 
-                // Make it fit cleanly onto the top edge of the screen.
-                inline_css: "
-                    border-top-right-radius: 0px;
-                    border-top-left-radius: 0px;
-                    border-top-width: 0px;
-                ",
+            /// Invisible entry capturing badge scans, eg. from a barcode scanner attached as a keyboard
+            ///
+            /// This is a password entry, since badges might contain credentials.
+            #[name = "badge_entry"]
+            add_overlay = &gtk::PasswordEntry {
+                set_widget_name: "regreet-badge-entry",
+                set_halign: gtk::Align::Start,
+                set_valign: gtk::Align::Start,
+                set_opacity: 0.0,
+                set_can_target: false,
+                set_visible: false,
+            },
+
+            /// Watermark shown in demo mode, so that screenshots can't be mistaken for a real system
+            #[name = "demo_watermark"]
+            add_overlay = &gtk::Label {
+                set_widget_name: "regreet-demo-watermark",
+                set_halign: gtk::Align::End,
+                set_valign: gtk::Align::Start,
+                set_label: "DEMO",
+                set_opacity: 0.5,
+                // Let clicks pass through to the widgets below.
+                set_can_target: false,
+                add_css_class: "demo-watermark",
+
+                #[wrap(Some)]
+                set_attributes = &gtk::pango::AttrList {
+                    insert: gtk::pango::AttrFloat::new_scale(4.0),
+                    insert: gtk::pango::AttrInt::new_weight(gtk::pango::Weight::Heavy),
+                },
             },
 
             /// Collection of widgets appearing at the bottom
+            #[name = "bottom_box"]
             add_overlay = &gtk::Box {
+                set_widget_name: "regreet-bottom",
                 set_orientation: gtk::Orientation::Vertical,
                 set_halign: gtk::Align::Center,
                 set_valign: gtk::Align::End,
-                set_margin_bottom: 15,
-                set_spacing: 15,
-
-                gtk::Frame {
-                    /// Notification bar for error messages
-                    #[name = "error_info"]
-                    gtk::InfoBar {
-                        // During init, the info bar closing animation is shown. To hide that, make
-                        // it invisible. Later, the code will permanently make it visible, so that
-                        // `InfoBar::set_revealed` will work properly with animations.
-                        set_visible: false,
-                        set_message_type: gtk::MessageType::Error,
 
-                        /// The actual error message
-                        #[name = "error_label"]
-                        gtk::Label {
-                            set_halign: gtk::Align::Center,
-                            set_margin_top: 10,
-                            set_margin_bottom: 10,
-                            set_margin_start: 10,
-                            set_margin_end: 10,
-                        },
-                    }
+                /// Banner for messages about the previous boot
+                #[name = "boot_banner_box"]
+                gtk::Box {
+                    set_widget_name: "regreet-boot-banner-box",
+                },
+
+                /// Banner for the maintenance window, or a scheduled shutdown
+                #[name = "schedule_banner_box"]
+                gtk::Box {
+                    set_widget_name: "regreet-schedule-banner-box",
+                },
+
+                /// Banner warning that the system clock looks wrong
+                #[name = "clock_banner_box"]
+                gtk::Box {
+                    set_widget_name: "regreet-clock-banner-box",
+                },
+
+                /// Banner asking whether to switch the user during a login attempt
+                #[name = "switch_banner_box"]
+                gtk::Box {
+                    set_widget_name: "regreet-switch-banner-box",
+                },
+
+                /// Banner asking whether to log in a user who's already logged in
+                #[name = "duplicate_login_banner_box"]
+                gtk::Box {
+                    set_widget_name: "regreet-duplicate-login-banner-box",
+                },
+
+                /// Banner counting down before rebooting or powering off
+                #[name = "power_banner_box"]
+                gtk::Box {
+                    set_widget_name: "regreet-power-banner-box",
+                },
+
+                /// Notification banner for error messages
+                #[name = "error_banner_box"]
+                gtk::Box {
+                    set_widget_name: "regreet-error-banner-box",
+                    add_css_class: "regreet-notification",
                 },
 
                 /// Collection of buttons that close the greeter (eg. Reboot)
+                #[name = "end_buttons_box"]
                 gtk::Box {
+                    set_widget_name: "regreet-end-buttons",
+                    add_css_class: "regreet-end-buttons",
                     set_halign: gtk::Align::Center,
                     set_homogeneous: true,
-                    set_spacing: 15,
+
+                    /// Button to suspend
+                    #[name = "suspend_button"]
+                    #[template]
+                    EndButton {
+                        set_widget_name: "regreet-suspend-button",
+                        set_label: "Suspend",
+                    },
+
+                    /// Button to hibernate
+                    #[name = "hibernate_button"]
+                    #[template]
+                    EndButton {
+                        set_widget_name: "regreet-hibernate-button",
+                        set_label: "Hibernate",
+                    },
 
                     /// Button to reboot
                     #[name = "reboot_button"]
                     #[template]
-                    EndButton { set_label: "Reboot" },
+                    EndButton {
+                        set_widget_name: "regreet-reboot-button",
+                        set_label: "Reboot",
+                    },
 
                     /// Button to power-off
                     #[name = "poweroff_button"]
                     #[template]
-                    EndButton { set_label: "Power Off" },
+                    EndButton {
+                        set_widget_name: "regreet-poweroff-button",
+                        set_label: "Power Off",
+                    },
                 },
             },
         }
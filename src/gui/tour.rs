@@ -0,0 +1,73 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Tour through the states of the login UI
+//!
+//! With `--ui-tour`, the greeter runs in demo mode and shows every state of the login UI in turn on a timer, so that
+//! they can be checked and screenshotted without going through the authentication flows by hand.
+
+use std::time::Duration;
+
+use relm4::AsyncComponentSender;
+use tokio::time::sleep;
+
+use super::{messages::CommandMsg, model::Greeter};
+
+/// How long each state is shown for
+const TOUR_STEP_DELAY: Duration = Duration::from_secs(4);
+
+pub(super) const TOUR_MSG_SECRET: &str = "Password:";
+pub(super) const TOUR_MSG_VISIBLE: &str = "Username:";
+pub(super) const TOUR_MSG_INFO: &str = "Place your finger on the fingerprint reader";
+pub(super) const TOUR_MSG_LONG_INFO: &str = "This system is for the use of authorized users only.\n\
+    Individuals using this computer system without authority, or in excess of their authority, are subject to having \
+    all of their activities on this system monitored and recorded by system personnel.\n\
+    Anyone using this system expressly consents to such monitoring.";
+pub(super) const TOUR_MSG_ERROR: &str = "Login failed: Pam_authenticate: AUTH_ERR";
+
+/// A state of the login UI
+#[derive(Clone, Copy, Debug)]
+pub enum TourStep {
+    /// No login attempt has been started.
+    Idle,
+    /// greetd asks for a secret, eg. a password.
+    SecretPrompt,
+    /// greetd asks for a visible input, eg. a username.
+    VisiblePrompt,
+    /// greetd shows a short informative message.
+    Info,
+    /// greetd shows a long informative message that needs to be acknowledged.
+    LongInfo,
+    /// An error is shown.
+    Error,
+}
+
+impl TourStep {
+    /// All states, in the order that they are shown
+    const ALL: [Self; 6] = [
+        Self::Idle,
+        Self::SecretPrompt,
+        Self::VisiblePrompt,
+        Self::Info,
+        Self::LongInfo,
+        Self::Error,
+    ];
+}
+
+/// Show every state of the login UI in turn, forever.
+pub(super) fn start_tour(sender: &AsyncComponentSender<Greeter>) {
+    sender.command(|sender, shutdown| {
+        shutdown
+            .register(async move {
+                for step in TourStep::ALL.into_iter().cycle() {
+                    if sender.send(CommandMsg::TourStep(step)).is_err() {
+                        error!("Stopping the UI tour because `send` failed");
+                        break;
+                    }
+                    sleep(TOUR_STEP_DELAY).await;
+                }
+            })
+            .drop_on_shutdown()
+    });
+}
@@ -0,0 +1,328 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! What the login UI shows in each state
+//!
+//! The view only copies these properties onto the widgets, so that the UI of every state can be tested without a
+//! display.
+
+use super::model::{InputMode, Updates};
+
+/// Widget that gets the focus when the input mode changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Focus {
+    SecretEntry,
+    VisibleEntry,
+    LoginButton,
+    BadgeEntry,
+    Notice,
+}
+
+/// Visibility and other properties of the widgets in the login UI
+#[derive(Debug, PartialEq)]
+pub(super) struct ViewState {
+    /// Whether the user, session and input widgets are shown, which they aren't until the secure attention key is
+    /// pressed and the legal notice is accepted
+    pub(super) login_form: bool,
+    /// Whether the legal notice is shown
+    pub(super) notice: bool,
+    pub(super) usernames_box: bool,
+    pub(super) username_entry: bool,
+    /// Whether the manually entered username can be edited
+    pub(super) username_entry_sensitive: bool,
+    pub(super) user_toggle_sensitive: bool,
+    pub(super) session_label: bool,
+    pub(super) sessions_box: bool,
+    pub(super) session_entry: bool,
+    pub(super) sess_toggle: bool,
+    pub(super) input_label: bool,
+    pub(super) secret_entry: bool,
+    pub(super) visible_entry: bool,
+    /// Whether the secret entry is shown along with the visible entry
+    pub(super) combined_secret: bool,
+    pub(super) layout_label: bool,
+    pub(super) combined_layout_label: bool,
+    /// Whether the warning that Caps Lock is on is shown next to the secret entry
+    pub(super) caps_lock_warning: bool,
+    pub(super) combined_caps_lock_warning: bool,
+    pub(super) cancel_button: bool,
+    pub(super) login_label: &'static str,
+    /// Whether badge scans are captured
+    pub(super) badge_entry: bool,
+    pub(super) focus: Focus,
+}
+
+impl ViewState {
+    pub(super) fn new(
+        updates: &Updates,
+        combine_prompts: bool,
+        badge_login: bool,
+        session_selector: bool,
+    ) -> Self {
+        let is_input = updates.is_input();
+        let combined_secret = combine_prompts && updates.input_mode == InputMode::Visible;
+        let has_layout = updates.keyboard_layout.is_some();
+        let login_form = !updates.awaiting_attention && !updates.notice_pending;
+        let notice = !updates.awaiting_attention && updates.notice_pending;
+        let badge_entry = badge_login && !is_input && login_form;
+        let session_selector = session_selector && !is_input;
+
+        Self {
+            login_form,
+            notice,
+            usernames_box: !updates.manual_user_mode,
+            username_entry: updates.manual_user_mode,
+            username_entry_sensitive: updates.manual_user_mode && !is_input,
+            user_toggle_sensitive: !is_input,
+            session_label: session_selector,
+            sessions_box: !updates.manual_sess_mode && session_selector,
+            session_entry: updates.manual_sess_mode && session_selector,
+            sess_toggle: session_selector,
+            input_label: is_input && updates.input_mode != InputMode::Acknowledge,
+            secret_entry: updates.input_mode == InputMode::Secret,
+            visible_entry: updates.input_mode == InputMode::Visible,
+            combined_secret,
+            layout_label: updates.input_mode == InputMode::Secret && has_layout,
+            combined_layout_label: combined_secret && has_layout,
+            caps_lock_warning: updates.input_mode == InputMode::Secret && updates.caps_lock,
+            combined_caps_lock_warning: combined_secret && updates.caps_lock,
+            cancel_button: is_input,
+            login_label: if updates.input_mode == InputMode::Acknowledge {
+                "Acknowledge"
+            } else {
+                "Login"
+            },
+            badge_entry,
+            focus: match updates.input_mode {
+                InputMode::Secret => Focus::SecretEntry,
+                InputMode::Visible => Focus::VisibleEntry,
+                InputMode::None if notice => Focus::Notice,
+                InputMode::None if badge_entry => Focus::BadgeEntry,
+                InputMode::None | InputMode::Acknowledge => Focus::LoginButton,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod ViewState {
+        use super::super::*;
+
+        fn updates(input_mode: InputMode, manual: bool, layout: Option<&str>) -> Updates {
+            Updates {
+                message: String::new(),
+                error: None,
+                input: String::new(),
+                manual_user_mode: manual,
+                manual_sess_mode: manual,
+                input_prompt: String::new(),
+                input_hint: None,
+                input_mode,
+                active_session_id: None,
+                time: String::new(),
+                monitor: None,
+                dry_run_request: None,
+                selected_user: None,
+                keyboard_layout: layout.map(String::from),
+                last_login: None,
+                manual_username: None,
+                badge_scan: None,
+                awaiting_attention: false,
+                notice_pending: false,
+                theme_period: None,
+                first_login_hint: None,
+                settings_unlocked: false,
+                panel_values: None,
+                high_contrast: false,
+                preview_config: None,
+                caps_lock: false,
+                tracker: 0,
+            }
+        }
+
+        /// The state when choosing the user and session
+        const CHOOSING: ViewState = ViewState {
+            login_form: true,
+            notice: false,
+            usernames_box: true,
+            username_entry: false,
+            username_entry_sensitive: false,
+            user_toggle_sensitive: true,
+            session_label: true,
+            sessions_box: true,
+            session_entry: false,
+            sess_toggle: true,
+            input_label: false,
+            secret_entry: false,
+            visible_entry: false,
+            combined_secret: false,
+            layout_label: false,
+            combined_layout_label: false,
+            caps_lock_warning: false,
+            combined_caps_lock_warning: false,
+            cancel_button: false,
+            login_label: "Login",
+            badge_entry: false,
+            focus: Focus::LoginButton,
+        };
+
+        /// The state when greetd asks for input, before the input itself is shown
+        const PROMPTED: ViewState = ViewState {
+            user_toggle_sensitive: false,
+            session_label: false,
+            sessions_box: false,
+            sess_toggle: false,
+            input_label: true,
+            cancel_button: true,
+            ..CHOOSING
+        };
+
+        #[test_case(InputMode::None, false, None, false, false => CHOOSING; "choosing")]
+        #[test_case(
+            InputMode::None, false, None, false, true
+            => ViewState { badge_entry: true, focus: Focus::BadgeEntry, ..CHOOSING };
+            "choosing with badge login"
+        )]
+        #[test_case(
+            InputMode::None, true, None, false, false
+            => ViewState {
+                usernames_box: false,
+                username_entry: true,
+                username_entry_sensitive: true,
+                sessions_box: false,
+                session_entry: true,
+                ..CHOOSING
+            };
+            "choosing manually"
+        )]
+        #[test_case(
+            InputMode::Secret, false, None, false, false
+            => ViewState { secret_entry: true, focus: Focus::SecretEntry, ..PROMPTED };
+            "secret"
+        )]
+        #[test_case(
+            InputMode::Secret, false, None, false, true
+            => ViewState { secret_entry: true, focus: Focus::SecretEntry, ..PROMPTED };
+            "secret with badge login"
+        )]
+        #[test_case(
+            InputMode::Secret, false, Some("us"), false, false
+            => ViewState { secret_entry: true, layout_label: true, focus: Focus::SecretEntry, ..PROMPTED };
+            "secret with layout"
+        )]
+        #[test_case(
+            InputMode::Secret, true, None, false, false
+            => ViewState {
+                usernames_box: false,
+                username_entry: true,
+                secret_entry: true,
+                focus: Focus::SecretEntry,
+                ..PROMPTED
+            };
+            "secret after manual entry"
+        )]
+        #[test_case(
+            InputMode::Visible, false, Some("us"), false, false
+            => ViewState { visible_entry: true, focus: Focus::VisibleEntry, ..PROMPTED };
+            "visible"
+        )]
+        #[test_case(
+            InputMode::Visible, false, Some("us"), true, false
+            => ViewState {
+                visible_entry: true,
+                combined_secret: true,
+                combined_layout_label: true,
+                focus: Focus::VisibleEntry,
+                ..PROMPTED
+            };
+            "visible with combined secret"
+        )]
+        #[test_case(
+            InputMode::Acknowledge, false, Some("us"), true, false
+            => ViewState { input_label: false, login_label: "Acknowledge", ..PROMPTED };
+            "acknowledge"
+        )]
+        fn new(
+            input_mode: InputMode,
+            manual: bool,
+            layout: Option<&str>,
+            combine_prompts: bool,
+            badge_login: bool,
+        ) -> ViewState {
+            ViewState::new(
+                &updates(input_mode, manual, layout),
+                combine_prompts,
+                badge_login,
+                true,
+            )
+        }
+
+        #[test_case(
+            InputMode::None
+            => ViewState { session_label: false, sessions_box: false, sess_toggle: false, ..CHOOSING };
+            "choosing"
+        )]
+        #[test_case(
+            InputMode::Secret
+            => ViewState { secret_entry: true, focus: Focus::SecretEntry, ..PROMPTED };
+            "secret"
+        )]
+        fn without_session_selector(input_mode: InputMode) -> ViewState {
+            ViewState::new(&updates(input_mode, false, None), false, false, false)
+        }
+
+        #[test_case(true, false, false => ViewState { login_form: false, ..CHOOSING }; "awaiting attention")]
+        #[test_case(
+            true, false, true
+            => ViewState { login_form: false, ..CHOOSING };
+            "awaiting attention with badge login"
+        )]
+        #[test_case(
+            true, true, false
+            => ViewState { login_form: false, ..CHOOSING };
+            "awaiting attention before notice"
+        )]
+        #[test_case(
+            false, true, true
+            => ViewState { login_form: false, notice: true, focus: Focus::Notice, ..CHOOSING };
+            "notice pending with badge login"
+        )]
+        fn gated(awaiting_attention: bool, notice_pending: bool, badge_login: bool) -> ViewState {
+            let mut updates = updates(InputMode::None, false, None);
+            updates.awaiting_attention = awaiting_attention;
+            updates.notice_pending = notice_pending;
+            ViewState::new(&updates, false, badge_login, true)
+        }
+
+        #[test_case(InputMode::None, false => CHOOSING; "choosing")]
+        #[test_case(
+            InputMode::Secret, false
+            => ViewState { secret_entry: true, caps_lock_warning: true, focus: Focus::SecretEntry, ..PROMPTED };
+            "secret"
+        )]
+        #[test_case(
+            InputMode::Visible, false
+            => ViewState { visible_entry: true, focus: Focus::VisibleEntry, ..PROMPTED };
+            "visible"
+        )]
+        #[test_case(
+            InputMode::Visible, true
+            => ViewState {
+                visible_entry: true,
+                combined_secret: true,
+                combined_caps_lock_warning: true,
+                focus: Focus::VisibleEntry,
+                ..PROMPTED
+            };
+            "visible with combined secret"
+        )]
+        fn caps_lock(input_mode: InputMode, combine_prompts: bool) -> ViewState {
+            let mut updates = updates(input_mode, false, None);
+            updates.caps_lock = true;
+            ViewState::new(&updates, combine_prompts, false, true)
+        }
+    }
+}
@@ -0,0 +1,159 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A widget that blurs and darkens the background behind another widget.
+//!
+//! GTK's CSS doesn't support `backdrop-filter`, so this draws a blurred copy of the background, clipped to the bounds
+//! of the target widget. It has to be stacked between the background and the target, and cover the background exactly.
+
+use relm4::gtk::{self, glib, prelude::*, subclass::prelude::*};
+
+/// Default styling for widgets with a backdrop, which makes their background translucent to show the backdrop
+pub const BACKDROP_CSS: &str = "
+    .regreet-backdrop-target {
+        background-color: alpha(@theme_bg_color, 0.7);
+    }
+";
+
+mod imp {
+    use std::cell::{Cell, RefCell};
+
+    use relm4::gtk::{self, gdk, glib, graphene, prelude::*, subclass::prelude::*};
+
+    #[derive(Default)]
+    pub struct Backdrop {
+        /// Contents of the background widget
+        pub(super) source: RefCell<Option<gtk::WidgetPaintable>>,
+        /// Widget whose bounds are blurred
+        pub(super) target: RefCell<Option<gtk::Widget>>,
+        /// Contents of the target, which is only kept to be notified when the target changes
+        pub(super) target_paintable: RefCell<Option<gtk::WidgetPaintable>>,
+        /// Blur radius in pixels
+        pub(super) blur: Cell<f64>,
+        /// Opacity of the black drawn over the blurred background
+        pub(super) darken: Cell<f32>,
+        /// Bounds of the target when last drawn, as x, y, width and height
+        pub(super) last_bounds: Cell<Option<(f32, f32, f32, f32)>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Backdrop {
+        const NAME: &'static str = "ReGreetBackdrop";
+        type Type = super::Backdrop;
+        type ParentType = gtk::Widget;
+    }
+
+    impl ObjectImpl for Backdrop {}
+
+    impl WidgetImpl for Backdrop {
+        fn snapshot(&self, snapshot: &gtk::Snapshot) {
+            let widget = self.obj();
+            let source = self.source.borrow();
+            let target = self.target.borrow();
+            let (source, target) = if let (Some(source), Some(target)) = (&*source, &*target) {
+                (source, target)
+            } else {
+                return;
+            };
+            let bounds = if let Some(bounds) = target.compute_bounds(&*widget) {
+                bounds
+            } else {
+                return;
+            };
+            self.last_bounds.set(Some(rect_tuple(&bounds)));
+
+            snapshot.push_clip(&bounds);
+            let blur = self.blur.get();
+            if blur > 0.0 {
+                snapshot.push_blur(blur);
+                // Only blur what's needed for the clipped area, since blurring is expensive.
+                let margin = 3.0 * blur as f32;
+                snapshot.push_clip(&bounds.inset_r(-margin, -margin));
+            }
+            source.snapshot(snapshot, widget.width().into(), widget.height().into());
+            if blur > 0.0 {
+                snapshot.pop();
+                snapshot.pop();
+            }
+            snapshot.append_color(&gdk::RGBA::new(0.0, 0.0, 0.0, self.darken.get()), &bounds);
+            snapshot.pop();
+        }
+    }
+
+    /// Convert a rectangle into a comparable tuple.
+    pub(super) fn rect_tuple(rect: &graphene::Rect) -> (f32, f32, f32, f32) {
+        (rect.x(), rect.y(), rect.width(), rect.height())
+    }
+}
+
+glib::wrapper! {
+    pub struct Backdrop(ObjectSubclass<imp::Backdrop>)
+        @extends gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for Backdrop {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl Backdrop {
+    /// Blur the background behind the target with the given radius, and darken it with the given opacity of black.
+    pub fn blur_behind(
+        &self,
+        background: &impl IsA<gtk::Widget>,
+        target: &impl IsA<gtk::Widget>,
+        blur: f64,
+        darken: f32,
+    ) {
+        let imp = self.imp();
+        imp.blur.set(blur.max(0.0));
+        imp.darken.set(darken.clamp(0.0, 1.0));
+        imp.target.replace(Some(target.clone().upcast()));
+
+        // Redraw when the background changes, eg. when it's animated.
+        let source = gtk::WidgetPaintable::new(Some(background));
+        let weak = self.downgrade();
+        source.connect_invalidate_contents(move |_| {
+            if let Some(backdrop) = weak.upgrade() {
+                backdrop.queue_draw();
+            }
+        });
+        imp.source.replace(Some(source));
+
+        // Redraw when the target moves or changes its size, eg. when a prompt is shown. Changes of its contents or
+        // size are a cheap hint for that, unlike checking on every frame, which would keep an idle greeter busy.
+        let target_paintable = gtk::WidgetPaintable::new(Some(target));
+        let weak = self.downgrade();
+        target_paintable.connect_invalidate_contents(move |_| {
+            if let Some(backdrop) = weak.upgrade() {
+                backdrop.redraw_if_moved();
+            }
+        });
+        let weak = self.downgrade();
+        target_paintable.connect_invalidate_size(move |_| {
+            if let Some(backdrop) = weak.upgrade() {
+                backdrop.redraw_if_moved();
+            }
+        });
+        imp.target_paintable.replace(Some(target_paintable));
+
+        self.queue_draw();
+    }
+
+    /// Redraw if the bounds of the target changed since it was last drawn.
+    fn redraw_if_moved(&self) {
+        let imp = self.imp();
+        let bounds = imp
+            .target
+            .borrow()
+            .as_ref()
+            .and_then(|target| target.compute_bounds(self))
+            .map(|bounds| imp::rect_tuple(&bounds));
+        if bounds != imp.last_bounds.get() {
+            self.queue_draw();
+        }
+    }
+}
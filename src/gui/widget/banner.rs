@@ -0,0 +1,143 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A banner that slides in to show a message, with optional action buttons.
+
+use relm4::{gtk::prelude::*, prelude::*};
+
+/// Default styling for banners, which mimics the deprecated `gtk::InfoBar`
+pub const BANNER_CSS: &str = "
+    .regreet-banner.info {
+        background-color: @theme_selected_bg_color;
+        color: @theme_selected_fg_color;
+    }
+    .regreet-banner.warning {
+        background-color: @warning_color;
+        color: white;
+    }
+    .regreet-banner.error {
+        background-color: @error_color;
+        color: white;
+    }
+";
+
+/// The kind of message shown in a banner, analogous to `gtk::MessageType`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BannerKind {
+    Info,
+    Warning,
+    Error,
+}
+
+impl BannerKind {
+    /// The CSS class used to style the banner.
+    fn css_class(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BannerMsg {
+    /// Reveal the banner with the given message.
+    Show(BannerKind, String),
+    /// Hide the banner.
+    Hide,
+}
+
+#[derive(Debug)]
+pub enum BannerOutput {
+    /// The action button at this index (in the order given at init) was clicked.
+    Action(usize),
+}
+
+#[derive(Debug)]
+pub struct MessageBanner {
+    kind: BannerKind,
+    /// The message is kept even when hidden, so that it stays visible while the banner slides away.
+    text: String,
+    revealed: bool,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for MessageBanner {
+    /// Labels of the action buttons
+    type Init = Vec<String>;
+    type Input = BannerMsg;
+    type Output = BannerOutput;
+
+    view! {
+        gtk::Revealer {
+            set_transition_type: gtk::RevealerTransitionType::SlideUp,
+            #[watch]
+            set_reveal_child: model.revealed,
+
+            gtk::Frame {
+                #[watch]
+                set_css_classes: &["regreet-banner", model.kind.css_class()],
+
+                gtk::Box {
+                    set_spacing: 10,
+                    set_margin_top: 10,
+                    set_margin_bottom: 10,
+                    set_margin_start: 10,
+                    set_margin_end: 10,
+
+                    gtk::Label {
+                        set_halign: gtk::Align::Center,
+                        set_hexpand: true,
+                        #[watch]
+                        set_label: &model.text,
+                    },
+
+                    #[name = "buttons"]
+                    gtk::Box {
+                        set_spacing: 10,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        actions: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Self {
+            kind: BannerKind::Info,
+            text: String::new(),
+            revealed: false,
+        };
+
+        let widgets = view_output!();
+
+        for (index, label) in actions.iter().enumerate() {
+            let button = gtk::Button::with_label(label);
+            let sender = sender.clone();
+            button.connect_clicked(move |_| {
+                if sender.output(BannerOutput::Action(index)).is_err() {
+                    error!("Couldn't send the banner action, since its receiver was dropped");
+                }
+            });
+            widgets.buttons.append(&button);
+        }
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, _: ComponentSender<Self>) {
+        match msg {
+            BannerMsg::Show(kind, text) => {
+                self.kind = kind;
+                self.text = text;
+                self.revealed = true;
+            }
+            BannerMsg::Hide => self.revealed = false,
+        }
+    }
+}
@@ -14,13 +14,64 @@ use serde::{
 };
 use tokio::time::sleep;
 
+/// Where the clock is shown on the screen
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClockPosition {
+    TopLeft,
+    #[default]
+    Top,
+    TopRight,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl ClockPosition {
+    /// Get the horizontal alignment of the clock.
+    pub fn halign(self) -> gtk::Align {
+        match self {
+            Self::TopLeft | Self::BottomLeft => gtk::Align::Start,
+            Self::Top | Self::Bottom => gtk::Align::Center,
+            Self::TopRight | Self::BottomRight => gtk::Align::End,
+        }
+    }
+
+    /// Get the vertical alignment of the clock.
+    pub fn valign(self) -> gtk::Align {
+        match self {
+            Self::TopLeft | Self::Top | Self::TopRight => gtk::Align::Start,
+            Self::BottomLeft | Self::Bottom | Self::BottomRight => gtk::Align::End,
+        }
+    }
+
+    /// Get the CSS that makes the clock's frame fit cleanly onto the edge of the screen.
+    pub fn frame_css(self) -> &'static str {
+        if self.valign() == gtk::Align::Start {
+            "border-top-right-radius: 0px; border-top-left-radius: 0px; border-top-width: 0px;"
+        } else {
+            "border-bottom-right-radius: 0px; border-bottom-left-radius: 0px; border-bottom-width: 0px;"
+        }
+    }
+}
+
 #[derive(Deserialize, Clone)]
 pub struct ClockConfig {
+    /// Whether the clock is shown
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+
+    /// Where the clock is shown on the screen
+    #[serde(default)]
+    pub position: ClockPosition,
+
     /// A [strftime][fmt] argument
     ///
+    /// If [`None`], the greeter fills in the weekday and its format of times of day.
+    ///
     /// [fmt]: jiff::fmt::strtime
-    #[serde(alias = "fmt", default = "weekday_and_24h_time")]
-    pub format: String,
+    #[serde(alias = "fmt", default)]
+    pub format: Option<String>,
 
     /// Amount of time between the clock's text updates
     #[serde(
@@ -41,6 +92,10 @@ pub struct ClockConfig {
     pub label_width: u32,
 }
 
+const fn enabled() -> bool {
+    true
+}
+
 fn weekday_and_24h_time() -> String {
     "%a %H:%M".into()
 }
@@ -60,7 +115,9 @@ const fn label_width() -> u32 {
 impl Default for ClockConfig {
     fn default() -> Self {
         Self {
-            format: weekday_and_24h_time(),
+            enabled: enabled(),
+            position: ClockPosition::default(),
+            format: None,
             resolution: half_second(),
             timezone: system_tz(),
             label_width: label_width(),
@@ -85,7 +142,7 @@ where
             E: de::Error,
         {
             Ok(TimeZone::get(time_zone_name).unwrap_or_else(|e| {
-                error!("Invalid timezone '{time_zone_name}' in the config: {e}");
+                error!(timezone = time_zone_name, error = %e, "Invalid timezone in the config");
                 TimeZone::system()
             }))
         }
@@ -131,27 +188,33 @@ impl Component for Clock {
             resolution,
             timezone,
             label_width,
+            enabled,
+            // This is applied to the clock's frame by the greeter.
+            position: _,
         }: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        sender.command(move |sender, shutdown| {
-            shutdown
-                .register(async move {
-                    loop {
-                        if sender.send(Tick).is_err() {
-                            error!("No longer updating the clock widget because `send` failed");
-                            break;
+        // The frame of a disabled clock is hidden by the greeter, so don't wake up to update it.
+        if enabled {
+            sender.command(move |sender, shutdown| {
+                shutdown
+                    .register(async move {
+                        loop {
+                            if sender.send(Tick).is_err() {
+                                error!("No longer updating the clock widget because `send` failed");
+                                break;
+                            }
+                            sleep(resolution).await;
                         }
-                        sleep(resolution).await;
-                    }
-                })
-                .drop_on_shutdown()
-        });
+                    })
+                    .drop_on_shutdown()
+            });
+        }
 
         let model = Self {
             current_time: String::new(),
-            format,
+            format: format.unwrap_or_else(weekday_and_24h_time),
             timezone,
         };
 
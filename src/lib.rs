@@ -0,0 +1,43 @@
+// SPDX-FileCopyrightText: 2022 Harish Rajagopal <harish.rajagopals@gmail.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Clean and customizable greeter for greetd
+//!
+//! The greeter binary lives in `main.rs`. Everything else is exposed as a library, so that the parsers run at boot can
+//! be benchmarked and fuzzed outside the GUI.
+
+pub mod admin;
+pub mod audit;
+pub mod cache;
+pub mod client;
+pub mod config;
+pub mod constants;
+pub mod demo;
+pub mod doctor;
+pub mod fallback;
+pub mod gui;
+pub mod logind;
+pub mod maintenance;
+pub mod metrics;
+pub mod notify;
+pub mod sanitize;
+pub mod session;
+pub mod status;
+pub mod sysutil;
+#[cfg(test)]
+mod testutil;
+pub mod time_format;
+pub mod timedate;
+mod tomlutils;
+
+#[macro_use]
+extern crate tracing;
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate const_format;
+
+#[cfg(test)]
+#[macro_use]
+extern crate test_case;
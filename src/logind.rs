@@ -0,0 +1,224 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Queries to systemd-logind over the system bus
+
+use std::collections::HashMap;
+
+use jiff::{SignedDuration, Timestamp};
+use relm4::gtk::{
+    gio,
+    glib::{self, variant::ObjectPath, ToVariant, Variant, VariantTy},
+};
+
+use crate::time_format::TimeFormatter;
+
+/// Name of the logind D-Bus service
+const LOGIND_NAME: &str = "org.freedesktop.login1";
+/// Object path of the logind manager
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+/// Interface of the logind manager
+const LOGIND_MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+/// Interface of logind sessions
+const LOGIND_SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+/// Time to wait for logind to respond
+const LOGIND_TIMEOUT_MS: i32 = 1000;
+/// Scheduled shutdowns closer than this only show the time, and others the date too
+const SHUTDOWN_DATE_THRESHOLD: SignedDuration = SignedDuration::from_hours(20);
+
+#[derive(thiserror::Error, Debug)]
+pub enum LogindError {
+    #[error("D-Bus error: {0}")]
+    DBus(#[from] glib::Error),
+    #[error("Unexpected reply from logind")]
+    UnexpectedReply,
+}
+
+/// A session of a user that's registered with logind
+#[derive(Debug)]
+pub struct UserSession {
+    pub id: String,
+    /// Name of the user that the session belongs to
+    pub user: String,
+    /// Object path of the session
+    pub path: String,
+    /// Seat of the session, which is empty for remote sessions
+    pub seat: String,
+    /// TTY of the session, which is empty for graphical sessions
+    pub tty: String,
+}
+
+impl UserSession {
+    /// Describe where the session is, eg. "seat0/tty2".
+    pub fn location(&self) -> String {
+        let parts: Vec<_> = [self.seat.as_str(), self.tty.as_str()]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.is_empty() {
+            format!("session {}", self.id)
+        } else {
+            parts.join("/")
+        }
+    }
+}
+
+/// Get the active sessions of the given user.
+///
+/// Sessions that are closing (eg. processes lingering after a logout) and non-user sessions (eg. greeters) are left
+/// out.
+pub async fn user_sessions(username: &str) -> Result<Vec<UserSession>, LogindError> {
+    let connection = gio::bus_get_future(gio::BusType::System).await?;
+    let reply = connection
+        .call_future(
+            Some(LOGIND_NAME),
+            LOGIND_PATH,
+            LOGIND_MANAGER_INTERFACE,
+            "ListSessions",
+            None,
+            Some(VariantTy::new("(a(susso))").expect("Invalid variant type")),
+            gio::DBusCallFlags::NONE,
+            LOGIND_TIMEOUT_MS,
+        )
+        .await?;
+    let (sessions,): (Vec<(String, u32, String, String, ObjectPath)>,) =
+        reply.get().ok_or(LogindError::UnexpectedReply)?;
+
+    let mut user_sessions = Vec::new();
+    for (id, _, user, seat, path) in sessions {
+        if user != username {
+            continue;
+        }
+
+        let reply = connection
+            .call_future(
+                Some(LOGIND_NAME),
+                path.as_str(),
+                "org.freedesktop.DBus.Properties",
+                "GetAll",
+                Some(&(LOGIND_SESSION_INTERFACE,).to_variant()),
+                Some(VariantTy::new("(a{sv})").expect("Invalid variant type")),
+                gio::DBusCallFlags::NONE,
+                LOGIND_TIMEOUT_MS,
+            )
+            .await?;
+        let (properties,): (HashMap<String, Variant>,) =
+            reply.get().ok_or(LogindError::UnexpectedReply)?;
+        let property = |name: &str| {
+            properties
+                .get(name)
+                .and_then(Variant::get::<String>)
+                .unwrap_or_default()
+        };
+
+        if property("Class") != "user" || property("State") == "closing" {
+            continue;
+        }
+        user_sessions.push(UserSession {
+            id,
+            user,
+            path: path.as_str().to_string(),
+            seat,
+            tty: property("TTY"),
+        });
+    }
+    Ok(user_sessions)
+}
+
+/// Bring the given session to the foreground, switching to its VT if needed.
+///
+/// logind might need a polkit rule to allow the greeter user to do this.
+pub async fn activate_session(session: &UserSession) -> Result<(), LogindError> {
+    let connection = gio::bus_get_future(gio::BusType::System).await?;
+    connection
+        .call_future(
+            Some(LOGIND_NAME),
+            &session.path,
+            LOGIND_SESSION_INTERFACE,
+            "Activate",
+            None,
+            None,
+            gio::DBusCallFlags::NONE,
+            LOGIND_TIMEOUT_MS,
+        )
+        .await?;
+    Ok(())
+}
+
+/// A shutdown scheduled with logind, eg. by `shutdown -r +30`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduledShutdown {
+    /// Kind of shutdown, eg. "reboot" or "poweroff"
+    pub kind: String,
+    /// When the shutdown happens
+    pub time: Timestamp,
+}
+
+impl ScheduledShutdown {
+    /// Describe the shutdown for the user, eg. "This machine will reboot at 03:00".
+    pub fn describe(&self, now: Timestamp, time_formatter: &TimeFormatter) -> String {
+        // Dry runs (eg. `shutdown -k`) only warn the users, but they're meant to look like the real thing.
+        let action = match self.kind.trim_start_matches("dry-") {
+            "reboot" | "kexec" | "soft-reboot" => "reboot",
+            "poweroff" => "power off",
+            "halt" => "halt",
+            _ => "shut down",
+        };
+        let time = if self.time.duration_since(now) < SHUTDOWN_DATE_THRESHOLD {
+            time_formatter.time(self.time)
+        } else {
+            time_formatter.date_time(self.time)
+        };
+        format!("This machine will {action} at {time}")
+    }
+}
+
+/// Get the shutdown scheduled with logind, if any.
+pub async fn scheduled_shutdown() -> Result<Option<ScheduledShutdown>, LogindError> {
+    let connection = gio::bus_get_future(gio::BusType::System).await?;
+    let reply = connection
+        .call_future(
+            Some(LOGIND_NAME),
+            LOGIND_PATH,
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            Some(&(LOGIND_MANAGER_INTERFACE, "ScheduledShutdown").to_variant()),
+            Some(VariantTy::new("(v)").expect("Invalid variant type")),
+            gio::DBusCallFlags::NONE,
+            LOGIND_TIMEOUT_MS,
+        )
+        .await?;
+    let (property,): (Variant,) = reply.get().ok_or(LogindError::UnexpectedReply)?;
+    let (kind, usec): (String, u64) = property.get().ok_or(LogindError::UnexpectedReply)?;
+    if kind.is_empty() || usec == 0 {
+        return Ok(None);
+    }
+    let usec = i64::try_from(usec).map_err(|_| LogindError::UnexpectedReply)?;
+    let time = Timestamp::from_microsecond(usec).map_err(|_| LogindError::UnexpectedReply)?;
+    Ok(Some(ScheduledShutdown { kind, time }))
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod ScheduledShutdown {
+        use jiff::tz::TimeZone;
+
+        use super::super::*;
+        use crate::time_format::HourCycle;
+
+        #[test_case("reboot", 30 * 60 => "This machine will reboot at 00:30"; "reboot soon")]
+        #[test_case("dry-poweroff", 60 => "This machine will power off at 00:01"; "dry run")]
+        #[test_case("halt", 2 * 24 * 3600 => "This machine will halt at 1970-01-03 00:00"; "halt in two days")]
+        #[test_case("unknown", 60 => "This machine will shut down at 00:01"; "unknown kind")]
+        fn describe(kind: &str, secs: i64) -> String {
+            let shutdown = ScheduledShutdown {
+                kind: kind.to_string(),
+                time: Timestamp::from_second(secs).unwrap(),
+            };
+            let time_formatter = TimeFormatter::new(None, HourCycle::H24, TimeZone::UTC);
+            shutdown.describe(Timestamp::UNIX_EPOCH, &time_formatter)
+        }
+    }
+}
@@ -2,42 +2,46 @@
 //
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-mod cache;
-mod client;
-mod config;
-mod constants;
-mod gui;
-mod sysutil;
-mod tomlutils;
-
+use std::env;
 use std::fs::{create_dir_all, OpenOptions};
-use std::io::{Result as IoResult, Write};
+use std::io::{IsTerminal, Result as IoResult, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use file_rotate::{compression::Compression, suffix::AppendCount, ContentLimit, FileRotate};
-use tracing::subscriber::set_global_default;
+use jiff::{fmt::strtime, tz::TimeZone, Timestamp, Zoned};
+use tracing::{
+    field::{Field, Visit},
+    subscriber::set_global_default,
+    Event, Subscriber,
+};
 use tracing_appender::{non_blocking, non_blocking::WorkerGuard};
 use tracing_subscriber::{
-    filter::LevelFilter, fmt::layer, fmt::time::OffsetTime, layer::SubscriberExt,
+    filter::LevelFilter,
+    fmt::format::{DefaultFields, Writer},
+    fmt::layer,
+    fmt::time::FormatTime,
+    fmt::FormatFields,
+    layer::{Context, Layer, SubscriberExt},
+    registry,
 };
 
-use crate::constants::{APP_ID, CONFIG_PATH, CSS_PATH, LOG_PATH};
-use crate::gui::{Greeter, GreeterInit};
-
-#[macro_use]
-extern crate tracing;
-#[macro_use]
-extern crate lazy_static;
-#[macro_use]
-extern crate const_format;
-
-#[cfg(test)]
-#[macro_use]
-extern crate test_case;
+use regreet::config::{Config, ConfigOverride};
+use regreet::constants::{
+    APP_ID, CACHE_PATH, CONFIG_PATH, CRASH_BREADCRUMB_PATH, CSS_PATH, LOG_PATH,
+};
+use regreet::demo::DemoFlow;
+use regreet::doctor;
+use regreet::fallback;
+use regreet::gui::{Greeter, GreeterInit};
+use regreet::maintenance;
+use regreet::sysutil::SysUtil;
 
 const MAX_LOG_FILES: usize = 3;
 const MAX_LOG_SIZE: usize = 1024 * 1024;
+/// Format of the timestamps in the logs, which is RFC 3339
+const LOG_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f%:z";
 
 #[derive(Clone, Debug, ValueEnum)]
 enum LogLevel {
@@ -68,28 +72,365 @@ struct Args {
     #[arg(short, long, value_name = "PATH", default_value = CONFIG_PATH)]
     config: PathBuf,
 
+    /// Override a value in the config file, eg. `-o background.fit=Cover` (can be repeated)
+    ///
+    /// Values are parsed as TOML, but strings don't need quotes.
+    #[arg(short = 'o', long = "override", value_name = "SECTION.KEY=VALUE")]
+    overrides: Vec<ConfigOverride>,
+
     /// The path to the custom CSS stylesheet
     #[arg(short, long, value_name = "PATH", default_value = CSS_PATH)]
     style: PathBuf,
 
-    /// Run in demo mode
+    /// Run in demo mode, using the given authentication flow
+    #[arg(
+        long,
+        value_name = "FLOW",
+        num_args = 0..=1,
+        default_missing_value = "password-otp"
+    )]
+    demo: Option<DemoFlow>,
+
+    /// Show the session command and environment instead of starting the session
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Show every state of the login UI in turn, for manual testing and screenshots (implies demo mode)
     #[arg(long)]
-    demo: bool,
+    ui_tour: bool,
+
+    /// Print the tree of CSS nodes of the greeter's window, for writing themes, then exit (implies demo mode)
+    ///
+    /// Each node is shown as a CSS selector, eg. `button#regreet-login-button.regreet-login-button`.
+    #[arg(long)]
+    dump_css_nodes: bool,
+
+    /// Open the GTK inspector along with the greeter, like `GTK_DEBUG=interactive` (implies demo mode)
+    ///
+    /// This is useful when the environment that greetd starts the greeter with can't be changed.
+    #[arg(long)]
+    inspector: bool,
+
+    /// Don't write any files, eg. the log file or the cache, on immutable systems
+    ///
+    /// Logs are written to stdout instead, and the cache is only kept for the current run. The status file, the
+    /// metrics, the audit log and the crash breadcrumb aren't written either.
+    #[arg(long)]
+    read_only: bool,
+
+    /// Turn off the background, animations and the clock, and use a single thread for background tasks
+    ///
+    /// This is meant for old hardware like thin clients, where the greeter feels sluggish otherwise.
+    #[arg(long)]
+    low_resource: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Remove old rotated logs, leftover temporary files and an oversized cache, then exit
+    ///
+    /// This is meant to be run periodically on long-running machines, eg. by a systemd timer.
+    Clean,
+    /// Check the config file for problems, then exit with an error if there are any
+    ///
+    /// This is meant to be run after changing the config, before rebooting into the greeter with it.
+    CheckConfig,
+    /// Check whether the greeter can run, eg. to find out why it shows a blank screen, then exit with an error if not
+    ///
+    /// This should be run as the user that greetd runs the greeter as, inside a compositor, so that the checks match
+    /// the greeter's environment.
+    Doctor,
+    /// List the sessions that the greeter would show, along with the desktop file that each one was read from, then
+    /// exit
+    ListSessions,
 }
 
 fn main() {
     let args = Args::parse();
+    match args.command {
+        Some(Command::Clean) => {
+            clean(&args);
+            return;
+        }
+        Some(Command::CheckConfig) => {
+            check_config(&args);
+            return;
+        }
+        Some(Command::Doctor) => {
+            doctor(&args);
+            return;
+        }
+        Some(Command::ListSessions) => {
+            list_sessions(&args);
+            return;
+        }
+        None => (),
+    }
+
+    // Logging needs the config, so its warnings are logged once logging is set up, and shown in the greeter. The
+    // environment is set before logging starts its threads.
+    let early_events = EarlyEvents::default();
+    let (config, timezone) =
+        tracing::subscriber::with_default(registry().with(early_events.clone()), || {
+            let config = Config::new(&args.config, &args.overrides);
+            setup_env(&config);
+            let timezone = log_timezone(config.get_log_timezone());
+            (config, timezone)
+        });
     // Keep the guard alive till the end of the function, since logging depends on this.
-    let _guard = init_logging(&args.logs, &args.log_level, args.verbose);
+    let guards = init_logging(
+        &args.logs,
+        &args.log_level,
+        args.verbose,
+        args.read_only,
+        timezone,
+    );
+    let warnings = early_events.replay();
+
+    setup_runtime(&config, args.low_resource);
+
+    // Don't hand off in demo mode, since the fallback greeter would talk to greetd.
+    let fallback_greeter = &config.get_sys_commands().fallback_greeter;
+    let demo = args.demo.is_some() || args.ui_tour || args.dump_css_nodes || args.inspector;
+    if !demo && !fallback_greeter.is_empty() {
+        fallback::exec_on_panic(fallback_greeter.clone());
+        if let Some(crashes) = detect_crash_loop(args.read_only) {
+            start_fallback_greeter(
+                &config,
+                &format!("it crashed {crashes} times in a row"),
+                guards,
+            );
+        }
+    }
+
+    // Relm4 panics if GTK can't be initialized, so check it first to explain what happened.
+    if let Err(err) = relm4::gtk::init() {
+        start_fallback_greeter(&config, &err.to_string(), guards);
+    }
 
     let app = relm4::RelmApp::new(APP_ID);
     app.with_args(vec![]).run_async::<Greeter>(GreeterInit {
-        config_path: args.config,
+        config,
         css_path: args.style,
-        demo: args.demo,
+        // The tour, the CSS nodes and the inspector only need the UI, so they must not talk to greetd.
+        demo: if demo {
+            Some(args.demo.unwrap_or_default())
+        } else {
+            args.demo
+        },
+        dry_run: args.dry_run,
+        ui_tour: args.ui_tour,
+        dump_css_nodes: args.dump_css_nodes,
+        inspector: args.inspector,
+        read_only: args.read_only,
+        low_resource: args.low_resource,
+        warnings,
+        transport: None,
     });
 }
 
+/// Count this start in the breadcrumb file, returning the number of crashes in a row if the greeter is in a crash loop.
+///
+/// The count is reset once the greeter has run long enough to be stable, or when it exits cleanly.
+fn detect_crash_loop(read_only: bool) -> Option<u32> {
+    if read_only {
+        return None;
+    }
+    let path = Path::new(CRASH_BREADCRUMB_PATH);
+    let starts = match fallback::record_start(path) {
+        Ok(starts) => starts,
+        Err(err) => {
+            tracing::warn!(path = %path.display(), error = %err, "Couldn't record the start");
+            return None;
+        }
+    };
+    if starts > fallback::CRASH_LOOP_STARTS {
+        // Give the greeter another chance after the fallback greeter exits, eg. once a driver is fixed.
+        if let Err(err) = fallback::clear_breadcrumb(path) {
+            tracing::warn!(path = %path.display(), error = %err, "Couldn't remove the crash breadcrumb");
+        }
+        return Some(starts - 1);
+    }
+
+    std::thread::spawn(move || {
+        std::thread::sleep(fallback::STABLE_AFTER);
+        if let Err(err) = fallback::clear_breadcrumb(path) {
+            tracing::warn!(path = %path.display(), error = %err, "Couldn't remove the crash breadcrumb");
+        }
+    });
+    None
+}
+
+/// Explain on the terminal why the greeter can't show its UI, then start the fallback greeter or exit with an error.
+///
+/// This is usually greetd's VT, so the message is shown even without a working display. The logging guards are
+/// dropped to flush the logs, since the process is replaced or exits.
+fn start_fallback_greeter(config: &Config, reason: &str, guards: Vec<WorkerGuard>) -> ! {
+    let command = &config.get_sys_commands().fallback_greeter;
+    tracing::error!(reason, "Couldn't show the greeter");
+    if !command.is_empty() {
+        tracing::info!(?command, "Starting the fallback greeter");
+    }
+    drop(guards);
+
+    eprintln!("ReGreet couldn't show its UI: {reason}");
+    eprintln!("Run `regreet doctor` as the greeter user inside a compositor to find out why.");
+    if !command.is_empty() {
+        fallback::hand_off(command);
+    }
+    std::process::exit(1);
+}
+
+/// Set the environment of the greeter's own process from the config.
+///
+/// This has to happen before any other thread is started, since setting environment variables isn't thread-safe.
+fn setup_env(config: &Config) {
+    for (var, value) in config.get_gtk_env() {
+        let value = if let Some(value) = value {
+            value
+        } else {
+            continue;
+        };
+        // The greetd config (or the compositor) may already set it for debugging, which takes precedence.
+        if env::var_os(var).is_some() {
+            tracing::info!(
+                var,
+                "Not setting the variable from the config, since it's already set"
+            );
+        } else {
+            tracing::info!(var, value, "Setting a variable from the config");
+            env::set_var(var, value);
+        }
+    }
+
+    // Unlike the variables above, this overrides the environment, since the greeter's user usually has the C locale.
+    if let Some(locale) = config.get_locale() {
+        tracing::info!(locale, "Setting the locale");
+        for var in ["LANG", "LC_TIME"] {
+            env::set_var(var, locale);
+        }
+    }
+}
+
+/// Apply the config for the greeter's own process, which has to happen before GTK and the Relm4 runtime are started.
+fn setup_runtime(config: &Config, low_resource: bool) {
+    // Relm4 starts its runtime lazily, so this applies as long as it's set before the app is run.
+    let threads = if low_resource {
+        Some(1)
+    } else {
+        config.get_runtime_threads().filter(|&threads| threads > 0)
+    };
+    if let Some(threads) = threads {
+        tracing::debug!("Running background tasks on {threads} threads");
+        let _ = relm4::RELM_THREADS.set(threads);
+    }
+    if low_resource {
+        let _ = relm4::RELM_BLOCKING_THREADS.set(1);
+    }
+}
+
+/// Remove files left behind by the greeter, and exit with an error if any couldn't be removed.
+fn clean(args: &Args) {
+    let config = Config::new(&args.config, &args.overrides);
+    let mut results = vec![maintenance::clean_rotated_logs(&args.logs, MAX_LOG_FILES)];
+    for path in [config.get_status_path(), config.get_metrics_path()]
+        .into_iter()
+        .flatten()
+    {
+        results.push(maintenance::clean_temp_file(path).map(Vec::from_iter));
+    }
+    results.push(maintenance::clean_oversized_cache(Path::new(CACHE_PATH)).map(Vec::from_iter));
+
+    let mut failed = false;
+    for result in results {
+        match result {
+            Ok(removed) => {
+                for path in removed {
+                    println!("Removed '{}'", path.display());
+                }
+            }
+            Err(err) => {
+                eprintln!("Couldn't clean up: {err}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Check the config file, and exit with an error if it has any problems.
+fn check_config(args: &Args) {
+    let problems = Config::check_file(&args.config);
+    if problems.is_empty() {
+        println!("No problems found in '{}'", args.config.display());
+        return;
+    }
+
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+    eprintln!(
+        "Found {} problem(s) in '{}'",
+        problems.len(),
+        args.config.display()
+    );
+    std::process::exit(1);
+}
+
+/// Print a pass/fail report of the self-diagnostic checks, and exit with an error if any failed.
+fn doctor(args: &Args) {
+    // Only color the report for terminals, so that it can be pasted into bug reports as is.
+    let (pass, fail) = if std::io::stdout().is_terminal() {
+        ("\x1b[32mPASS\x1b[0m", "\x1b[31mFAIL\x1b[0m")
+    } else {
+        ("PASS", "FAIL")
+    };
+
+    let mut failed = false;
+    for (name, outcome) in doctor::run_checks(&args.config, &args.overrides) {
+        match outcome {
+            Ok(details) => println!("{pass} {name}: {details}"),
+            Err(details) => {
+                println!("{fail} {name}: {details}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Print the sessions that the greeter would show, and exit with an error if they couldn't be read.
+fn list_sessions(args: &Args) {
+    let config = Config::new(&args.config, &args.overrides);
+    let sys_util = match SysUtil::new(&config) {
+        Ok(sys_util) => sys_util,
+        Err(err) => {
+            eprintln!("Couldn't read users and sessions: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut sessions: Vec<_> = sys_util.get_sessions().iter().collect();
+    sessions.sort_unstable_by_key(|(name, _)| name.as_str());
+    for (name, info) in sessions {
+        let source = info
+            .path
+            .as_ref()
+            .map_or_else(|| "-".into(), |path| path.to_string_lossy());
+        println!("{name}\t{:?}\t{source}", info.sess_type);
+    }
+    for path in sys_util.get_malformed_sessions() {
+        eprintln!("Malformed session file: {}", path.display());
+    }
+}
+
 /// Initialize the log file with file rotation.
 fn setup_log_file(log_path: &Path) -> IoResult<FileRotate<AppendCount>> {
     if !log_path.exists() {
@@ -115,8 +456,143 @@ fn setup_log_file(log_path: &Path) -> IoResult<FileRotate<AppendCount>> {
     ))
 }
 
+/// Log timer that writes timestamps in a time zone
+///
+/// Unlike `OffsetTime`, this doesn't need to find the local time offset at startup, which fails in some environments
+/// (eg. containers without time zone data).
+#[derive(Clone)]
+struct ZonedTime(TimeZone);
+
+impl FormatTime for ZonedTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        let now = Zoned::new(Timestamp::now(), self.0.clone());
+        let text = strtime::format(LOG_TIME_FORMAT, &now).map_err(|_| std::fmt::Error)?;
+        w.write_str(&text)
+    }
+}
+
+/// Get the time zone for the logs, which is the system time zone unless it's overridden.
+///
+/// If no time zone can be found, then this falls back to UTC with a warning.
+fn log_timezone(name: Option<&str>) -> TimeZone {
+    if let Some(name) = name {
+        match TimeZone::get(name) {
+            Ok(timezone) => return timezone,
+            Err(err) => {
+                tracing::warn!(timezone = name, error = %err, "Invalid log timezone in the config")
+            }
+        }
+    }
+    TimeZone::try_system().unwrap_or_else(|err| {
+        tracing::warn!(error = %err, "Couldn't get the system timezone, so logging in UTC");
+        TimeZone::UTC
+    })
+}
+
+/// Events that are logged before logging is set up, eg. warnings about the config
+///
+/// They're recorded with their fields formatted, and logged again with [`EarlyEvents::replay`].
+#[derive(Clone, Default)]
+struct EarlyEvents(Arc<Mutex<Vec<EarlyEvent>>>);
+
+struct EarlyEvent {
+    level: tracing::Level,
+    /// Fields formatted like in the logs
+    fields: String,
+    /// Message followed by the values of the other fields, to be shown to the user
+    text: String,
+}
+
+/// Collects the message and the values of the other fields of an event, without their names.
+#[derive(Default)]
+struct EventText {
+    message: String,
+    values: Vec<String>,
+}
+
+impl Visit for EventText {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        // Values recorded with `%` are shown with `Display` by their `Debug` implementation.
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.values.push(format!("{value:?}"));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.values.push(value.to_string());
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EarlyEvents {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut fields = String::new();
+        if DefaultFields::new()
+            .format_fields(Writer::new(&mut fields), event)
+            .is_err()
+        {
+            return;
+        }
+        let mut text = EventText::default();
+        event.record(&mut text);
+        let text = if text.values.is_empty() {
+            text.message
+        } else {
+            format!("{}: {}", text.message, text.values.join(", "))
+        };
+
+        let mut events = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        events.push(EarlyEvent {
+            level: *event.metadata().level(),
+            fields,
+            text,
+        });
+    }
+}
+
+impl EarlyEvents {
+    /// Log the recorded events, once logging is set up.
+    ///
+    /// This returns the warnings and errors, so that they can be shown in the greeter.
+    fn replay(self) -> Vec<String> {
+        let events = std::mem::take(&mut *self.0.lock().unwrap_or_else(|err| err.into_inner()));
+        let mut warnings = Vec::new();
+        for EarlyEvent {
+            level,
+            fields,
+            text,
+        } in events
+        {
+            match level {
+                tracing::Level::ERROR => tracing::error!("{fields}"),
+                tracing::Level::WARN => tracing::warn!("{fields}"),
+                tracing::Level::INFO => tracing::info!("{fields}"),
+                tracing::Level::DEBUG => tracing::debug!("{fields}"),
+                tracing::Level::TRACE => tracing::trace!("{fields}"),
+            }
+            if level <= tracing::Level::WARN {
+                warnings.push(text);
+            }
+        }
+        warnings
+    }
+}
+
 /// Initialize logging with file rotation.
-fn init_logging(log_path: &Path, log_level: &LogLevel, stdout: bool) -> Vec<WorkerGuard> {
+///
+/// In read-only mode, or if the log file can't be written, this logs to stdout instead.
+fn init_logging(
+    log_path: &Path,
+    log_level: &LogLevel,
+    stdout: bool,
+    read_only: bool,
+    timezone: TimeZone,
+) -> Vec<WorkerGuard> {
     // Parse the log level string.
     let filter = match log_level {
         LogLevel::Off => LevelFilter::OFF,
@@ -127,8 +603,7 @@ fn init_logging(log_path: &Path, log_level: &LogLevel, stdout: bool) -> Vec<Work
         LogLevel::Trace => LevelFilter::TRACE,
     };
 
-    // Load the timer before spawning threads, otherwise getting the local time offset will fail.
-    let timer = OffsetTime::local_rfc_3339().expect("Couldn't get local time offset");
+    let timer = ZonedTime(timezone);
 
     // Set up the logger.
     let builder = tracing_subscriber::fmt()
@@ -139,7 +614,12 @@ fn init_logging(log_path: &Path, log_level: &LogLevel, stdout: bool) -> Vec<Work
     // Log in a separate non-blocking thread, then return the guard (otherise the non-blocking
     // writer will immediately stop).
     let mut guards = Vec::new();
-    match setup_log_file(log_path) {
+    let log_file = if read_only {
+        Err(None)
+    } else {
+        setup_log_file(log_path).map_err(Some)
+    };
+    match log_file {
         Ok(file) => {
             let (file, guard) = non_blocking(file);
             guards.push(guard);
@@ -165,7 +645,20 @@ fn init_logging(log_path: &Path, log_level: &LogLevel, stdout: bool) -> Vec<Work
             let (file, guard) = non_blocking(std::io::stdout());
             guards.push(guard);
             builder.with_writer(file).init();
-            tracing::error!("Couldn't create log file '{LOG_PATH}': {file_err}");
+            match file_err {
+                None => tracing::info!("Read-only mode, so not writing the log file or the cache"),
+                Some(file_err) if file_err.raw_os_error() == Some(libc::EROFS) => {
+                    // Immutable systems shouldn't get an error on every boot. Other files may still be writable (eg.
+                    // in `/run`), so this doesn't turn on read-only mode.
+                    tracing::warn!(
+                        path = %log_path.display(),
+                        "Log file is on a read-only filesystem, so logging to stdout instead"
+                    );
+                }
+                Some(file_err) => {
+                    tracing::error!(path = %log_path.display(), error = %file_err, "Couldn't create log file")
+                }
+            }
         }
     };
 
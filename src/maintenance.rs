@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Cleanup of files left behind by the greeter, for long-running machines
+//!
+//! This is run by `regreet clean`, eg. from a systemd timer. ReGreet doesn't take any lock files, but interrupted
+//! writes of the status and metrics files can leave temporary files behind.
+
+use std::fs::{metadata, read_dir, remove_file};
+use std::io::{ErrorKind, Result as IOResult};
+use std::path::{Path, PathBuf};
+
+/// Cache files larger than this are broken, since the cache only holds a limited number of users.
+pub const MAX_CACHE_SIZE: u64 = 1024 * 1024;
+
+/// Remove rotated log files that are beyond the number of kept log files.
+///
+/// Rotated log files are named `<log file>.<index>`, optionally followed by `.gz`, with indices starting at 1.
+pub fn clean_rotated_logs(log_path: &Path, max_files: usize) -> IOResult<Vec<PathBuf>> {
+    let (log_dir, log_name) =
+        if let (Some(log_dir), Some(log_name)) = (log_path.parent(), log_path.file_name()) {
+            (log_dir, log_name)
+        } else {
+            return Ok(Vec::new());
+        };
+    let prefix = format!("{}.", log_name.to_string_lossy());
+
+    let mut removed = Vec::new();
+    let entries = match read_dir(log_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(removed),
+        Err(err) => return Err(err),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let index = path
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_prefix(&prefix))
+            .map(|suffix| suffix.strip_suffix(".gz").unwrap_or(suffix))
+            .and_then(|index| index.parse::<usize>().ok());
+        if index.is_some_and(|index| index > max_files) {
+            remove_file(&path)?;
+            removed.push(path);
+        }
+    }
+    removed.sort();
+    Ok(removed)
+}
+
+/// Remove the temporary file left behind by an interrupted write of the given file.
+pub fn clean_temp_file(path: &Path) -> IOResult<Option<PathBuf>> {
+    let tmp_path = path.with_extension("tmp");
+    remove_if_exists(tmp_path)
+}
+
+/// Remove the cache file if it's larger than `MAX_CACHE_SIZE`.
+pub fn clean_oversized_cache(cache_path: &Path) -> IOResult<Option<PathBuf>> {
+    match metadata(cache_path) {
+        Ok(info) if info.len() > MAX_CACHE_SIZE => remove_if_exists(cache_path.to_path_buf()),
+        Ok(_) => Ok(None),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Remove a file, returning its path if it existed.
+fn remove_if_exists(path: PathBuf) -> IOResult<Option<PathBuf>> {
+    match remove_file(&path) {
+        Ok(()) => Ok(Some(path)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    mod clean_rotated_logs {
+        use super::*;
+
+        #[test]
+        fn beyond_limit() {
+            let dir = TempDir::new("clean-logs");
+            for name in [
+                "log",
+                "log.1.gz",
+                "log.3.gz",
+                "log.4.gz",
+                "log.12",
+                "log.x.gz",
+                "other.5.gz",
+            ] {
+                write(dir.join(name), "").expect("Couldn't create log file");
+            }
+
+            let removed = clean_rotated_logs(&dir.join("log"), 3).expect("Couldn't clean logs");
+            let mut left: Vec<_> = read_dir(&dir)
+                .expect("Couldn't read test directory")
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            left.sort();
+
+            assert_eq!(removed, vec![dir.join("log.12"), dir.join("log.4.gz")]);
+            assert_eq!(
+                left,
+                ["log", "log.1.gz", "log.3.gz", "log.x.gz", "other.5.gz"]
+            );
+        }
+    }
+
+    mod clean_oversized_cache {
+        use super::*;
+
+        #[test]
+        fn only_oversized() {
+            let dir = TempDir::new("clean-cache");
+            let small = dir.join("small.toml");
+            let large = dir.join("large.toml");
+            write(&small, "last_user = \"alice\"").expect("Couldn't create cache file");
+            write(&large, vec![b'#'; MAX_CACHE_SIZE as usize + 1])
+                .expect("Couldn't create cache file");
+
+            let small_removed = clean_oversized_cache(&small).expect("Couldn't clean cache");
+            let large_removed = clean_oversized_cache(&large).expect("Couldn't clean cache");
+            let missing_removed =
+                clean_oversized_cache(&dir.join("missing.toml")).expect("Couldn't clean cache");
+            let small_left = small.exists();
+
+            assert_eq!(small_removed, None);
+            assert_eq!(large_removed, Some(large));
+            assert_eq!(missing_removed, None);
+            assert!(small_left);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Login metrics in the Prometheus text format
+//!
+//! The metrics are written to a file, which is meant to be read by the textfile collector of the Prometheus node
+//! exporter.
+
+use std::fmt::Write;
+use std::io::Result as IOResult;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::status::replace_file;
+
+/// Counters about login attempts since the greeter started
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Number of greetd sessions created
+    logins_started: u64,
+    /// Number of failed authentications, eg. due to wrong passwords
+    auth_failures: u64,
+    /// Number of errors from greetd other than failed authentications
+    greetd_errors: u64,
+    /// Number of successful authentications
+    auth_successes: u64,
+    /// Total time taken by the successful authentications
+    auth_duration: Duration,
+}
+
+impl Metrics {
+    pub fn login_started(&mut self) {
+        self.logins_started += 1;
+    }
+
+    pub fn auth_failed(&mut self) {
+        self.auth_failures += 1;
+    }
+
+    pub fn greetd_error(&mut self) {
+        self.greetd_errors += 1;
+    }
+
+    /// Record a successful authentication that took the given time.
+    pub fn auth_succeeded(&mut self, duration: Duration) {
+        self.auth_successes += 1;
+        self.auth_duration += duration;
+    }
+
+    /// Render the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let counters = [
+            (
+                "regreet_logins_started_total",
+                "Number of login attempts started",
+                self.logins_started,
+            ),
+            (
+                "regreet_auth_failures_total",
+                "Number of failed authentications",
+                self.auth_failures,
+            ),
+            (
+                "regreet_greetd_errors_total",
+                "Number of errors from greetd other than failed authentications",
+                self.greetd_errors,
+            ),
+        ];
+        for (name, help, value) in counters {
+            // Writing to a string can't fail.
+            let _ = writeln!(text, "# HELP {name} {help}");
+            let _ = writeln!(text, "# TYPE {name} counter");
+            let _ = writeln!(text, "{name} {value}");
+        }
+
+        // The average authentication time is the sum divided by the count.
+        let name = "regreet_auth_duration_seconds";
+        let _ = writeln!(
+            text,
+            "# HELP {name} Time taken by successful authentications"
+        );
+        let _ = writeln!(text, "# TYPE {name} summary");
+        let _ = writeln!(text, "{name}_sum {}", self.auth_duration.as_secs_f64());
+        let _ = writeln!(text, "{name}_count {}", self.auth_successes);
+        text
+    }
+}
+
+/// File that the metrics are written to
+pub struct MetricsFile {
+    path: PathBuf,
+}
+
+impl MetricsFile {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Replace the file's contents with the given metrics.
+    pub fn write(&self, metrics: &Metrics) -> IOResult<()> {
+        replace_file(&self.path, &metrics.render())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod Metrics {
+        use super::super::*;
+
+        #[test]
+        fn render() {
+            let mut metrics = Metrics::default();
+            metrics.login_started();
+            metrics.login_started();
+            metrics.auth_failed();
+            metrics.auth_succeeded(Duration::from_millis(1500));
+
+            let text = metrics.render();
+            assert!(
+                text.contains("\nregreet_logins_started_total 2\n"),
+                "{text}"
+            );
+            assert!(text.contains("\nregreet_auth_failures_total 1\n"), "{text}");
+            assert!(text.contains("\nregreet_greetd_errors_total 0\n"), "{text}");
+            assert!(
+                text.contains("\nregreet_auth_duration_seconds_sum 1.5\n"),
+                "{text}"
+            );
+            assert!(
+                text.contains("\nregreet_auth_duration_seconds_count 1\n"),
+                "{text}"
+            );
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Forwarding of notifications to sinks other than the screen
+//!
+//! Sinks are configured in the `[notifications]` section of the config, and every notification shown by the greeter
+//! is also sent to each of them.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+use jiff::Timestamp;
+use relm4::gtk::{
+    gio::{self, prelude::*},
+    glib::{self, ToVariant, Variant},
+};
+use serde::Deserialize;
+
+/// Name of the D-Bus service for desktop notifications
+const DBUS_NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+/// Object path of the D-Bus service for desktop notifications
+const DBUS_NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+/// Time to wait for the notification daemon to respond
+const DBUS_TIMEOUT_MS: i32 = 1000;
+
+/// How important a notification is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Config for a single notification sink
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+    /// Run a command with the level and message appended as arguments.
+    Command { command: Vec<String> },
+    /// Append a line to a file.
+    File { path: PathBuf },
+    /// Send a desktop notification over the session bus, if a notification daemon is running.
+    Dbus,
+}
+
+/// Something that notifications can be forwarded to
+pub trait NotificationSink {
+    /// Forward a notification, logging any errors instead of returning them.
+    fn notify(&self, level: Level, message: &str);
+}
+
+/// Sink that runs a command for every notification
+pub struct CommandSink {
+    command: Vec<String>,
+}
+
+impl NotificationSink for CommandSink {
+    fn notify(&self, level: Level, message: &str) {
+        let (program, args) = if let Some((program, args)) = self.command.split_first() {
+            (program, args)
+        } else {
+            warn!("Empty notification command");
+            return;
+        };
+
+        let mut process = Command::new(program);
+        process.args(args).arg(level.as_str()).arg(message);
+        // Wait for the command in a separate thread, so as to not block the GUI.
+        std::thread::spawn(move || match process.output() {
+            Ok(output) if !output.status.success() => error!(
+                stderr = %String::from_utf8_lossy(&output.stderr),
+                "Notification command failed"
+            ),
+            Ok(_) => {}
+            Err(err) => error!(error = %err, "Failed to launch notification command"),
+        });
+    }
+}
+
+/// Sink that appends every notification to a file
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    fn append(&self, level: Level, message: &str) -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{} [{}] {message}", Timestamp::now(), level.as_str())
+    }
+}
+
+impl NotificationSink for FileSink {
+    fn notify(&self, level: Level, message: &str) {
+        if let Err(err) = self.append(level, message) {
+            error!(
+                path = %self.path.display(),
+                error = %err,
+                "Couldn't write notification"
+            );
+        }
+    }
+}
+
+/// Sink that sends desktop notifications over the session bus
+pub struct DbusSink {
+    connection: gio::DBusConnection,
+}
+
+impl DbusSink {
+    /// Connect to the session bus, if there is one.
+    fn connect() -> Option<Self> {
+        match gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE) {
+            Ok(connection) => Some(Self { connection }),
+            Err(err) => {
+                info!(error = %err, "Not sending desktop notifications, since there's no session bus");
+                None
+            }
+        }
+    }
+}
+
+impl NotificationSink for DbusSink {
+    fn notify(&self, level: Level, message: &str) {
+        // Urgency levels from the desktop notifications spec
+        let urgency: u8 = match level {
+            Level::Info => 0,
+            Level::Warning => 1,
+            Level::Error => 2,
+        };
+        let hints = HashMap::from([("urgency".to_string(), urgency.to_variant())]);
+        // Notification daemons may show the body as markup.
+        let body = glib::markup_escape_text(message);
+        let params: Variant = (
+            "ReGreet",
+            0u32,
+            "",
+            "ReGreet",
+            body.as_str(),
+            Vec::<String>::new(),
+            hints,
+            -1i32,
+        )
+            .to_variant();
+
+        self.connection.call(
+            Some(DBUS_NOTIFICATIONS_NAME),
+            DBUS_NOTIFICATIONS_PATH,
+            DBUS_NOTIFICATIONS_NAME,
+            "Notify",
+            Some(&params),
+            None,
+            gio::DBusCallFlags::NONE,
+            DBUS_TIMEOUT_MS,
+            gio::Cancellable::NONE,
+            |result| {
+                if let Err(err) = result {
+                    // There might just be no notification daemon running.
+                    debug!(error = %err, "Couldn't send desktop notification");
+                }
+            },
+        );
+    }
+}
+
+/// Forwards notifications to all configured sinks
+pub struct Notifier {
+    sinks: Vec<Box<dyn NotificationSink>>,
+}
+
+impl Notifier {
+    pub fn new(configs: &[SinkConfig]) -> Self {
+        let sinks = configs
+            .iter()
+            .filter_map(|config| -> Option<Box<dyn NotificationSink>> {
+                match config {
+                    SinkConfig::Command { command } => Some(Box::new(CommandSink {
+                        command: command.clone(),
+                    })),
+                    SinkConfig::File { path } => Some(Box::new(FileSink { path: path.clone() })),
+                    SinkConfig::Dbus => DbusSink::connect().map(|sink| Box::new(sink) as _),
+                }
+            })
+            .collect();
+        Self { sinks }
+    }
+
+    /// Forward a notification to all sinks.
+    pub fn notify(&self, level: Level, message: &str) {
+        for sink in &self.sinks {
+            sink.notify(level, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod SinkConfig {
+        use super::super::*;
+
+        #[derive(Deserialize)]
+        struct Sinks {
+            sinks: Vec<SinkConfig>,
+        }
+
+        #[test_case(
+            r#"sinks = [{ type = "command", command = ["logger", "-t", "regreet"] }]"#
+            => SinkConfig::Command { command: vec!["logger".into(), "-t".into(), "regreet".into()] };
+            "command"
+        )]
+        #[test_case(
+            r#"sinks = [{ type = "file", path = "/var/log/regreet/notifications" }]"#
+            => SinkConfig::File { path: "/var/log/regreet/notifications".into() };
+            "file"
+        )]
+        #[test_case(r#"sinks = [{ type = "dbus" }]"# => SinkConfig::Dbus; "dbus")]
+        fn deserialize(text: &str) -> SinkConfig {
+            toml::from_str::<Sinks>(text)
+                .expect("Invalid sink config")
+                .sinks
+                .remove(0)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod FileSink {
+        use super::super::*;
+        use crate::testutil::TempDir;
+
+        #[test]
+        fn appends_lines() {
+            let dir = TempDir::new("notify");
+            let path = dir.join("notifications");
+            let sink = FileSink { path: path.clone() };
+            sink.notify(Level::Info, "first");
+            sink.notify(Level::Error, "second");
+
+            let text = std::fs::read_to_string(&path).expect("Notification file wasn't written");
+            let lines: Vec<_> = text.lines().collect();
+            assert_eq!(lines.len(), 2);
+            assert!(lines[0].ends_with(" [info] first"), "{}", lines[0]);
+            assert!(lines[1].ends_with(" [error] second"), "{}", lines[1]);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Sanitation of text that comes from greetd, and thus from PAM modules
+//!
+//! This text isn't trusted, so it's cleaned up before it's shown or forwarded anywhere.
+
+/// Maximum number of characters kept from a text sent by greetd
+const MAX_TEXT_LEN: usize = 8192;
+
+/// Whether a character changes the direction of the text around it, which can be used to disguise text
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Clean up a text sent by greetd.
+///
+/// This normalizes line endings, strips control characters (except newlines and tabs) and bidirectional overrides,
+/// and truncates the text if it's too long.
+pub fn sanitize(text: &str) -> String {
+    let mut sanitized: String = text
+        .replace("\r\n", "\n")
+        .chars()
+        .filter(|&c| c == '\n' || c == '\t' || !(c.is_control() || is_bidi_control(c)))
+        .take(MAX_TEXT_LEN + 1)
+        .collect();
+
+    if sanitized.chars().count() > MAX_TEXT_LEN {
+        sanitized = sanitized.chars().take(MAX_TEXT_LEN).collect();
+        sanitized.push('…');
+    }
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod sanitize {
+        use super::super::*;
+
+        #[test_case("Password: " => "Password: "; "plain")]
+        #[test_case("Line 1\r\nLine 2\n\tIndented" => "Line 1\nLine 2\n\tIndented"; "line endings")]
+        #[test_case("Bell\u{7} and escape\u{1b}[31m" => "Bell and escape[31m"; "control characters")]
+        #[test_case("abc\u{202E}fed\u{202C}" => "abcfed"; "bidi override")]
+        #[test_case("<b>bold</b>" => "<b>bold</b>"; "markup is kept")]
+        fn text(text: &str) -> String {
+            sanitize(text)
+        }
+
+        #[test]
+        fn truncates_long_text() {
+            let text = "ä".repeat(MAX_TEXT_LEN + 10);
+            let sanitized = sanitize(&text);
+            assert_eq!(sanitized.chars().count(), MAX_TEXT_LEN + 1);
+            assert!(sanitized.ends_with("ä…"));
+        }
+
+        #[test]
+        fn keeps_text_at_limit() {
+            let text = "a".repeat(MAX_TEXT_LEN);
+            assert_eq!(sanitize(&text), text);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Resolution of the user's session choice into the command and environment given to greetd
+
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::sysutil::{SessionInfo, SessionMap, SessionType, ShellMap};
+
+/// How the user chose the session
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionChoice<'a> {
+    /// A session command typed in manually
+    Manual(&'a str),
+    /// The name of a session selected from the available sessions
+    Selected(&'a str),
+    /// No session, so the user's login shell is used
+    None,
+}
+
+/// A session that is ready to be started
+#[derive(Debug, PartialEq)]
+pub struct ResolvedSession {
+    /// The session name to save in the cache, if the session was selected from the available ones
+    pub name: Option<String>,
+    pub command: Vec<String>,
+    /// Environment variables in the form `KEY=VALUE`
+    pub env: Vec<String>,
+}
+
+/// Reasons a session can't be started
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ResolveError {
+    #[error("Invalid session command: {0}")]
+    InvalidCommand(String),
+    #[error("Session command not allowed: {0}")]
+    CommandNotAllowed(String),
+    #[error("Session '{0}' not found")]
+    SessionNotFound(String),
+    #[error("No session or login shell found")]
+    NoShell,
+}
+
+/// Maps the session chosen by the user to the final command and environment
+pub struct SessionResolver<'a> {
+    sessions: &'a SessionMap,
+    shells: &'a ShellMap,
+    env: &'a HashMap<String, String>,
+    /// Patterns that the program of a manually entered command has to match, if it's restricted
+    manual_allowlist: Option<&'a [Regex]>,
+}
+
+impl<'a> SessionResolver<'a> {
+    pub fn new(
+        sessions: &'a SessionMap,
+        shells: &'a ShellMap,
+        env: &'a HashMap<String, String>,
+    ) -> Self {
+        Self {
+            sessions,
+            shells,
+            env,
+            manual_allowlist: None,
+        }
+    }
+
+    /// Only allow manually entered commands whose program fully matches one of the patterns.
+    pub fn with_manual_allowlist(mut self, allowlist: Option<&'a [Regex]>) -> Self {
+        self.manual_allowlist = allowlist;
+        self
+    }
+
+    /// Resolve the session chosen for the given user.
+    ///
+    /// The X11 prefix isn't applied here, since sessions already have it applied when they're loaded.
+    pub fn resolve(
+        &self,
+        username: Option<&str>,
+        choice: SessionChoice,
+    ) -> Result<ResolvedSession, ResolveError> {
+        let (name, info) = match choice {
+            SessionChoice::Manual(text) => {
+                debug!("Retrieved session command '{text}' through manual entry");
+                if let Some(command) = shlex::split(text) {
+                    if !self.is_allowed(&command) {
+                        warn!("Manually entered session command isn't allowed: {text}");
+                        return Err(ResolveError::CommandNotAllowed(text.to_string()));
+                    }
+                    let info = SessionInfo {
+                        command,
+                        sess_type: SessionType::Unknown,
+                        path: None,
+                    };
+                    (None, info)
+                } else {
+                    return Err(ResolveError::InvalidCommand(text.to_string()));
+                }
+            }
+            SessionChoice::Selected(session) => {
+                debug!("Retrieved current session: {session}");
+                if let Some(info) = self.sessions.get(session) {
+                    (Some(session.to_string()), info.clone())
+                } else {
+                    // Shouldn't happen, unless there are no sessions available.
+                    return Err(ResolveError::SessionNotFound(session.to_string()));
+                }
+            }
+            SessionChoice::None => {
+                warn!("No entry found; using default login shell of user: {username:?}");
+                if let Some(command) = username.and_then(|username| self.shells.get(username)) {
+                    let info = SessionInfo {
+                        command: command.clone(),
+                        sess_type: SessionType::Unknown,
+                        path: None,
+                    };
+                    (None, info)
+                } else {
+                    return Err(ResolveError::NoShell);
+                }
+            }
+        };
+
+        Ok(ResolvedSession {
+            name,
+            env: self.environment(info.sess_type),
+            command: info.command,
+        })
+    }
+
+    /// Check whether a manually entered command may be started.
+    fn is_allowed(&self, command: &[String]) -> bool {
+        let allowlist = if let Some(allowlist) = self.manual_allowlist {
+            allowlist
+        } else {
+            return true;
+        };
+        command
+            .first()
+            .is_some_and(|program| allowlist.iter().any(|regex| regex.is_match(program)))
+    }
+
+    /// Generate the environment for a session of the given type.
+    fn environment(&self, sess_type: SessionType) -> Vec<String> {
+        let mut environment = Vec::with_capacity(self.env.len() + 1);
+        match sess_type {
+            SessionType::X11 => {
+                environment.push("XDG_SESSION_TYPE=x11".to_string());
+            }
+            SessionType::Wayland => {
+                environment.push("XDG_SESSION_TYPE=wayland".to_string());
+            }
+            SessionType::Unknown => {}
+        };
+        for (k, v) in self.env {
+            environment.push(format!("{}={}", k, v));
+        }
+        environment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod SessionResolver {
+        use super::super::*;
+        use crate::config::Config;
+
+        fn sessions() -> SessionMap {
+            HashMap::from([
+                (
+                    "Sway".to_string(),
+                    SessionInfo {
+                        command: vec!["sway".to_string()],
+                        sess_type: SessionType::Wayland,
+                        path: None,
+                    },
+                ),
+                (
+                    "Xfce".to_string(),
+                    SessionInfo {
+                        command: vec!["startx".to_string(), "startxfce4".to_string()],
+                        sess_type: SessionType::X11,
+                        path: None,
+                    },
+                ),
+            ])
+        }
+
+        fn shells() -> ShellMap {
+            HashMap::from([("alice".to_string(), vec!["/bin/zsh".to_string()])])
+        }
+
+        fn resolve(
+            username: Option<&str>,
+            choice: SessionChoice,
+            env: &HashMap<String, String>,
+        ) -> Result<ResolvedSession, ResolveError> {
+            let (sessions, shells) = (sessions(), shells());
+            SessionResolver::new(&sessions, &shells, env).resolve(username, choice)
+        }
+
+        #[test_case(
+            SessionChoice::Selected("Sway")
+            => Ok(ResolvedSession {
+                name: Some("Sway".into()),
+                command: vec!["sway".into()],
+                env: vec!["XDG_SESSION_TYPE=wayland".into()],
+            });
+            "selected wayland"
+        )]
+        #[test_case(
+            SessionChoice::Selected("Xfce")
+            => Ok(ResolvedSession {
+                name: Some("Xfce".into()),
+                command: vec!["startx".into(), "startxfce4".into()],
+                env: vec!["XDG_SESSION_TYPE=x11".into()],
+            });
+            "selected x11"
+        )]
+        #[test_case(
+            SessionChoice::Selected("Gnome")
+            => Err(ResolveError::SessionNotFound("Gnome".into()));
+            "selected missing"
+        )]
+        #[test_case(
+            SessionChoice::Manual("dbus-run-session 'my session'")
+            => Ok(ResolvedSession {
+                name: None,
+                command: vec!["dbus-run-session".into(), "my session".into()],
+                env: vec![],
+            });
+            "manual"
+        )]
+        #[test_case(
+            SessionChoice::Manual("sway 'unclosed")
+            => Err(ResolveError::InvalidCommand("sway 'unclosed".into()));
+            "manual invalid"
+        )]
+        #[test_case(
+            SessionChoice::None
+            => Ok(ResolvedSession {
+                name: None,
+                command: vec!["/bin/zsh".into()],
+                env: vec![],
+            });
+            "shell fallback"
+        )]
+        fn resolve_choice(choice: SessionChoice) -> Result<ResolvedSession, ResolveError> {
+            resolve(Some("alice"), choice, &HashMap::new())
+        }
+
+        #[test_case(Some("bob"); "user without shell")]
+        #[test_case(None; "no user")]
+        fn no_shell(username: Option<&str>) {
+            assert_eq!(
+                resolve(username, SessionChoice::None, &HashMap::new()),
+                Err(ResolveError::NoShell)
+            );
+        }
+
+        #[test_case("sway" => true; "allowed")]
+        #[test_case("/usr/bin/sway --unsupported-gpu" => true; "allowed path")]
+        #[test_case("swayfx" => false; "partial match")]
+        #[test_case("bash -c sway" => false; "not allowed")]
+        #[test_case("" => false; "empty")]
+        fn manual_allowlist(text: &str) -> bool {
+            let (sessions, shells, env) = (sessions(), shells(), HashMap::new());
+            let mut config: Config =
+                toml::from_str("[behavior]\nmanual_session_allowlist = [\"(/usr/bin/)?sway\"]")
+                    .expect("Invalid config");
+            config.compile_patterns();
+            SessionResolver::new(&sessions, &shells, &env)
+                .with_manual_allowlist(config.get_manual_session_allowlist())
+                .resolve(Some("alice"), SessionChoice::Manual(text))
+                .is_ok()
+        }
+
+        #[test]
+        fn env_merged_after_session_type() {
+            let env = HashMap::from([("FOO".to_string(), "bar".to_string())]);
+            let session = resolve(Some("alice"), SessionChoice::Selected("Sway"), &env).unwrap();
+            assert_eq!(session.env, ["XDG_SESSION_TYPE=wayland", "FOO=bar"]);
+        }
+    }
+}
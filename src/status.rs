@@ -0,0 +1,106 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Status file for monitoring the greeter
+//!
+//! The status is periodically written as JSON, so that monitoring tools can detect a greeter that's stuck or broken.
+
+use std::fs::{create_dir_all, rename, write};
+use std::io::Result as IOResult;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// State of the greeter's login flow
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    /// No login attempt is in progress.
+    Idle,
+    /// A login attempt is in progress.
+    Authenticating,
+    /// Authentication succeeded, and the session is being started.
+    Authenticated,
+    /// A request to greetd hasn't been answered yet.
+    WaitingForGreetd,
+}
+
+/// Snapshot of the greeter's state, written to the status file
+#[derive(Debug, Serialize)]
+pub struct Status<'a> {
+    pub state: State,
+    /// Seconds since the greeter started
+    pub uptime_secs: u64,
+    /// The last error shown to the user
+    pub last_error: Option<&'a str>,
+    /// Whether the greeter talks to greetd, which is false in demo mode
+    pub greetd_connected: bool,
+}
+
+/// File that the status is written to
+pub struct StatusFile {
+    path: PathBuf,
+}
+
+impl StatusFile {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Replace the file's contents with the given status.
+    pub fn write(&self, status: &Status) -> IOResult<()> {
+        replace_file(&self.path, &serde_json::to_string(status)?)
+    }
+}
+
+/// Replace a file's contents, creating its directory if needed.
+///
+/// The contents are written to a temporary file first, so that readers never see a partially written file.
+pub(crate) fn replace_file(path: &Path, contents: &str) -> IOResult<()> {
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    write(&tmp_path, contents)?;
+    rename(tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(non_snake_case)]
+    mod StatusFile {
+        use super::super::*;
+        use crate::testutil::TempDir;
+
+        #[test]
+        fn writes_json() {
+            let dir = TempDir::new("status");
+            let path = dir.join("status.json");
+            let file = StatusFile::new(&path);
+
+            file.write(&Status {
+                state: State::Authenticating,
+                uptime_secs: 42,
+                last_error: None,
+                greetd_connected: true,
+            })
+            .expect("Couldn't write status file");
+            file.write(&Status {
+                state: State::WaitingForGreetd,
+                uptime_secs: 52,
+                last_error: Some("Login failed"),
+                greetd_connected: true,
+            })
+            .expect("Couldn't overwrite status file");
+
+            let text = std::fs::read_to_string(&path).expect("Status file wasn't written");
+            assert_eq!(
+                text,
+                r#"{"state":"waiting_for_greetd","uptime_secs":52,"last_error":"Login failed","greetd_connected":true}"#
+            );
+        }
+    }
+}
@@ -4,42 +4,533 @@
 
 //! Helper for system utilities like users and sessions
 
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::{read, read_to_string};
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::{metadata, read, read_dir, read_to_string};
 use std::io;
+use std::mem::MaybeUninit;
 use std::ops::ControlFlow;
-use std::path::Path;
-use std::str::from_utf8;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::{Mutex, PoisonError};
 
 use glob::glob;
-use pwd::Passwd;
 use regex::Regex;
 use shlex::Shlex;
 
-use crate::config::Config;
+use crate::config::{Config, SessionFilter};
 use crate::constants::{LOGIN_DEFS_PATHS, LOGIN_DEFS_UID_MAX, LOGIN_DEFS_UID_MIN, SESSION_DIRS};
 
 /// XDG data directory variable name (parent directory for X11/Wayland sessions)
 const XDG_DIR_ENV_VAR: &str = "XDG_DATA_DIRS";
+/// File containing the hostname of the machine
+const HOSTNAME_PATH: &str = "/proc/sys/kernel/hostname";
+/// Limit to the number of desktop files scanned in a session directory
+const MAX_SESSION_FILES: usize = 1000;
+/// Desktop files larger than this aren't sessions, eg. when they're symlinks to something else.
+const MAX_SESSION_FILE_SIZE: u64 = 1024 * 1024;
+/// Header of the section in desktop files that describes the session
+const DESKTOP_ENTRY_HEADER: &str = "[Desktop Entry]";
+/// Initial size of the buffer for the strings of a looked up user, which is doubled while it's too small
+const PASSWD_BUFFER_SIZE: usize = 1024;
+/// Size of the buffer for the strings of a looked up user, beyond which it isn't grown anymore
+const MAX_PASSWD_BUFFER_SIZE: usize = 1024 * 1024;
 
-#[derive(Clone, Copy)]
+/// Lock that's held while reading the whole user database, since `getpwent` keeps its position and its result in
+/// global state
+static USER_DATABASE: Mutex<()> = Mutex::new(());
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SessionType {
     X11,
     Wayland,
     Unknown,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SessionInfo {
     pub command: Vec<String>,
     pub sess_type: SessionType,
+    /// Desktop file that the session was read from, which is `None` for commands that aren't from one
+    pub path: Option<PathBuf>,
+}
+
+/// The outcome of parsing a session desktop file
+#[derive(Debug, PartialEq)]
+pub enum ParsedSession {
+    /// A session that can be shown, along with its full name
+    Visible(String, SessionInfo),
+    /// A session hidden through either `Hidden` or `NoDisplay`
+    Hidden,
+    /// A session that can't be used, eg. due to a missing command
+    Invalid,
+}
+
+impl SessionInfo {
+    /// Parse the contents of a session desktop file.
+    ///
+    /// The path is recorded as the source of the session, and used as a fallback for the session name. For X11
+    /// sessions, `cmd_prefix` is the command that starts the X server.
+    pub fn parse(
+        text: &str,
+        path: &Path,
+        sess_type: SessionType,
+        cmd_prefix: Option<&[String]>,
+    ) -> ParsedSession {
+        // The session launch command is specified as: Exec=command arg1 arg2...
+        let cmd_regex = Regex::new(r"Exec=(.*)").expect("Invalid regex for session command");
+        // The session name is specified as: Name=My Session
+        let name_regex = Regex::new(r"Name=(.*)").expect("Invalid regex for session name");
+
+        // Hiding could be either as Hidden=true or NoDisplay=true
+        let hidden_regex = Regex::new(r"Hidden=(.*)").expect("Invalid regex for hidden");
+        let no_display_regex = Regex::new(r"NoDisplay=(.*)").expect("Invalid regex for no display");
+
+        let hidden: bool = if let Some(hidden_str) = hidden_regex
+            .captures(text)
+            .and_then(|capture| capture.get(1))
+        {
+            hidden_str.as_str().parse().unwrap_or(false)
+        } else {
+            false
+        };
+
+        let no_display: bool = if let Some(no_display_str) = no_display_regex
+            .captures(text)
+            .and_then(|capture| capture.get(1))
+        {
+            no_display_str.as_str().parse().unwrap_or(false)
+        } else {
+            false
+        };
+
+        if hidden | no_display {
+            return ParsedSession::Hidden;
+        };
+
+        // Parse the desktop file to get the session command.
+        let cmd = if let Some(cmd_str) = cmd_regex.captures(text).and_then(|capture| capture.get(1))
+        {
+            let mut cmd = if let Some(prefix) = cmd_prefix {
+                prefix.to_vec()
+            } else {
+                Vec::new()
+            };
+            let prefix_len = cmd.len();
+            cmd.extend(Shlex::new(cmd_str.as_str()));
+            if cmd.len() > prefix_len {
+                cmd
+            } else {
+                warn!(
+                    "Couldn't split command of '{}' into arguments: {}",
+                    path.display(),
+                    cmd_str.as_str()
+                );
+                // Skip the desktop file, since a missing command means that we can't
+                // use it.
+                return ParsedSession::Invalid;
+            }
+        } else {
+            warn!("No command found for session: {}", path.display());
+            // Skip the desktop file, since a missing command means that we can't use it.
+            return ParsedSession::Invalid;
+        };
+
+        // Get the full name of this session.
+        let name = if let Some(name) = name_regex.captures(text).and_then(|capture| capture.get(1))
+        {
+            debug!(
+                "Found name '{}' for session '{}' with command '{:?}'",
+                name.as_str(),
+                path.display(),
+                cmd
+            );
+            name.as_str()
+        } else if let Some(stem) = path.file_stem() {
+            // Get the stem of the filename of this desktop file.
+            // This is used as backup, in case the file name doesn't exist.
+            if let Some(stem) = stem.to_str() {
+                debug!(
+                    "Using file stem '{stem}', since no name was found for session: {}",
+                    path.display()
+                );
+                stem
+            } else {
+                warn!("Non-UTF-8 file stem in session file: {}", path.display());
+                // No way to display this session name, so just skip it.
+                return ParsedSession::Invalid;
+            }
+        } else {
+            warn!("No file stem found for session: {}", path.display());
+            // No file stem implies no file name, which shouldn't happen.
+            // Since there's no full name nor file stem, just skip this anomalous
+            // session.
+            return ParsedSession::Invalid;
+        };
+        ParsedSession::Visible(
+            name.to_string(),
+            SessionInfo {
+                command: cmd,
+                sess_type,
+                path: Some(path.to_path_buf()),
+            },
+        )
+    }
+}
+
+/// Get the hostname of the machine.
+pub fn hostname() -> Option<String> {
+    match read_to_string(HOSTNAME_PATH) {
+        Ok(hostname) => Some(hostname.trim().to_string()),
+        Err(err) => {
+            warn!(path = HOSTNAME_PATH, error = %err, "Couldn't read hostname");
+            None
+        }
+    }
+}
+
+/// Get the contents of the first `[Desktop Entry]` section of a desktop file, and whether there are more of them.
+///
+/// Files without this section are used as a whole, since some session files omit the header.
+fn desktop_entry(text: &str) -> (&str, bool) {
+    // Start and end offsets of the first section
+    let mut section = None;
+    let mut duplicated = false;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let header = line.trim();
+        if header.starts_with('[') {
+            match section {
+                None if header == DESKTOP_ENTRY_HEADER => {
+                    section = Some((offset + line.len(), text.len()));
+                }
+                None => (),
+                Some((start, end)) => {
+                    if end == text.len() {
+                        section = Some((start, offset));
+                    }
+                    duplicated |= header == DESKTOP_ENTRY_HEADER;
+                }
+            }
+        }
+        offset += line.len();
+    }
+
+    if let Some((start, end)) = section {
+        (&text[start..end], duplicated)
+    } else {
+        (text, false)
+    }
+}
+
+/// Check whether the user's home directory is missing or empty, eg. because they haven't logged in yet.
+///
+/// This is `None` if it can't be told, eg. because the greeter isn't allowed to read the home directory.
+pub fn home_is_empty(username: &str) -> Option<bool> {
+    lookup_user(username).and_then(|entry| dir_is_empty(&entry.dir))
+}
+
+/// Check whether the user is in one of the groups, either as their primary group or as a listed member.
+///
+/// Errors of the user and group databases are logged, and count as the user not being in the group.
+pub fn user_in_groups(username: &str, groups: &[String]) -> bool {
+    if groups.is_empty() {
+        return false;
+    }
+    let user = if let Some(user) = lookup_user(username) {
+        user
+    } else {
+        return false;
+    };
+
+    groups
+        .iter()
+        .any(|group| match GroupEntry::from_name(group) {
+            Ok(Some(entry)) => {
+                entry.gid == user.gid || entry.members.iter().any(|member| member == username)
+            }
+            Ok(None) => {
+                warn!(%group, "Unknown group");
+                false
+            }
+            Err(err) => {
+                warn!(%group, error = %err, "Couldn't look up group");
+                false
+            }
+        })
+}
+
+/// Look up a user by their username, logging errors of the user database (eg. when LDAP can't be reached).
+fn lookup_user(username: &str) -> Option<PasswdEntry> {
+    PasswdEntry::from_name(username).unwrap_or_else(|err| {
+        warn!(user = username, error = %err, "Couldn't look up user");
+        None
+    })
+}
+
+/// Check whether the directory is missing or empty, or `None` if it can't be read.
+fn dir_is_empty(dir: &Path) -> Option<bool> {
+    match read_dir(dir) {
+        Ok(mut entries) => Some(entries.next().is_none()),
+        // Home directories may be created at the first login, eg. by pam_mkhomedir.
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Some(true),
+        Err(err) => {
+            debug!(path = %dir.display(), error = %err, "Couldn't read directory");
+            None
+        }
+    }
+}
+
+/// Entry of the user database, with the raw bytes of its strings
+///
+/// Unlike [`String`]s, these can hold usernames and paths that aren't valid UTF-8, so that such an entry doesn't stop
+/// the whole user database from being read.
+#[derive(Clone, Debug, PartialEq)]
+struct PasswdEntry {
+    name: OsString,
+    uid: u32,
+    gid: u32,
+    gecos: Option<OsString>,
+    dir: PathBuf,
+    shell: OsString,
+}
+
+impl PasswdEntry {
+    /// Copy the fields out of an entry returned by libc.
+    ///
+    /// # Safety
+    /// The entry and its non-null strings must be valid.
+    unsafe fn from_raw(entry: &libc::passwd) -> Self {
+        let to_os_string = |ptr: *const libc::c_char| {
+            if ptr.is_null() {
+                OsString::new()
+            } else {
+                OsStr::from_bytes(CStr::from_ptr(ptr).to_bytes()).to_os_string()
+            }
+        };
+        Self {
+            name: to_os_string(entry.pw_name),
+            uid: entry.pw_uid,
+            gid: entry.pw_gid,
+            gecos: (!entry.pw_gecos.is_null()).then(|| to_os_string(entry.pw_gecos)),
+            dir: to_os_string(entry.pw_dir).into(),
+            shell: to_os_string(entry.pw_shell),
+        }
+    }
+
+    /// Look up a user by their username.
+    ///
+    /// This is `None` if there's no such user, and an error if the user database couldn't be read.
+    fn from_name(username: &str) -> io::Result<Option<Self>> {
+        // Usernames with a null byte can't exist.
+        let name = if let Ok(name) = CString::new(username) {
+            name
+        } else {
+            return Ok(None);
+        };
+
+        let mut buffer = vec![0; PASSWD_BUFFER_SIZE];
+        loop {
+            let mut entry = MaybeUninit::<libc::passwd>::uninit();
+            let mut result = ptr::null_mut();
+            // SAFETY: The strings of the entry point into the buffer, which outlives the entry.
+            let code = unsafe {
+                libc::getpwnam_r(
+                    name.as_ptr(),
+                    entry.as_mut_ptr(),
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut result,
+                )
+            };
+            match code {
+                0 => {
+                    // SAFETY: The result is either null or points to the entry, which `getpwnam_r` filled in.
+                    let entry = unsafe { result.as_ref() };
+                    return Ok(entry.map(|entry| unsafe { Self::from_raw(entry) }));
+                }
+                libc::ERANGE if buffer.len() < MAX_PASSWD_BUFFER_SIZE => {
+                    buffer.resize(buffer.len() * 2, 0)
+                }
+                code => return Err(io::Error::from_raw_os_error(code)),
+            }
+        }
+    }
+
+    /// Read all entries of the user database.
+    ///
+    /// If it can't be read completely (eg. when LDAP can't be reached), the error is logged, and the entries read
+    /// before it are returned, so that eg. local users can still log in.
+    fn all() -> Vec<Self> {
+        // `getpwent_r` isn't available everywhere (eg. with musl), and it shares the position in the database with
+        // `getpwent`, so the whole enumeration is serialized instead.
+        let _lock = USER_DATABASE.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut entries = Vec::new();
+        // SAFETY: The lock keeps other threads of the greeter from using `getpwent` meanwhile, and each returned entry
+        // is copied before the next call to it.
+        unsafe {
+            libc::setpwent();
+            loop {
+                // `getpwent` returns null both at the end and for errors, which are only told apart by `errno`.
+                *libc::__errno_location() = 0;
+                if let Some(entry) = libc::getpwent().as_ref() {
+                    entries.push(Self::from_raw(entry));
+                    continue;
+                }
+                let err = io::Error::last_os_error();
+                // Some implementations set ENOENT at the end.
+                if !matches!(err.raw_os_error(), Some(0 | libc::ENOENT)) {
+                    error!(error = %err, read = entries.len(), "Couldn't read the whole user database");
+                }
+                break;
+            }
+            libc::endpwent();
+        }
+        entries
+    }
+}
+
+/// Entry of the group database
+struct GroupEntry {
+    gid: u32,
+    /// Usernames of the members, besides the users who have this as their primary group
+    members: Vec<OsString>,
+}
+
+impl GroupEntry {
+    /// Look up a group by its name.
+    ///
+    /// This is `None` if there's no such group, and an error if the group database couldn't be read.
+    fn from_name(name: &str) -> io::Result<Option<Self>> {
+        // Group names with a null byte can't exist.
+        let name = if let Ok(name) = CString::new(name) {
+            name
+        } else {
+            return Ok(None);
+        };
+
+        let mut buffer = vec![0; PASSWD_BUFFER_SIZE];
+        loop {
+            let mut entry = MaybeUninit::<libc::group>::uninit();
+            let mut result = ptr::null_mut();
+            // SAFETY: The strings of the entry point into the buffer, which outlives the entry.
+            let code = unsafe {
+                libc::getgrnam_r(
+                    name.as_ptr(),
+                    entry.as_mut_ptr(),
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut result,
+                )
+            };
+            match code {
+                0 => {
+                    // SAFETY: The result is either null or points to the entry, which `getgrnam_r` filled in. Its list
+                    // of members ends with a null pointer.
+                    let entry = unsafe { result.as_ref() };
+                    return Ok(entry.map(|entry| unsafe { Self::from_raw(entry) }));
+                }
+                libc::ERANGE if buffer.len() < MAX_PASSWD_BUFFER_SIZE => {
+                    buffer.resize(buffer.len() * 2, 0)
+                }
+                code => return Err(io::Error::from_raw_os_error(code)),
+            }
+        }
+    }
+
+    /// Copy the fields out of an entry returned by libc.
+    ///
+    /// # Safety
+    /// The entry, its list of members and the strings in it must be valid.
+    unsafe fn from_raw(entry: &libc::group) -> Self {
+        let mut members = Vec::new();
+        let mut member = entry.gr_mem;
+        while !member.is_null() && !(*member).is_null() {
+            members.push(OsStr::from_bytes(CStr::from_ptr(*member).to_bytes()).to_os_string());
+            member = member.add(1);
+        }
+        Self {
+            gid: entry.gr_gid,
+            members,
+        }
+    }
+}
+
+/// Collect the names and shells of the regular users in the user database.
+///
+/// Usernames that aren't valid UTF-8 are skipped with a warning, since greetd can only log in users whose names are.
+/// Full names are displayed lossily instead.
+fn collect_users(
+    entries: impl IntoIterator<Item = PasswdEntry>,
+    normal_user: &NormalUser,
+) -> (UserMap, ShellMap) {
+    let mut users = HashMap::new();
+    let mut shells = HashMap::new();
+
+    for entry in entries
+        .into_iter()
+        .filter(|entry| normal_user.is_normal_user(entry.uid))
+    {
+        let name = if let Some(name) = entry.name.to_str() {
+            name.to_string()
+        } else {
+            warn!(
+                "Skipping user '{}' with UID '{}', since their username isn't valid UTF-8",
+                entry.name.to_string_lossy(),
+                entry.uid
+            );
+            continue;
+        };
+
+        // Use the actual system username if the "full name" is not available.
+        let full_name = if let Some(gecos) = entry.gecos {
+            let gecos = gecos.to_string_lossy();
+            if gecos.is_empty() {
+                debug!(
+                    "Found user '{name}' with UID '{}' and empty full name",
+                    entry.uid
+                );
+                name.clone()
+            } else {
+                // Only take first entry in gecos field.
+                let gecos_name_part: &str = gecos.split(',').next().unwrap_or(&gecos);
+                debug!(
+                    "Found user '{name}' with UID '{}' and full name: {gecos_name_part}",
+                    entry.uid
+                );
+                gecos_name_part.into()
+            }
+        } else {
+            debug!(
+                "Found user '{name}' with UID '{}' and missing full name",
+                entry.uid
+            );
+            name.clone()
+        };
+        users.insert(full_name, name.clone());
+
+        if let Some(cmd) = entry.shell.to_str().and_then(shlex::split) {
+            shells.insert(name, cmd);
+        } else {
+            // Skip this user, since a missing command means that we can't use it.
+            warn!(
+                "Couldn't split shell of username '{name}' into arguments: {}",
+                entry.shell.to_string_lossy()
+            );
+        };
+    }
+
+    (users, shells)
 }
 
 // Convenient aliases for used maps
-type UserMap = HashMap<String, String>;
-type ShellMap = HashMap<String, Vec<String>>;
-type SessionMap = HashMap<String, SessionInfo>;
+pub type UserMap = HashMap<String, String>;
+pub type ShellMap = HashMap<String, Vec<String>>;
+pub type SessionMap = HashMap<String, SessionInfo>;
 
 /// Stores info of all regular users and sessions
 pub struct SysUtil {
@@ -49,10 +540,41 @@ pub struct SysUtil {
     shells: ShellMap,
     /// Maps a session's full name to its command
     sessions: SessionMap,
+    /// Session files that could only be partially read, eg. due to invalid UTF-8
+    malformed_sessions: Vec<PathBuf>,
+    /// UID limits for regular users
+    normal_user: NormalUser,
 }
 
 impl SysUtil {
     pub fn new(config: &Config) -> io::Result<Self> {
+        // The UID range in the config takes precedence, eg. on systems without `login.defs`.
+        let normal_user = match config.get_uid_range() {
+            (Some(uid_min), Some(uid_max)) => NormalUser { uid_min, uid_max },
+            (uid_min, uid_max) => {
+                let login_defs = Self::read_login_defs();
+                NormalUser {
+                    uid_min: uid_min.unwrap_or(login_defs.uid_min),
+                    uid_max: uid_max.unwrap_or(login_defs.uid_max),
+                }
+            }
+        };
+
+        debug!("{normal_user:?}");
+
+        let (users, shells) = Self::init_users(&normal_user)?;
+        let (sessions, malformed_sessions) = Self::init_sessions(config)?;
+        Ok(Self {
+            users,
+            shells,
+            sessions,
+            malformed_sessions,
+            normal_user,
+        })
+    }
+
+    /// Read the UID range of normal users from the first `login.defs` file found, or else use the defaults.
+    fn read_login_defs() -> NormalUser {
         let path = (*LOGIN_DEFS_PATHS).iter().try_for_each(|path| {
             if let Ok(true) = AsRef::<Path>::as_ref(&path).try_exists() {
                 ControlFlow::Break(path)
@@ -61,10 +583,10 @@ impl SysUtil {
             }
         });
 
-        let normal_user = match path {
+        match path {
             ControlFlow::Break(path) => read_to_string(path)
                 .map_err(|err| {
-                    warn!("Failed to read login.defs from '{path}', using default values: {err}")
+                    warn!(path, error = %err, "Failed to read login.defs, using default values")
                 })
                 .map(|text| NormalUser::parse_login_defs(&text))
                 .unwrap_or_default(),
@@ -73,74 +595,21 @@ impl SysUtil {
 
                 NormalUser::default()
             }
-        };
-
-        debug!("{normal_user:?}");
-
-        let (users, shells) = Self::init_users(normal_user)?;
-        Ok(Self {
-            users,
-            shells,
-            sessions: Self::init_sessions(config)?,
-        })
+        }
     }
 
     /// Get the list of regular users.
     ///
     /// These are defined as a list of users with UID between `UID_MIN` and `UID_MAX`.
-    fn init_users(normal_user: NormalUser) -> io::Result<(UserMap, ShellMap)> {
-        let mut users = HashMap::new();
-        let mut shells = HashMap::new();
-
-        for entry in Passwd::iter().filter(|entry| normal_user.is_normal_user(entry.uid)) {
-            // Use the actual system username if the "full name" is not available.
-            let full_name = if let Some(gecos) = entry.gecos {
-                if gecos.is_empty() {
-                    debug!(
-                        "Found user '{}' with UID '{}' and empty full name",
-                        entry.name, entry.uid
-                    );
-                    entry.name.clone()
-                } else {
-                    // Only take first entry in gecos field.
-                    let gecos_name_part: &str = gecos.split(',').next().unwrap_or(&gecos);
-                    debug!(
-                        "Found user '{}' with UID '{}' and full name: {gecos_name_part}",
-                        entry.name, entry.uid
-                    );
-                    gecos_name_part.into()
-                }
-            } else {
-                debug!(
-                    "Found user '{}' with UID '{}' and missing full name",
-                    entry.name, entry.uid
-                );
-                entry.name.clone()
-            };
-            users.insert(full_name, entry.name.clone());
-
-            if let Some(cmd) = shlex::split(entry.shell.as_str()) {
-                shells.insert(entry.name, cmd);
-            } else {
-                // Skip this user, since a missing command means that we can't use it.
-                warn!(
-                    "Couldn't split shell of username '{}' into arguments: {}",
-                    entry.name, entry.shell
-                );
-            };
-        }
-
-        Ok((users, shells))
+    fn init_users(normal_user: &NormalUser) -> io::Result<(UserMap, ShellMap)> {
+        Ok(collect_users(PasswdEntry::all(), normal_user))
     }
 
     /// Get available X11 and Wayland sessions.
     ///
     /// These are defined as either X11 or Wayland session desktop files stored in specific
-    /// directories.
-    fn init_sessions(config: &Config) -> io::Result<SessionMap> {
-        let mut found_session_names = HashSet::new();
-        let mut sessions = HashMap::new();
-
+    /// directories. Session files that could only be partially read are also returned.
+    fn init_sessions(config: &Config) -> io::Result<(SessionMap, Vec<PathBuf>)> {
         // Use the XDG spec if available, else use the one that's compiled.
         // The XDG env var can change after compilation in some distros like NixOS.
         let session_dirs = if let Ok(sess_parent_dirs) = env::var(XDG_DIR_ENV_VAR) {
@@ -157,6 +626,35 @@ impl SysUtil {
             SESSION_DIRS.to_string()
         };
 
+        Self::scan_sessions(
+            &session_dirs,
+            &config.get_sys_commands().x11_prefix,
+            config.get_session_filter(),
+        )
+    }
+
+    /// Find all sessions in the given `:`-separated list of directories.
+    ///
+    /// Desktop files in directories named `xsessions` are treated as X11 sessions, and their commands are prefixed with
+    /// `x11_prefix`. The first session found with a given file name and type shadows the rest. Sessions hidden by
+    /// `filter` shadow the rest too, so that they're hidden in every directory.
+    ///
+    /// Directories are only scanned once, even if they're listed multiple times (eg. through symlinks), and only up to
+    /// `MAX_SESSION_FILES` desktop files are scanned in each. Files that aren't regular files, or that are larger than
+    /// `MAX_SESSION_FILE_SIZE`, are skipped, since reading them could hang the greeter.
+    ///
+    /// Invalid UTF-8 is replaced, and only the first `[Desktop Entry]` section is used. Such malformed files are
+    /// returned along with the sessions.
+    pub fn scan_sessions(
+        session_dirs: &str,
+        x11_prefix: &[String],
+        filter: &SessionFilter,
+    ) -> io::Result<(SessionMap, Vec<PathBuf>)> {
+        let mut found_session_names = HashSet::new();
+        let mut scanned_dirs = HashSet::new();
+        let mut sessions = HashMap::new();
+        let mut malformed_sessions = Vec::new();
+
         for sess_dir in session_dirs.split(':') {
             let sess_dir_path = Path::new(sess_dir);
             let sess_parent_dir = if let Some(sess_parent_dir) = sess_dir_path.parent() {
@@ -171,35 +669,93 @@ impl SysUtil {
             } else {
                 false
             };
-            let cmd_prefix = if is_x11 {
-                Some(&config.get_sys_commands().x11_prefix)
+            let (sess_type, cmd_prefix) = if is_x11 {
+                (SessionType::X11, Some(x11_prefix))
             } else {
-                None
+                (SessionType::Wayland, None)
+            };
+
+            match metadata(sess_dir_path) {
+                Ok(info) => {
+                    if !scanned_dirs.insert((info.dev(), info.ino())) {
+                        debug!(
+                            path = sess_dir,
+                            "Session directory was already scanned, skipping it"
+                        );
+                        continue;
+                    }
+                }
+                Err(err) => {
+                    debug!(path = sess_dir, error = %err, "Skipping session directory");
+                    continue;
+                }
             };
 
             debug!("Checking session directory: {sess_dir}");
             // Iterate over all '.desktop' files.
-            for glob_path in glob(&format!("{sess_dir}/*.desktop"))
+            for (index, glob_path) in glob(&format!("{sess_dir}/*.desktop"))
                 .expect("Invalid glob pattern for session desktop files")
+                .enumerate()
             {
+                if index == MAX_SESSION_FILES {
+                    warn!(path = sess_dir, "Too many session files, skipping the rest");
+                    break;
+                }
                 let path = match glob_path {
                     Ok(path) => path,
                     Err(err) => {
-                        warn!("Error when globbing: {err}");
+                        warn!(error = %err, "Error when globbing");
                         continue;
                     }
                 };
                 info!("Now scanning session file: {}", path.display());
 
-                let contents = read(&path)?;
-                let text = from_utf8(contents.as_slice()).unwrap_or_else(|err| {
-                    panic!("Session file '{}' is not UTF-8: {}", path.display(), err)
-                });
+                // One unreadable file mustn't hide the other sessions.
+                let info = match metadata(&path) {
+                    Ok(info) => info,
+                    Err(err) => {
+                        warn!(path = %path.display(), error = %err, "Couldn't read session file");
+                        continue;
+                    }
+                };
+                if !info.is_file() {
+                    warn!(path = %path.display(), "Session file is not a regular file");
+                    continue;
+                } else if info.len() > MAX_SESSION_FILE_SIZE {
+                    warn!(path = %path.display(), "Session file is too large");
+                    continue;
+                }
+
+                let contents = match read(&path) {
+                    Ok(contents) => contents,
+                    Err(err) => {
+                        warn!(path = %path.display(), error = %err, "Couldn't read session file");
+                        continue;
+                    }
+                };
+                let text = String::from_utf8_lossy(contents.as_slice());
+                let mut malformed = false;
+                if let Cow::Owned(_) = text {
+                    warn!(
+                        "Session file is not UTF-8, replacing invalid characters: {}",
+                        path.display()
+                    );
+                    malformed = true;
+                }
+
+                let (entry, duplicated) = desktop_entry(&text);
+                if duplicated {
+                    warn!(
+                        "Session file has multiple {DESKTOP_ENTRY_HEADER} sections, using the first: {}",
+                        path.display()
+                    );
+                    malformed = true;
+                }
 
                 let fname_and_type = match path.strip_prefix(sess_parent_dir) {
                     Ok(fname_and_type) => fname_and_type.to_owned(),
                     Err(err) => {
-                        warn!("Error with file name: {err}");
+                        warn!(error = %err, "Error with file name");
                         continue;
                     }
                 };
@@ -212,117 +768,48 @@ impl SysUtil {
                     continue;
                 };
 
-                // The session launch command is specified as: Exec=command arg1 arg2...
-                let cmd_regex =
-                    Regex::new(r"Exec=(.*)").expect("Invalid regex for session command");
-                // The session name is specified as: Name=My Session
-                let name_regex = Regex::new(r"Name=(.*)").expect("Invalid regex for session name");
-
-                // Hiding could be either as Hidden=true or NoDisplay=true
-                let hidden_regex = Regex::new(r"Hidden=(.*)").expect("Invalid regex for hidden");
-                let no_display_regex =
-                    Regex::new(r"NoDisplay=(.*)").expect("Invalid regex for no display");
-
-                let hidden: bool = if let Some(hidden_str) = hidden_regex
-                    .captures(text)
-                    .and_then(|capture| capture.get(1))
-                {
-                    hidden_str.as_str().parse().unwrap_or(false)
-                } else {
-                    false
-                };
-
-                let no_display: bool = if let Some(no_display_str) = no_display_regex
-                    .captures(text)
-                    .and_then(|capture| capture.get(1))
-                {
-                    no_display_str.as_str().parse().unwrap_or(false)
-                } else {
-                    false
-                };
-
-                if hidden | no_display {
+                let is_shown = path
+                    .file_name()
+                    .map_or(true, |id| filter.is_shown(&id.to_string_lossy()));
+                if !is_shown {
+                    info!(path = %path.display(), "Session file is hidden by the config");
+                    // Also hide desktop files with the same ID in later directories.
                     found_session_names.insert(fname_and_type);
                     continue;
-                };
+                }
 
-                // Parse the desktop file to get the session command.
-                let cmd = if let Some(cmd_str) =
-                    cmd_regex.captures(text).and_then(|capture| capture.get(1))
-                {
-                    let mut cmd = if let Some(prefix) = cmd_prefix {
-                        prefix.clone()
-                    } else {
-                        Vec::new()
-                    };
-                    let prefix_len = cmd.len();
-                    cmd.extend(Shlex::new(cmd_str.as_str()));
-                    if cmd.len() > prefix_len {
-                        cmd
-                    } else {
-                        warn!(
-                            "Couldn't split command of '{}' into arguments: {}",
-                            path.display(),
-                            cmd_str.as_str()
-                        );
-                        // Skip the desktop file, since a missing command means that we can't
-                        // use it.
-                        continue;
-                    }
-                } else {
-                    warn!("No command found for session: {}", path.display());
-                    // Skip the desktop file, since a missing command means that we can't use it.
-                    continue;
-                };
+                if malformed {
+                    malformed_sessions.push(path.clone());
+                }
 
-                // Get the full name of this session.
-                let name = if let Some(name) =
-                    name_regex.captures(text).and_then(|capture| capture.get(1))
-                {
-                    debug!(
-                        "Found name '{}' for session '{}' with command '{:?}'",
-                        name.as_str(),
-                        path.display(),
-                        cmd
-                    );
-                    name.as_str()
-                } else if let Some(stem) = path.file_stem() {
-                    // Get the stem of the filename of this desktop file.
-                    // This is used as backup, in case the file name doesn't exist.
-                    if let Some(stem) = stem.to_str() {
-                        debug!(
-                            "Using file stem '{stem}', since no name was found for session: {}",
-                            path.display()
-                        );
-                        stem
-                    } else {
-                        warn!("Non-UTF-8 file stem in session file: {}", path.display());
-                        // No way to display this session name, so just skip it.
-                        continue;
+                match SessionInfo::parse(entry, &path, sess_type, cmd_prefix) {
+                    ParsedSession::Visible(name, info) => {
+                        // Sessions with the same name, eg. the Wayland and X11 variants of a desktop, are told apart
+                        // by their desktop file.
+                        let name = if sessions.contains_key(&name) {
+                            let label = format!("{name} ({})", fname_and_type.display());
+                            info!(
+                                path = %path.display(),
+                                label = %label,
+                                "Session name is already used, adding the desktop file to it"
+                            );
+                            label
+                        } else {
+                            name
+                        };
+                        found_session_names.insert(fname_and_type);
+                        sessions.insert(name, info);
                     }
-                } else {
-                    warn!("No file stem found for session: {}", path.display());
-                    // No file stem implies no file name, which shouldn't happen.
-                    // Since there's no full name nor file stem, just skip this anomalous
-                    // session.
-                    continue;
+                    ParsedSession::Hidden => {
+                        found_session_names.insert(fname_and_type);
+                    }
+                    // Skip the desktop file, since it can't be used.
+                    ParsedSession::Invalid => continue,
                 };
-                found_session_names.insert(fname_and_type);
-                sessions.insert(
-                    name.to_string(),
-                    SessionInfo {
-                        command: cmd,
-                        sess_type: if is_x11 {
-                            SessionType::X11
-                        } else {
-                            SessionType::Wayland
-                        },
-                    },
-                );
             }
         }
 
-        Ok(sessions)
+        Ok((sessions, malformed_sessions))
     }
 
     /// Get the mapping of a user's full name to their system username.
@@ -343,6 +830,33 @@ impl SysUtil {
     pub fn get_sessions(&self) -> &SessionMap {
         &self.sessions
     }
+
+    /// Get the name of the session read from the desktop file with the given ID, eg. "sway.desktop".
+    ///
+    /// If sessions from several directories have the ID, the one with the shortest name is used, which is the one that
+    /// isn't disambiguated by its desktop file.
+    pub fn get_session_by_id(&self, id: &str) -> Option<&str> {
+        self.sessions
+            .iter()
+            .filter(|(_, info)| {
+                let file_name = info.path.as_ref().and_then(|path| path.file_name());
+                file_name.is_some_and(|file_name| file_name == id)
+            })
+            .map(|(name, _)| name.as_str())
+            .min()
+    }
+
+    /// Get the session files that could only be partially read, eg. due to invalid UTF-8.
+    pub fn get_malformed_sessions(&self) -> &[PathBuf] {
+        &self.malformed_sessions
+    }
+
+    /// Check whether the given username belongs to root or a system user, eg. one that was manually entered.
+    ///
+    /// Unknown users aren't considered system users, since they might be known to PAM (eg. through LDAP).
+    pub fn is_system_user(&self, username: &str) -> bool {
+        lookup_user(username).is_some_and(|entry| !self.normal_user.is_normal_user(entry.uid))
+    }
 }
 
 /// A named tuple of min and max that stores UID limits for normal users.
@@ -350,7 +864,7 @@ impl SysUtil {
 /// Use [`Self::parse_login_defs`] to obtain the system configuration. If the file is missing or there are
 /// parsing errors a fallback of [`Self::default`] should be used.
 #[derive(Debug, PartialEq, Eq)]
-struct NormalUser {
+pub struct NormalUser {
     uid_min: u64,
     uid_max: u64,
 }
@@ -499,4 +1013,241 @@ mod tests {
             NormalUser::parse_number(num)
         }
     }
+
+    #[allow(non_snake_case)]
+    mod DesktopFile {
+        use super::super::*;
+        use crate::testutil::TempDir;
+
+        #[test_case(
+            "[Desktop Entry]\nName=Sway\nExec=sway --unsupported-gpu"
+            => ParsedSession::Visible(
+                "Sway".to_string(),
+                SessionInfo {
+                    command: vec!["sway".to_string(), "--unsupported-gpu".to_string()],
+                    sess_type: SessionType::Wayland,
+                    path: Some("/usr/share/wayland-sessions/sway.desktop".into()),
+                },
+            );
+            "name and command"
+        )]
+        #[test_case(
+            "[Desktop Entry]\nExec=sway"
+            => ParsedSession::Visible(
+                "sway".to_string(),
+                SessionInfo {
+                    command: vec!["sway".to_string()],
+                    sess_type: SessionType::Wayland,
+                    path: Some("/usr/share/wayland-sessions/sway.desktop".into()),
+                },
+            );
+            "file stem as name"
+        )]
+        #[test_case("[Desktop Entry]\nName=Sway\nExec=sway\nHidden=true" => ParsedSession::Hidden; "hidden")]
+        #[test_case(
+            "[Desktop Entry]\nName=Sway\nExec=sway\nNoDisplay=true"
+            => ParsedSession::Hidden;
+            "no display"
+        )]
+        #[test_case("[Desktop Entry]\nName=Sway" => ParsedSession::Invalid; "missing command")]
+        #[test_case("[Desktop Entry]\nName=Sway\nExec=\"" => ParsedSession::Invalid; "unlexable command")]
+        fn parse(text: &str) -> ParsedSession {
+            SessionInfo::parse(
+                text,
+                Path::new("/usr/share/wayland-sessions/sway.desktop"),
+                SessionType::Wayland,
+                None,
+            )
+        }
+
+        #[test]
+        fn x11_prefix() {
+            let prefix = ["startx".to_string(), "/usr/bin/env".to_string()];
+            let parsed = SessionInfo::parse(
+                "[Desktop Entry]\nName=i3\nExec=i3",
+                Path::new("/usr/share/xsessions/i3.desktop"),
+                SessionType::X11,
+                Some(&prefix),
+            );
+
+            assert_eq!(
+                parsed,
+                ParsedSession::Visible(
+                    "i3".to_string(),
+                    SessionInfo {
+                        command: vec!["startx".into(), "/usr/bin/env".into(), "i3".into()],
+                        sess_type: SessionType::X11,
+                        path: Some("/usr/share/xsessions/i3.desktop".into()),
+                    },
+                )
+            );
+        }
+
+        #[test]
+        fn scan_skips_non_sessions() {
+            use std::fs::{create_dir_all, write};
+
+            let dir = TempDir::new("scan-sessions");
+            let sess_dir = dir.join("wayland-sessions");
+            create_dir_all(sess_dir.join("dir.desktop"))
+                .expect("Couldn't create session directory");
+            write(
+                sess_dir.join("sway.desktop"),
+                "[Desktop Entry]\nName=Sway\nExec=sway",
+            )
+            .expect("Couldn't create session file");
+            write(
+                sess_dir.join("large.desktop"),
+                vec![b'#'; MAX_SESSION_FILE_SIZE as usize + 1],
+            )
+            .expect("Couldn't create session file");
+            std::os::unix::fs::symlink(dir.join("missing"), sess_dir.join("broken.desktop"))
+                .expect("Couldn't create broken session file");
+
+            let sess_dir = sess_dir.to_str().unwrap();
+            let sessions = SysUtil::scan_sessions(
+                &format!("{sess_dir}:{sess_dir}"),
+                &[],
+                &SessionFilter::default(),
+            );
+
+            let (sessions, malformed) = sessions.expect("Couldn't scan sessions");
+            assert_eq!(sessions.keys().collect::<Vec<_>>(), ["Sway"]);
+            assert!(malformed.is_empty());
+        }
+
+        #[test]
+        fn scan_malformed() {
+            use std::fs::{create_dir_all, write};
+
+            let dir = TempDir::new("scan-malformed");
+            let sess_dir = dir.join("wayland-sessions");
+            create_dir_all(&sess_dir).expect("Couldn't create session directory");
+            let path = sess_dir.join("cafe.desktop");
+            write(&path, b"[Desktop Entry]\nName=Caf\xe9\nExec=cafe")
+                .expect("Couldn't create session file");
+
+            let sessions =
+                SysUtil::scan_sessions(sess_dir.to_str().unwrap(), &[], &SessionFilter::default());
+
+            let (sessions, malformed) = sessions.expect("Couldn't scan sessions");
+            assert_eq!(sessions.keys().collect::<Vec<_>>(), ["Caf\u{FFFD}"]);
+            assert_eq!(malformed, [path]);
+        }
+
+        #[test]
+        fn scan_same_name() {
+            use std::fs::{create_dir_all, write};
+
+            let dir = TempDir::new("scan-same-name");
+            for sess_dir in ["wayland-sessions", "xsessions"] {
+                create_dir_all(dir.join(sess_dir)).expect("Couldn't create session directory");
+                write(
+                    dir.join(sess_dir).join("plasma.desktop"),
+                    "[Desktop Entry]\nName=Plasma\nExec=startplasma",
+                )
+                .expect("Couldn't create session file");
+            }
+
+            let sessions = SysUtil::scan_sessions(
+                &format!("{0}/wayland-sessions:{0}/xsessions", dir.display()),
+                &[],
+                &SessionFilter::default(),
+            );
+
+            let (sessions, _) = sessions.expect("Couldn't scan sessions");
+            assert_eq!(sessions.len(), 2);
+            assert_eq!(sessions["Plasma"].sess_type, SessionType::Wayland);
+            assert_eq!(
+                sessions["Plasma (xsessions/plasma.desktop)"].sess_type,
+                SessionType::X11
+            );
+        }
+
+        #[test_case("Name=Sway\nExec=sway" => ("Name=Sway\nExec=sway", false); "no header")]
+        #[test_case(
+            "# Comment\n[Desktop Entry]\nExec=sway\n[Desktop Action x]\nExec=x"
+            => ("Exec=sway\n", false);
+            "other section"
+        )]
+        #[test_case(
+            "[Desktop Entry]\nExec=sway\n[Desktop Entry]\nExec=i3"
+            => ("Exec=sway\n", true);
+            "duplicated"
+        )]
+        fn desktop_entry(text: &str) -> (&str, bool) {
+            super::super::desktop_entry(text)
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod HomeDir {
+        use std::fs::{create_dir_all, write};
+
+        use super::super::*;
+        use crate::testutil::TempDir;
+
+        #[test]
+        fn dir_is_empty() {
+            let parent = TempDir::new("home");
+            let dir = parent.join("home");
+            let missing = super::super::dir_is_empty(&dir);
+            create_dir_all(&dir).expect("Couldn't create test directory");
+            let empty = super::super::dir_is_empty(&dir);
+            write(dir.join(".bashrc"), "").expect("Couldn't create test file");
+            let used = super::super::dir_is_empty(&dir);
+
+            assert_eq!(missing, Some(true));
+            assert_eq!(empty, Some(true));
+            assert_eq!(used, Some(false));
+        }
+    }
+
+    #[allow(non_snake_case)]
+    mod Users {
+        use super::super::*;
+
+        fn entry(name: &[u8], uid: u32, gecos: Option<&[u8]>, shell: &[u8]) -> PasswdEntry {
+            PasswdEntry {
+                name: OsStr::from_bytes(name).into(),
+                uid,
+                gid: uid,
+                gecos: gecos.map(|gecos| OsStr::from_bytes(gecos).into()),
+                dir: PathBuf::from("/home/user"),
+                shell: OsStr::from_bytes(shell).into(),
+            }
+        }
+
+        #[test]
+        fn collect_users() {
+            let normal_user = NormalUser {
+                uid_min: 1000,
+                uid_max: 2000,
+            };
+            let entries = [
+                entry(b"root", 0, Some(b"root"), b"/bin/sh"),
+                entry(b"alice", 1000, Some(b"Alice,,,"), b"/bin/bash"),
+                entry(b"b\xf6b", 1001, Some(b"Bob"), b"/bin/sh"),
+                entry(b"carol", 1002, Some(b"Car\xf6l"), b"/bin/sh"),
+                entry(b"dave", 1003, None, b"/opt/sh\xf6ll"),
+            ];
+            let (users, shells) = super::super::collect_users(entries, &normal_user);
+
+            let users: HashMap<_, _> = users
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            assert_eq!(
+                users,
+                HashMap::from([
+                    ("Alice", "alice"),
+                    ("Car\u{fffd}l", "carol"),
+                    ("dave", "dave")
+                ])
+            );
+            let mut shell_users: Vec<_> = shells.keys().map(String::as_str).collect();
+            shell_users.sort_unstable();
+            assert_eq!(shell_users, ["alice", "carol"]);
+        }
+    }
 }
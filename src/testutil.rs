@@ -0,0 +1,48 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Helpers shared by the unit tests
+
+use std::env;
+use std::fs::{create_dir_all, remove_dir_all};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Empty directory for a test, which is removed with everything in it when it's dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a directory that's unique to this test, even if other tests with the same name run in parallel.
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("regreet-{name}-{}-{count}", process::id()));
+        // Remove what's left of an earlier run with the same process ID.
+        let _ = remove_dir_all(&path);
+        create_dir_all(&path).expect("Couldn't create test directory");
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
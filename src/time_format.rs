@@ -0,0 +1,133 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Formatting of the times shown at the greeter
+//!
+//! The clock, the last login of users and scheduled shutdowns all show times of day in the same format, which is
+//! either set in the config, or follows the 12-hour or 24-hour clock of the locale. The logs aren't formatted here,
+//! since they always use ISO 8601 timestamps.
+
+use std::ffi::CStr;
+
+use jiff::{fmt::strtime, tz::TimeZone, Timestamp, Zoned};
+use serde::Deserialize;
+
+/// Format of times of day with a 24-hour clock
+const FORMAT_24H: &str = "%H:%M";
+/// Format of times of day with a 12-hour clock
+const FORMAT_12H: &str = "%-I:%M %p";
+/// Format of dates, which are shown before the time of day for times that aren't soon
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Whether times of day are shown with a 12-hour or a 24-hour clock
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HourCycle {
+    /// Use the clock of the locale.
+    #[default]
+    Locale,
+    #[serde(rename = "12h")]
+    H12,
+    #[serde(rename = "24h")]
+    H24,
+}
+
+impl HourCycle {
+    /// Get the hour cycle of the locale's time format.
+    ///
+    /// This needs the locale to be set up, which GTK does while initializing.
+    fn from_locale() -> Self {
+        // SAFETY: `nl_langinfo` returns a valid string, which is only changed by later calls to it or to `setlocale`.
+        let format = unsafe { CStr::from_ptr(libc::nl_langinfo(libc::T_FMT)) };
+        Self::from_locale_format(&format.to_string_lossy())
+    }
+
+    /// Get the hour cycle of a time format in the locale's database, eg. "%I:%M:%S %p" for "en_US".
+    fn from_locale_format(format: &str) -> Self {
+        if ["%I", "%l", "%r"].iter().any(|spec| format.contains(spec)) {
+            Self::H12
+        } else {
+            Self::H24
+        }
+    }
+}
+
+/// Formatter for the times shown at the greeter
+#[derive(Clone, Debug)]
+pub struct TimeFormatter {
+    /// strftime format of times of day
+    format: String,
+    timezone: TimeZone,
+}
+
+impl TimeFormatter {
+    /// Create a formatter, where an explicit format of times of day overrides the hour cycle.
+    pub fn new(format: Option<&str>, hour_cycle: HourCycle, timezone: TimeZone) -> Self {
+        let format = if let Some(format) = format {
+            format.to_string()
+        } else {
+            let hour_cycle = if hour_cycle == HourCycle::Locale {
+                HourCycle::from_locale()
+            } else {
+                hour_cycle
+            };
+            match hour_cycle {
+                HourCycle::H12 => FORMAT_12H.to_string(),
+                HourCycle::Locale | HourCycle::H24 => FORMAT_24H.to_string(),
+            }
+        };
+        Self { format, timezone }
+    }
+
+    /// Get the strftime format of times of day.
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+
+    /// Format the time of day, eg. "14:30".
+    ///
+    /// Invalid formats are reported by the config check, so this just falls back to a 24-hour clock for them.
+    pub fn time(&self, time: Timestamp) -> String {
+        let time = Zoned::new(time, self.timezone.clone());
+        strtime::format(&self.format, &time)
+            .or_else(|_| strtime::format(FORMAT_24H, &time))
+            .unwrap_or_default()
+    }
+
+    /// Format the date and the time of day, eg. "2024-05-01 14:30".
+    pub fn date_time(&self, time: Timestamp) -> String {
+        let date = strtime::format(DATE_FORMAT, &Zoned::new(time, self.timezone.clone()))
+            .unwrap_or_default();
+        format!("{date} {}", self.time(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case("%I:%M:%S %p" => HourCycle::H12; "en_US")]
+    #[test_case("%r" => HourCycle::H12; "locale 12-hour format")]
+    #[test_case("%H:%M:%S" => HourCycle::H24; "de_DE")]
+    #[test_case("%T" => HourCycle::H24; "C")]
+    fn from_locale_format(format: &str) -> HourCycle {
+        HourCycle::from_locale_format(format)
+    }
+
+    #[test_case(None, HourCycle::H24 => "15:04"; "24-hour")]
+    #[test_case(None, HourCycle::H12 => "3:04 PM"; "12-hour")]
+    #[test_case(Some("%H.%M"), HourCycle::H12 => "15.04"; "explicit format")]
+    #[test_case(Some("%H:%"), HourCycle::H12 => "15:04"; "invalid format")]
+    fn time(format: Option<&str>, hour_cycle: HourCycle) -> String {
+        let time = Timestamp::from_second(15 * 3600 + 4 * 60).unwrap();
+        TimeFormatter::new(format, hour_cycle, TimeZone::UTC).time(time)
+    }
+
+    #[test]
+    fn date_time() {
+        let formatter = TimeFormatter::new(None, HourCycle::H24, TimeZone::UTC);
+        let time = Timestamp::from_second(2 * 24 * 3600 + 3600).unwrap();
+        assert_eq!(formatter.date_time(time), "1970-01-03 01:00");
+    }
+}
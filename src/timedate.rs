@@ -0,0 +1,131 @@
+// SPDX-FileCopyrightText: 2024 max-ishere <47008271+max-ishere@users.noreply.github.com>
+//
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Checks whether the system clock looks wrong
+//!
+//! Logins that need the clock to be accurate (eg. Kerberos or Active Directory) fail with unhelpful errors if it's off,
+//! so the greeter warns about it. The clock is wrong if it's before the greeter was built, and it may be off if NTP is
+//! enabled in systemd-timedated but hasn't synchronized it.
+
+use jiff::{SignedDuration, Timestamp};
+use relm4::gtk::{
+    gio,
+    glib::{self, ToVariant, Variant, VariantTy},
+};
+
+use crate::time_format::TimeFormatter;
+
+/// Name of the timedated D-Bus service, which is also the name of its interface
+const TIMEDATE_NAME: &str = "org.freedesktop.timedate1";
+/// Object path of timedated
+const TIMEDATE_PATH: &str = "/org/freedesktop/timedate1";
+/// Time to wait for timedated to respond
+const TIMEDATE_TIMEOUT_MS: i32 = 1000;
+/// Time by which the clock may be before the build, eg. if the greeter was built in another time zone
+const BUILD_TIME_TOLERANCE: SignedDuration = SignedDuration::from_hours(24);
+
+#[derive(thiserror::Error, Debug)]
+pub enum TimedateError {
+    #[error("D-Bus error: {0}")]
+    DBus(#[from] glib::Error),
+    #[error("Unexpected reply from timedated")]
+    UnexpectedReply,
+}
+
+/// Why the system clock looks wrong
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockProblem {
+    /// The clock is set before the greeter was built.
+    BeforeBuild,
+    /// NTP is enabled, but hasn't synchronized the clock.
+    Unsynchronized,
+}
+
+impl ClockProblem {
+    /// Describe the problem for the user.
+    pub fn describe(self, now: Timestamp, time_formatter: &TimeFormatter) -> String {
+        match self {
+            Self::BeforeBuild => format!(
+                "The system clock is set to {}, which is wrong, so logins may fail",
+                time_formatter.date_time(now)
+            ),
+            Self::Unsynchronized => {
+                "The system clock isn't synchronized yet, so logins may fail if it's wrong"
+                    .to_string()
+            }
+        }
+    }
+}
+
+/// Get when the greeter was built.
+pub fn build_time() -> Timestamp {
+    env!("REGREET_BUILD_TIME")
+        .parse()
+        .ok()
+        .and_then(|secs| Timestamp::from_second(secs).ok())
+        .unwrap_or(Timestamp::UNIX_EPOCH)
+}
+
+/// Check whether the clock is set before the build.
+pub fn is_before_build(now: Timestamp, build_time: Timestamp) -> bool {
+    build_time.duration_since(now) > BUILD_TIME_TOLERANCE
+}
+
+/// Check whether NTP is enabled in timedated, but hasn't synchronized the clock.
+///
+/// Machines without NTP (eg. offline ones) aren't expected to be synchronized, so they aren't reported.
+pub async fn is_unsynchronized() -> Result<bool, TimedateError> {
+    let connection = gio::bus_get_future(gio::BusType::System).await?;
+    let mut properties = [false; 2];
+    for (property, value) in ["NTP", "NTPSynchronized"].into_iter().zip(&mut properties) {
+        let reply = connection
+            .call_future(
+                Some(TIMEDATE_NAME),
+                TIMEDATE_PATH,
+                "org.freedesktop.DBus.Properties",
+                "Get",
+                Some(&(TIMEDATE_NAME, property).to_variant()),
+                Some(VariantTy::new("(v)").expect("Invalid variant type")),
+                gio::DBusCallFlags::NONE,
+                TIMEDATE_TIMEOUT_MS,
+            )
+            .await?;
+        let (variant,): (Variant,) = reply.get().ok_or(TimedateError::UnexpectedReply)?;
+        *value = variant.get().ok_or(TimedateError::UnexpectedReply)?;
+    }
+    let [ntp, synchronized] = properties;
+    Ok(ntp && !synchronized)
+}
+
+/// Find out whether the system clock looks wrong.
+///
+/// If timedated can't be asked (eg. since it isn't running), only the build time is checked.
+pub async fn check_clock(now: Timestamp) -> Option<ClockProblem> {
+    if is_before_build(now, build_time()) {
+        return Some(ClockProblem::BeforeBuild);
+    }
+    match is_unsynchronized().await {
+        Ok(true) => Some(ClockProblem::Unsynchronized),
+        Ok(false) => None,
+        Err(err) => {
+            debug!(error = %err, "Couldn't ask timedated whether the clock is synchronized");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case(0 => false; "at build")]
+    #[test_case(-3600 => false; "an hour before")]
+    #[test_case(-3 * 24 * 3600 => true; "days before")]
+    #[test_case(365 * 24 * 3600 => false; "after")]
+    fn is_before_build(offset_secs: i64) -> bool {
+        let build_time = Timestamp::from_second(1_700_000_000).unwrap();
+        let now = Timestamp::from_second(1_700_000_000 + offset_secs).unwrap();
+        super::is_before_build(now, build_time)
+    }
+}
@@ -26,7 +26,7 @@ pub enum TomlFileError {
 pub type TomlFileResult<T> = Result<T, TomlFileError>;
 
 /// Load the TOML file from disk without any checks.
-fn load_raw_toml<T: DeserializeOwned>(path: &Path) -> TomlFileResult<T> {
+pub(crate) fn load_raw_toml<T: DeserializeOwned>(path: &Path) -> TomlFileResult<T> {
     Ok(toml::from_str(std::str::from_utf8(
         read(path)?.as_slice(),
     )?)?)
@@ -48,7 +48,7 @@ where
                 item
             }
             Err(err) => {
-                warn!("Error loading TOML file '{}': {err}", path.display());
+                warn!(path = %path.display(), error = %err, "Error loading TOML file");
                 R::default()
             }
         }